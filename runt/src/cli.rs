@@ -1,4 +1,4 @@
-use clap::{crate_authors, crate_name, crate_version, App, Arg, SubCommand};
+use clap::{crate_authors, crate_name, crate_version, App, AppSettings, Arg, SubCommand};
 
 pub fn app_config<'a>() -> App<'a, 'a> {
     // Initialize Application
@@ -9,8 +9,10 @@ pub fn app_config<'a>() -> App<'a, 'a> {
 
     // Root options {{
     let root_arg = Arg::with_name("root")
+        .help("directory for the container states (default: /run/runt, $XDG_RUNTIME_DIR/runt when rootless)")
         .required(false)
         .takes_value(true)
+        .global(true)
         .long("root");
 
     let debug_flag = Arg::with_name("debug").takes_value(false).long("debug");
@@ -44,6 +46,16 @@ pub fn app_config<'a>() -> App<'a, 'a> {
     let container_id_arg = Arg::with_name("container-id")
         .required(true)
         .takes_value(true);
+
+    let no_pivot_flag = Arg::with_name("no-pivot")
+        .help("chroot into the rootfs instead of pivot_root, for ramfs")
+        .takes_value(false)
+        .long("no-pivot");
+
+    let ignore_unknown_caps_flag = Arg::with_name("ignore-unknown-caps")
+        .help("warn about unknown capabilities in the spec instead of failing")
+        .takes_value(false)
+        .long("ignore-unknown-caps");
     // }}
 
     // SubCommands {{
@@ -52,6 +64,8 @@ pub fn app_config<'a>() -> App<'a, 'a> {
         .arg(&bundle_arg)
         .arg(&pid_file_arg)
         .arg(&console_socket_arg)
+        .arg(&no_pivot_flag)
+        .arg(&ignore_unknown_caps_flag)
         .arg(&container_id_arg);
 
     let start_command = SubCommand::with_name("start")
@@ -63,6 +77,15 @@ pub fn app_config<'a>() -> App<'a, 'a> {
         .arg(&bundle_arg)
         .arg(&pid_file_arg)
         .arg(&console_socket_arg)
+        .arg(
+            Arg::with_name("detach")
+                .takes_value(false)
+                .long("detach")
+                .short("d"),
+        )
+        .arg(Arg::with_name("keep").takes_value(false).long("keep"))
+        .arg(&no_pivot_flag)
+        .arg(&ignore_unknown_caps_flag)
         .arg(&container_id_arg);
 
     let state_command = SubCommand::with_name("state")
@@ -82,12 +105,164 @@ pub fn app_config<'a>() -> App<'a, 'a> {
 
     let kill_command = SubCommand::with_name("kill")
         .about("kill container")
+        .arg(
+            Arg::with_name("all")
+                .required(false)
+                .takes_value(false)
+                .long("all")
+                .short("a"),
+        )
         .arg(&container_id_arg)
         .arg(Arg::with_name("signal").takes_value(true).required(true));
 
+    let exec_command = SubCommand::with_name("exec")
+        .about("execute new process inside the container")
+        .setting(AppSettings::TrailingVarArg)
+        .arg(
+            Arg::with_name("process")
+                .takes_value(true)
+                .long("process")
+                .short("p"),
+        )
+        .arg(
+            Arg::with_name("tty")
+                .takes_value(false)
+                .long("tty")
+                .short("t"),
+        )
+        .arg(
+            Arg::with_name("detach")
+                .takes_value(false)
+                .long("detach")
+                .short("d"),
+        )
+        .arg(&pid_file_arg)
+        .arg(&container_id_arg)
+        .arg(Arg::with_name("command").multiple(true));
+
+    let list_command = SubCommand::with_name("list")
+        .about("list containers")
+        .arg(
+            Arg::with_name("format")
+                .takes_value(true)
+                .possible_values(&["table", "json"])
+                .long("format")
+                .short("f"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .takes_value(false)
+                .long("quiet")
+                .short("q"),
+        );
+
+    let ps_command = SubCommand::with_name("ps")
+        .about("list processes in container")
+        .setting(AppSettings::TrailingVarArg)
+        .setting(AppSettings::AllowLeadingHyphen)
+        .arg(
+            Arg::with_name("format")
+                .takes_value(true)
+                .possible_values(&["table", "json"])
+                .long("format")
+                .short("f"),
+        )
+        .arg(&container_id_arg)
+        .arg(Arg::with_name("ps-options").multiple(true));
+
+    let pause_command = SubCommand::with_name("pause")
+        .about("pause all processes in container")
+        .arg(&container_id_arg);
+
+    let resume_command = SubCommand::with_name("resume")
+        .about("resume all processes in container")
+        .arg(&container_id_arg);
+
+    let events_command = SubCommand::with_name("events")
+        .about("display container events and resource usage")
+        .arg(
+            Arg::with_name("interval")
+                .takes_value(true)
+                .long("interval"),
+        )
+        .arg(Arg::with_name("stats").takes_value(false).long("stats"))
+        .arg(&container_id_arg);
+
+    let update_command = SubCommand::with_name("update")
+        .about("update container resource limits")
+        .arg(
+            Arg::with_name("resources")
+                .takes_value(true)
+                .long("resources")
+                .short("r"),
+        )
+        .args(
+            &[
+                "memory",
+                "memory-reservation",
+                "memory-swap",
+                "cpu-shares",
+                "cpu-quota",
+                "cpu-period",
+                "cpuset-cpus",
+                "cpuset-mems",
+                "pids-limit",
+                "blkio-weight",
+            ]
+            .iter()
+            .map(|name| Arg::with_name(name).takes_value(true).long(name))
+            .collect::<Vec<_>>(),
+        )
+        .arg(&container_id_arg);
+
+    let image_path_arg = Arg::with_name("image-path")
+        .takes_value(true)
+        .long("image-path");
+
+    let work_path_arg = Arg::with_name("work-path")
+        .takes_value(true)
+        .long("work-path");
+
+    let tcp_established_flag = Arg::with_name("tcp-established")
+        .takes_value(false)
+        .long("tcp-established");
+
+    let checkpoint_command = SubCommand::with_name("checkpoint")
+        .about("checkpoint a running container")
+        .arg(&image_path_arg)
+        .arg(&work_path_arg)
+        .arg(&tcp_established_flag)
+        .arg(
+            Arg::with_name("leave-running")
+                .takes_value(false)
+                .long("leave-running"),
+        )
+        .arg(&container_id_arg);
+
+    let restore_command = SubCommand::with_name("restore")
+        .about("restore a container from a checkpoint")
+        .arg(&image_path_arg)
+        .arg(&work_path_arg)
+        .arg(&tcp_established_flag)
+        .arg(&bundle_arg)
+        .arg(&pid_file_arg)
+        .arg(
+            Arg::with_name("detach")
+                .takes_value(false)
+                .long("detach")
+                .short("d"),
+        )
+        .arg(&container_id_arg);
+
     let spec_command = SubCommand::with_name("spec")
         .about("generate spec file")
-        .arg(&bundle_arg);
+        .arg(&bundle_arg)
+        .arg(
+            Arg::with_name("rootless")
+                .help("generate a spec for a container run by the current user")
+                .takes_value(false)
+                .long("rootless"),
+        );
     // }}
 
     app.arg(root_arg)
@@ -100,6 +275,15 @@ pub fn app_config<'a>() -> App<'a, 'a> {
         .subcommand(state_command)
         .subcommand(delete_command)
         .subcommand(kill_command)
+        .subcommand(exec_command)
+        .subcommand(list_command)
+        .subcommand(ps_command)
+        .subcommand(pause_command)
+        .subcommand(resume_command)
+        .subcommand(events_command)
+        .subcommand(update_command)
+        .subcommand(checkpoint_command)
+        .subcommand(restore_command)
         .subcommand(spec_command)
 }
 
@@ -127,6 +311,8 @@ mod test {
             "container.pid",
             "--bundle",
             "./",
+            "--no-pivot",
+            "--ignore-unknown-caps",
             "container-a",
         ];
 
@@ -164,6 +350,10 @@ mod test {
             "container.pid",
             "--console-socket",
             "console.sock",
+            "--detach",
+            "--keep",
+            "--no-pivot",
+            "--ignore-unknown-caps",
             "container-a",
         ];
 
@@ -226,6 +416,102 @@ mod test {
             .expect_err("should be error");
     }
 
+    #[test]
+    fn test_exec_command() {
+        let args = vec!["runt", "exec", "-t", "container-a", "sh", "-c", "ls -l"];
+
+        app_config()
+            .get_matches_from_safe(&args)
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn test_exec_command_must_specify_container_id() {
+        let args = vec!["runt", "exec"];
+
+        app_config()
+            .get_matches_from_safe(&args)
+            .expect_err("should be error");
+    }
+
+    #[test]
+    fn test_root_option_should_reach_subcommand() {
+        let args = vec!["runt", "--root", "/run/custom", "list"];
+
+        let app_matches = app_config()
+            .get_matches_from_safe(&args)
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+        let matches = app_matches.subcommand_matches("list").unwrap();
+        assert_eq!(matches.value_of("root"), Some("/run/custom"));
+    }
+
+    #[test]
+    fn test_list_command() {
+        let args = vec!["runt", "list", "--format", "json"];
+
+        app_config()
+            .get_matches_from_safe(&args)
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn test_list_command_must_specify_known_format() {
+        let args = vec!["runt", "list", "--format", "yaml"];
+
+        app_config()
+            .get_matches_from_safe(&args)
+            .expect_err("should be error");
+    }
+
+    #[test]
+    fn test_ps_command() {
+        let args = vec!["runt", "ps", "container-a", "-eo", "pid,comm"];
+
+        app_config()
+            .get_matches_from_safe(&args)
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn test_pause_and_resume_command_must_specify_container_id() {
+        for subcommand in &["pause", "resume"] {
+            app_config()
+                .get_matches_from_safe(["runt", subcommand, "container-a"])
+                .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+            app_config()
+                .get_matches_from_safe(["runt", subcommand])
+                .expect_err("should be error");
+        }
+    }
+
+    #[test]
+    fn test_events_command() {
+        let args = vec!["runt", "events", "--interval", "10s", "container-a"];
+
+        app_config()
+            .get_matches_from_safe(&args)
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
+    #[test]
+    fn test_update_command() {
+        let args = vec![
+            "runt",
+            "update",
+            "--memory",
+            "512m",
+            "--cpu-quota",
+            "50000",
+            "--cpuset-cpus",
+            "0-1",
+            "container-a",
+        ];
+
+        app_config()
+            .get_matches_from_safe(&args)
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+    }
+
     #[test]
     fn test_spec_command() {
         let args = vec!["runt", "spec"];
@@ -234,4 +520,15 @@ mod test {
             .get_matches_from_safe(&args)
             .unwrap_or_else(|e| panic!("An error occurs: {}", e));
     }
+
+    #[test]
+    fn test_spec_command_with_rootless() {
+        let args = vec!["runt", "spec", "--rootless"];
+
+        let matches = app_config()
+            .get_matches_from_safe(&args)
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+        let (_, spec_matches) = matches.subcommand();
+        assert!(spec_matches.unwrap().is_present("rootless"));
+    }
 }
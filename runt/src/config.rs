@@ -1 +1,60 @@
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use nix::unistd::geteuid;
+
 pub static SPEC_FILE: &str = "config.json";
+
+pub static DEFAULT_ROOT: &str = "/run/runt";
+pub static ROOTLESS_ROOT_DIR: &str = "runt";
+
+/// Whether runt runs as a user other than root, who can only use what has
+/// been delegated to them.
+pub fn is_rootless() -> bool {
+    !geteuid().is_root()
+}
+
+/// Directory holding the container states: `root` when given, otherwise
+/// /run/runt for root and $XDG_RUNTIME_DIR/runt for the other users.
+pub fn state_root(root: Option<&str>) -> Result<PathBuf> {
+    let root = match root {
+        Some(root) => PathBuf::from(root),
+        None if !is_rootless() => PathBuf::from(DEFAULT_ROOT),
+        None => match env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir).join(ROOTLESS_ROOT_DIR),
+            _ => bail!("XDG_RUNTIME_DIR is not set, specify the state directory with --root"),
+        },
+    };
+    // The init changes its working directory, so keep the path absolute.
+    if root.is_relative() {
+        return Ok(env::current_dir()?.join(root));
+    }
+    Ok(root)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn root_flag_should_be_preferred() {
+        let root = state_root(Some("/var/run/custom")).unwrap();
+        assert_eq!(root, PathBuf::from("/var/run/custom"));
+
+        let root = state_root(Some("relative")).unwrap();
+        assert_eq!(root, env::current_dir().unwrap().join("relative"));
+    }
+
+    #[test]
+    fn default_root_should_depend_on_user() {
+        if geteuid().is_root() {
+            assert_eq!(state_root(None).unwrap(), PathBuf::from(DEFAULT_ROOT));
+        } else if let Some(dir) = env::var_os("XDG_RUNTIME_DIR") {
+            assert_eq!(
+                state_root(None).unwrap(),
+                PathBuf::from(dir).join(ROOTLESS_ROOT_DIR)
+            );
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::{bail, Result};
+use nix::errno::Errno;
+
+use crate::container::specs::{Capability, LinuxCapabilities};
+
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// Number of `cap`, e.g. 5 for CAP_KILL.
+pub fn number(cap: &Capability) -> Result<u64> {
+    cap.check()?;
+    let number = Capability::VALUES
+        .iter()
+        .position(|name| *name == cap.as_str())
+        .expect("known capabilities must be listed: ");
+    Ok(number as u64)
+}
+
+/// Check the capability names of every set against the running kernel.
+/// With `ignore_unknown`, the names it doesn't know are reported and left
+/// out rather than rejected, for specs written for newer kernels.
+pub fn check(caps: &mut LinuxCapabilities, ignore_unknown: bool) -> Result<()> {
+    let last_cap = last_cap()?;
+    for set in [
+        &mut caps.bounding,
+        &mut caps.effective,
+        &mut caps.inheritable,
+        &mut caps.permitted,
+        &mut caps.ambient,
+    ] {
+        let caps = match set {
+            Some(caps) => caps,
+            None => continue,
+        };
+        let mut unknown = vec![];
+        for cap in caps.iter() {
+            let error = match number(cap) {
+                Ok(number) if number <= last_cap => continue,
+                Ok(_) => format!("capability {} is not supported by the kernel", cap),
+                Err(e) => e.to_string(),
+            };
+            if !ignore_unknown {
+                bail!(error);
+            }
+            eprintln!("warning: {}, ignoring it", error);
+            unknown.push(cap.clone());
+        }
+        caps.retain(|cap| !unknown.contains(cap));
+    }
+    Ok(())
+}
+
+fn to_mask(caps: &Option<Vec<Capability>>) -> Result<u64> {
+    let mut mask = 0;
+    for cap in caps.iter().flatten() {
+        mask |= 1 << number(cap)?;
+    }
+    Ok(mask)
+}
+
+/// Highest capability the kernel knows. /proc may not be mounted in the
+/// container, so probe the bounding set rather than read cap_last_cap.
+fn last_cap() -> Result<u64> {
+    let mut cap = 0;
+    loop {
+        let ret = unsafe { libc::prctl(libc::PR_CAPBSET_READ, cap + 1, 0, 0, 0) };
+        match Errno::result(ret) {
+            Ok(_) => cap += 1,
+            Err(nix::Error::Sys(Errno::EINVAL)) => return Ok(cap),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Shrink the bounding set to `bounding`. Needs CAP_SETPCAP, so it must
+/// run before switching to the process user.
+pub fn drop_bounding(caps: &LinuxCapabilities) -> Result<()> {
+    let bounding = to_mask(&caps.bounding)?;
+    for cap in 0..=last_cap()? {
+        if bounding & (1 << cap) != 0 {
+            continue;
+        }
+        let ret = unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) };
+        Errno::result(ret)?;
+    }
+    Ok(())
+}
+
+/// Set the effective, permitted and inheritable sets and raise the ambient set.
+pub fn apply(caps: &LinuxCapabilities) -> Result<()> {
+    let effective = to_mask(&caps.effective)?;
+    let permitted = to_mask(&caps.permitted)?;
+    let inheritable = to_mask(&caps.inheritable)?;
+
+    let mut header = CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let mut data = [CapUserData::default(); 2];
+    for (i, data) in data.iter_mut().enumerate() {
+        data.effective = (effective >> (32 * i)) as u32;
+        data.permitted = (permitted >> (32 * i)) as u32;
+        data.inheritable = (inheritable >> (32 * i)) as u32;
+    }
+    let ret = unsafe { libc::syscall(libc::SYS_capset, &mut header, data.as_mut_ptr()) };
+    Errno::result(ret)?;
+
+    for cap in caps.ambient.iter().flatten() {
+        let number = number(cap)?;
+        let ret = unsafe {
+            libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_RAISE,
+                number,
+                0,
+                0,
+            )
+        };
+        if let Err(e) = Errno::result(ret) {
+            bail!("failed to raise ambient capability {}: {}", cap, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn capability_number_should_be_resolved() {
+        assert_eq!(number(&Capability::Chown).unwrap(), 0);
+        assert_eq!(number(&Capability::Kill).unwrap(), 5);
+        assert_eq!(number(&"CAP_NET_BIND_SERVICE".into()).unwrap(), 10);
+        assert_eq!(number(&Capability::CheckpointRestore).unwrap(), 40);
+    }
+
+    #[test]
+    fn last_cap_should_match_kernel() {
+        let expected = std::fs::read_to_string("/proc/sys/kernel/cap_last_cap").unwrap();
+        assert_eq!(last_cap().unwrap(), expected.trim().parse::<u64>().unwrap());
+    }
+
+    #[test]
+    fn unknown_capability_should_be_rejected() {
+        assert!(number(&"CAP_FOO".into()).is_err());
+        assert!(number(&"kill".into()).is_err());
+    }
+
+    #[test]
+    fn unknown_capability_should_be_ignored_in_compat_mode() {
+        let names = Some(vec!["CAP_KILL".into(), "CAP_FOO".into()]);
+        let mut caps = LinuxCapabilities {
+            bounding: names.clone(),
+            effective: None,
+            inheritable: None,
+            permitted: names,
+            ambient: Some(vec!["CAP_CHOWN".into()]),
+        };
+
+        let err = check(&mut caps.clone(), false).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("unknown capability: CAP_FOO (expected one of CAP_CHOWN, "));
+
+        check(&mut caps, true).unwrap();
+        assert_eq!(caps.bounding, Some(vec!["CAP_KILL".into()]));
+        assert_eq!(caps.permitted, Some(vec!["CAP_KILL".into()]));
+        assert_eq!(caps.ambient, Some(vec!["CAP_CHOWN".into()]));
+        assert_eq!(caps.effective, None);
+    }
+}
@@ -0,0 +1,466 @@
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use nix::sys::eventfd::{eventfd, EfdFlags};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use nix::unistd::{geteuid, Pid, Uid};
+
+use crate::container::specs::Spec;
+
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+const PROC_CGROUP: &str = "/proc/self/cgroup";
+const CGROUP_PROCS: &str = "cgroup.procs";
+const CGROUP_CONTROLLERS: &str = "cgroup.controllers";
+const CGROUP_SUBTREE_CONTROL: &str = "cgroup.subtree_control";
+const DEFAULT_CGROUP_PARENT: &str = "/runt";
+const FREEZER_STATE: &str = "freezer.state";
+const CGROUP_FREEZE: &str = "cgroup.freeze";
+const CGROUP_EVENTS: &str = "cgroup.events";
+const MEMORY_OOM_CONTROL: &str = "memory.oom_control";
+const CGROUP_EVENT_CONTROL: &str = "cgroup.event_control";
+const MEMORY_EVENTS: &str = "memory.events";
+const FREEZE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A mounted cgroup hierarchy.
+#[derive(Clone, Debug)]
+struct Hierarchy {
+    mount_point: PathBuf,
+    // Empty on cgroup v2, where every controller shares one hierarchy.
+    subsystems: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CgroupVersion {
+    V1,
+    V2,
+}
+
+/// The cgroup a container's processes are placed in.
+#[derive(Clone, Debug)]
+pub struct Cgroup {
+    /// Path of the container cgroup inside each hierarchy.
+    pub path: PathBuf,
+    hierarchies: Vec<Hierarchy>,
+}
+
+impl Cgroup {
+    pub fn new(container_id: &str, spec: &Spec) -> Result<Self> {
+        let cgroups_path = spec
+            .linux
+            .as_ref()
+            .and_then(|linux| linux.cgroups_path.clone())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CGROUP_PARENT).join(container_id));
+
+        let hierarchies = mounted_hierarchies()?;
+        Ok(Cgroup {
+            path: cgroups_path,
+            hierarchies,
+        })
+    }
+
+    /// Absolute directories of the container cgroup, one per hierarchy.
+    pub fn paths(&self) -> Result<Vec<PathBuf>> {
+        self.hierarchies.iter().map(|h| self.path_in(h)).collect()
+    }
+
+    /// Whether we may create the container cgroup in every hierarchy, as
+    /// root may, or a user in a subtree delegated to them.
+    pub fn is_delegated(&self) -> Result<bool> {
+        self.is_delegated_to(geteuid())
+    }
+
+    /// A subtree is delegated to `uid` when it owns the nearest existing
+    /// ancestor of the container cgroup and its cgroup.procs, which moving
+    /// processes in needs.
+    fn is_delegated_to(&self, uid: Uid) -> Result<bool> {
+        if self.hierarchies.is_empty() {
+            return Ok(false);
+        }
+        if uid.is_root() {
+            return Ok(true);
+        }
+        for hierarchy in &self.hierarchies {
+            let path = self.path_in(hierarchy)?;
+            let existing = match path.ancestors().find(|path| path.exists()) {
+                Some(existing) => existing,
+                None => return Ok(false),
+            };
+            for path in &[existing.to_path_buf(), existing.join(CGROUP_PROCS)] {
+                match fs::metadata(path) {
+                    Ok(metadata) if metadata.uid() == uid.as_raw() => {}
+                    _ => return Ok(false),
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Create the container cgroup and move `pid` into it.
+    pub fn apply(&self, pid: Pid) -> Result<()> {
+        for hierarchy in &self.hierarchies {
+            let path = self.path_in(hierarchy)?;
+            fs::create_dir_all(&path)
+                .with_context(|| format!("failed to create cgroup {}", path.display()))?;
+            if hierarchy.subsystems.is_empty() {
+                enable_controllers(&hierarchy.mount_point, &path)?;
+            }
+            if hierarchy.subsystems.iter().any(|s| s == "cpuset") {
+                inherit_cpuset(&hierarchy.mount_point, &path)?;
+            }
+            fs::write(path.join(CGROUP_PROCS), pid.to_string())
+                .with_context(|| format!("failed to join cgroup {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Processes currently in the container cgroup.
+    pub fn pids(&self) -> Result<Vec<Pid>> {
+        let path = match self.hierarchies.first() {
+            Some(hierarchy) => self.path_in(hierarchy)?,
+            None => return Ok(vec![]),
+        };
+        let procs = match fs::read_to_string(path.join(CGROUP_PROCS)) {
+            Ok(procs) => procs,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        procs
+            .lines()
+            .map(|pid| Ok(Pid::from_raw(pid.trim().parse()?)))
+            .collect()
+    }
+
+    /// Remove the container cgroup. Its processes must already be gone.
+    pub fn remove(&self) -> Result<()> {
+        for path in self.paths()? {
+            if !path.exists() {
+                continue;
+            }
+            // Killed processes leave the cgroup asynchronously.
+            let mut retry = 0;
+            loop {
+                match fs::remove_dir(&path) {
+                    Ok(()) => break,
+                    Err(e) if e.raw_os_error() == Some(libc::EBUSY) && retry < 50 => {
+                        retry += 1;
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                    Err(e) => {
+                        return Err(e)
+                            .with_context(|| format!("failed to remove cgroup {}", path.display()))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Freeze every process of the cgroup and wait until they are stopped.
+    pub fn freeze(&self) -> Result<()> {
+        if let Err(e) = self.set_frozen(true) {
+            // Do not leave the container half frozen.
+            self.set_frozen(false).ok();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Thaw the processes stopped by `freeze`.
+    pub fn thaw(&self) -> Result<()> {
+        self.set_frozen(false)
+    }
+
+    /// Hierarchies are either all v1 or a single v2 one.
+    pub fn version(&self) -> CgroupVersion {
+        match self.hierarchies.first() {
+            Some(hierarchy) if hierarchy.subsystems.is_empty() => CgroupVersion::V2,
+            _ => CgroupVersion::V1,
+        }
+    }
+
+    /// Directory of the container cgroup holding the files of `subsystem`,
+    /// if that subsystem is mounted. On v2 every subsystem shares one.
+    pub fn subsystem_path(&self, subsystem: &str) -> Result<Option<PathBuf>> {
+        let hierarchy = self
+            .hierarchies
+            .iter()
+            .find(|h| h.subsystems.is_empty() || h.subsystems.iter().any(|s| s == subsystem));
+        hierarchy.map(|h| self.path_in(h)).transpose()
+    }
+
+    /// Send to `sender` each time the OOM killer hits the cgroup, until the
+    /// cgroup is removed.
+    pub fn notify_oom(&self, sender: Sender<()>) -> Result<()> {
+        let path = self
+            .subsystem_path("memory")?
+            .ok_or_else(|| anyhow!("memory cgroup is not mounted"))?;
+        match self.version() {
+            CgroupVersion::V1 => notify_oom_v1(path, sender),
+            CgroupVersion::V2 => notify_oom_v2(path, sender),
+        }
+    }
+
+    fn set_frozen(&self, frozen: bool) -> Result<()> {
+        let path = self
+            .subsystem_path("freezer")?
+            .ok_or_else(|| anyhow!("freezer cgroup is not mounted"))?;
+
+        let start = Instant::now();
+        if self.version() == CgroupVersion::V2 {
+            fs::write(path.join(CGROUP_FREEZE), if frozen { "1" } else { "0" })?;
+            let expected = if frozen { "frozen 1" } else { "frozen 0" };
+            while !fs::read_to_string(path.join(CGROUP_EVENTS))?
+                .lines()
+                .any(|line| line == expected)
+            {
+                wait_for_freezer(start)?;
+            }
+        } else {
+            let state = if frozen { "FROZEN" } else { "THAWED" };
+            loop {
+                // v1 may give up freezing and fall back to THAWED, so keep
+                // asking until the state sticks.
+                fs::write(path.join(FREEZER_STATE), state)?;
+                if fs::read_to_string(path.join(FREEZER_STATE))?.trim() == state {
+                    break;
+                }
+                wait_for_freezer(start)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path_in(&self, hierarchy: &Hierarchy) -> Result<PathBuf> {
+        let relative = if self.path.is_absolute() {
+            self.path.strip_prefix("/")?.to_path_buf()
+        } else {
+            // Relative paths are relative to the cgroup runt itself is in.
+            own_cgroup(hierarchy)?.strip_prefix("/")?.join(&self.path)
+        };
+        Ok(hierarchy.mount_point.join(relative))
+    }
+}
+
+/// v1 signals an eventfd registered for memory.oom_control.
+fn notify_oom_v1(path: PathBuf, sender: Sender<()>) -> Result<()> {
+    let event_fd = eventfd(0, EfdFlags::EFD_CLOEXEC)?;
+    let mut event = unsafe { File::from_raw_fd(event_fd) };
+    let oom_control = File::open(path.join(MEMORY_OOM_CONTROL))?;
+    fs::write(
+        path.join(CGROUP_EVENT_CONTROL),
+        format!("{} {}", event_fd, oom_control.as_raw_fd()),
+    )?;
+
+    thread::spawn(move || {
+        let _oom_control = oom_control;
+        let mut count = [0; 8];
+        // The eventfd also fires when the cgroup is removed.
+        while event.read_exact(&mut count).is_ok() && path.exists() {
+            if sender.send(()).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// v2 counts OOM kills in memory.events, which raises a modify event on change.
+fn notify_oom_v2(path: PathBuf, sender: Sender<()>) -> Result<()> {
+    let events = path.join(MEMORY_EVENTS);
+    let oom_kills = move || -> Option<u64> {
+        let content = fs::read_to_string(&events).ok()?;
+        content
+            .lines()
+            .find(|line| line.starts_with("oom_kill "))
+            .and_then(|line| line["oom_kill ".len()..].trim().parse().ok())
+    };
+    let inotify = Inotify::init(InitFlags::IN_CLOEXEC)?;
+    inotify.add_watch(&path.join(MEMORY_EVENTS), AddWatchFlags::IN_MODIFY)?;
+    let mut last = oom_kills().unwrap_or(0);
+
+    thread::spawn(move || {
+        while inotify.read_events().is_ok() {
+            let current = match oom_kills() {
+                Some(current) => current,
+                None => break,
+            };
+            if current > last && sender.send(()).is_err() {
+                break;
+            }
+            last = current;
+        }
+    });
+    Ok(())
+}
+
+fn wait_for_freezer(start: Instant) -> Result<()> {
+    if start.elapsed() > FREEZE_TIMEOUT {
+        bail!("timed out waiting for the cgroup freezer");
+    }
+    thread::sleep(Duration::from_millis(10));
+    Ok(())
+}
+
+fn mounted_hierarchies() -> Result<Vec<Hierarchy>> {
+    let mountinfo = fs::read_to_string(MOUNTINFO)?;
+    let mut v1 = Vec::new();
+    let mut v2 = Vec::new();
+    for line in mountinfo.lines() {
+        // <id> <parent> <major:minor> <root> <mount point> <options> ... - <fstype> <source> <super options>
+        let mut halves = line.splitn(2, " - ");
+        let fields: Vec<&str> = halves.next().unwrap_or_default().split(' ').collect();
+        let tail: Vec<&str> = halves.next().unwrap_or_default().split(' ').collect();
+        if fields.len() < 5 || tail.len() < 3 {
+            continue;
+        }
+        let mount_point = PathBuf::from(fields[4]);
+        match tail[0] {
+            "cgroup" => v1.push(Hierarchy {
+                mount_point,
+                subsystems: tail[2]
+                    .split(',')
+                    .filter(|opt| !matches!(*opt, "rw" | "ro") && !opt.starts_with("release_agent"))
+                    .map(String::from)
+                    .collect(),
+            }),
+            "cgroup2" => v2.push(Hierarchy {
+                mount_point,
+                subsystems: vec![],
+            }),
+            _ => {}
+        }
+    }
+
+    // Hybrid hosts mount both; the v1 controllers are the ones in use there.
+    if !v1.is_empty() {
+        Ok(v1)
+    } else {
+        v2.truncate(1);
+        Ok(v2)
+    }
+}
+
+fn own_cgroup(hierarchy: &Hierarchy) -> Result<PathBuf> {
+    let cgroups = fs::read_to_string(PROC_CGROUP)?;
+    for line in cgroups.lines() {
+        // <hierarchy id>:<subsystems>:<path>
+        let fields: Vec<&str> = line.splitn(3, ':').collect();
+        if fields.len() != 3 {
+            continue;
+        }
+        let matched = if hierarchy.subsystems.is_empty() {
+            fields[0] == "0"
+        } else {
+            fields[1]
+                .split(',')
+                .any(|s| hierarchy.subsystems.iter().any(|h| h == s))
+        };
+        if matched {
+            return Ok(PathBuf::from(fields[2]));
+        }
+    }
+    Ok(PathBuf::from("/"))
+}
+
+/// On v2 a controller is only usable in a cgroup once every ancestor has
+/// delegated it. Delegate whatever is available down to `path`.
+fn enable_controllers(mount_point: &Path, path: &Path) -> Result<()> {
+    let relative = path.strip_prefix(mount_point)?;
+    let mut current = mount_point.to_path_buf();
+    for component in relative.components() {
+        let controllers = fs::read_to_string(current.join(CGROUP_CONTROLLERS))?;
+        for controller in controllers.split_whitespace() {
+            // Controllers may be unavailable to delegate, e.g. with
+            // realtime processes in the cgroup, and are then left out.
+            fs::write(
+                current.join(CGROUP_SUBTREE_CONTROL),
+                format!("+{}", controller),
+            )
+            .ok();
+        }
+        current.push(component);
+    }
+    Ok(())
+}
+
+/// A new cpuset cgroup starts with no cpus or mems, which rejects every
+/// process. Copy them down from the closest configured ancestor.
+fn inherit_cpuset(mount_point: &Path, path: &Path) -> Result<()> {
+    let relative = path.strip_prefix(mount_point)?;
+    let mut current = mount_point.to_path_buf();
+    for component in relative.components() {
+        let parent = current.clone();
+        current.push(component);
+        for file in &["cpuset.cpus", "cpuset.mems"] {
+            let value = fs::read_to_string(current.join(file))?;
+            if value.trim().is_empty() {
+                fs::write(current.join(file), fs::read_to_string(parent.join(file))?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::container::specs::Spec;
+
+    #[test]
+    fn default_path_should_be_under_runt() {
+        let cgroup = Cgroup::new("container-a", &Spec::default()).unwrap();
+        assert_eq!(cgroup.path, PathBuf::from("/runt/container-a"));
+    }
+
+    #[test]
+    fn delegation_should_need_owned_cgroup_procs() {
+        use nix::unistd::{chown, Gid};
+
+        let mount_point = tempfile::tempdir().unwrap().into_path();
+        let user = Uid::from_raw(1000);
+        for (dir, procs_owner) in &[("owned", user), ("not-owned", Uid::from_raw(0))] {
+            let dir = mount_point.join(dir);
+            fs::create_dir(&dir).unwrap();
+            fs::write(dir.join(CGROUP_PROCS), "").unwrap();
+            chown(&dir, Some(user), Some(Gid::from_raw(1000))).unwrap();
+            chown(&dir.join(CGROUP_PROCS), Some(*procs_owner), None).unwrap();
+        }
+        let cgroup = |path: &str| Cgroup {
+            path: path.into(),
+            hierarchies: vec![Hierarchy {
+                mount_point: mount_point.clone(),
+                subsystems: vec![],
+            }],
+        };
+
+        assert!(cgroup("/owned/a/b").is_delegated_to(user).unwrap());
+        assert!(!cgroup("/not-owned/a").is_delegated_to(user).unwrap());
+        assert!(!cgroup("/a").is_delegated_to(user).unwrap());
+        assert!(cgroup("/a").is_delegated_to(Uid::from_raw(0)).unwrap());
+        let mut cgroup = cgroup("/owned/a");
+        cgroup.hierarchies.clear();
+        assert!(!cgroup.is_delegated_to(Uid::from_raw(0)).unwrap());
+        fs::remove_dir_all(&mount_point).unwrap();
+    }
+
+    #[test]
+    fn cgroups_path_should_be_used() {
+        let mut spec = Spec::default();
+        spec.linux.as_mut().unwrap().cgroups_path = Some("/custom/container-a".into());
+
+        let cgroup = Cgroup::new("container-a", &spec).unwrap();
+        assert_eq!(cgroup.path, PathBuf::from("/custom/container-a"));
+        for path in cgroup.paths().unwrap() {
+            assert!(path.ends_with("custom/container-a"));
+        }
+    }
+}
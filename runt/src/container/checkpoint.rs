@@ -0,0 +1,314 @@
+use std::fs::{self, File};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use nix::errno::Errno;
+use nix::unistd::Pid;
+
+use crate::container::specs::{LinuxNamespaceType, Spec, Status};
+use crate::container::{start_time, Container, MetadataManager};
+
+const CRIU: &str = "criu";
+const DUMP_LOG: &str = "dump.log";
+const RESTORE_LOG: &str = "restore.log";
+const RESTORE_PID_FILE: &str = "restore.pid";
+const EXTERNAL_NETWORK_KEY: &str = "extRootNetNS";
+
+/// Options shared by `checkpoint` and `restore`.
+#[derive(Clone, Debug, Default)]
+pub struct CheckpointOptions {
+    /// Directory holding the criu images.
+    pub image_path: PathBuf,
+    /// Directory for criu logs, the image directory when unset.
+    pub work_path: Option<PathBuf>,
+    pub leave_running: bool,
+    pub tcp_established: bool,
+}
+
+impl CheckpointOptions {
+    fn work_path(&self) -> &Path {
+        self.work_path.as_deref().unwrap_or(&self.image_path)
+    }
+
+    fn common_args(&self, spec: &Spec, bundle: &Path, log: &str) -> Vec<String> {
+        let mut args = vec![
+            "--images-dir".into(),
+            self.image_path.display().to_string(),
+            "--work-dir".into(),
+            self.work_path().display().to_string(),
+            "--log-file".into(),
+            log.into(),
+            "-v4".into(),
+            "--root".into(),
+            bundle.join(&spec.root.path).display().to_string(),
+            "--manage-cgroups".into(),
+        ];
+        if self.tcp_established {
+            args.push("--tcp-established".into());
+        }
+        args
+    }
+}
+
+/// Bind mounts come from outside the container and have to be described
+/// to criu as external, by destination.
+fn bind_mounts(spec: &Spec) -> impl Iterator<Item = &crate::container::specs::Mount> {
+    spec.mounts.iter().filter(|mount| {
+        mount.type_ == "bind"
+            || mount
+                .options
+                .iter()
+                .flatten()
+                .any(|option| option == "bind" || option == "rbind")
+    })
+}
+
+/// Path of a network namespace the container joined rather than created.
+fn external_network(spec: &Spec) -> Option<&str> {
+    spec.linux
+        .as_ref()?
+        .namespaces
+        .iter()
+        .flatten()
+        .find(|ns| ns.type_ == LinuxNamespaceType::Network)?
+        .path
+        .as_deref()
+}
+
+fn run_criu(args: &[String], log: PathBuf) -> Result<()> {
+    let status = match Command::new(CRIU).args(args).status() {
+        Ok(status) => status,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            bail!("{} is not installed or not in PATH", CRIU)
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !status.success() {
+        bail!("criu {} failed, see {}", args[0], log.display());
+    }
+    Ok(())
+}
+
+impl Container {
+    fn dump_args(&self, options: &CheckpointOptions) -> Result<Vec<String>> {
+        let pid = self
+            .pid
+            .ok_or_else(|| anyhow!("container pid is not set"))?;
+        let mut args = vec!["dump".into(), "--tree".into(), pid.to_string()];
+        args.extend(options.common_args(&self.spec, &self.bundle, DUMP_LOG));
+        if options.leave_running {
+            args.push("--leave-running".into());
+        }
+        if let Some(freezer) = self.cgroup()?.subsystem_path("freezer")? {
+            args.push("--freeze-cgroup".into());
+            args.push(freezer.display().to_string());
+        }
+        for mount in bind_mounts(&self.spec) {
+            args.push("--ext-mount-map".into());
+            args.push(format!("{}:{}", mount.destination, mount.destination));
+        }
+        if let Some(path) = external_network(&self.spec) {
+            let inode = fs::metadata(path)?.ino();
+            args.push("--external".into());
+            args.push(format!("net[{}]:{}", inode, EXTERNAL_NETWORK_KEY));
+        }
+        Ok(args)
+    }
+
+    fn restore_args(&self, options: &CheckpointOptions) -> Result<Vec<String>> {
+        let cgroup = self.cgroup()?;
+        let mut args = vec!["restore".into(), "--restore-detached".into()];
+        args.extend(options.common_args(&self.spec, &self.bundle, RESTORE_LOG));
+        args.push("--pidfile".into());
+        args.push(
+            options
+                .work_path()
+                .join(RESTORE_PID_FILE)
+                .display()
+                .to_string(),
+        );
+        // Put the restored processes in the new container's cgroup
+        // instead of the one they were dumped from.
+        args.push("--cgroup-root".into());
+        args.push(cgroup.path.display().to_string());
+        for mount in bind_mounts(&self.spec) {
+            args.push("--ext-mount-map".into());
+            let source = self.bundle.join(&mount.source);
+            args.push(format!("{}:{}", mount.destination, source.display()));
+        }
+        Ok(args)
+    }
+
+    /// Dump the processes of the running container into `image_path`.
+    /// Unless `leave_running` is set, the container is stopped afterwards.
+    pub fn checkpoint(&mut self, options: &CheckpointOptions) -> Result<()> {
+        if self.status != Status::Running {
+            bail!("cannot checkpoint a container in {} status", self.status);
+        }
+        fs::create_dir_all(&options.image_path)?;
+        fs::create_dir_all(options.work_path())?;
+
+        let args = self.dump_args(options)?;
+        run_criu(&args, options.work_path().join(DUMP_LOG))?;
+
+        if !options.leave_running {
+            self.status = Status::Stopped;
+            self.save_metadata(self)?;
+        }
+        Ok(())
+    }
+
+    /// Restore the processes dumped by `checkpoint` as this new container
+    /// and return the pid of its init.
+    pub fn restore(&mut self, options: &CheckpointOptions) -> Result<Pid> {
+        if self.status != Status::Creating {
+            bail!("cannot restore into a container in {} status", self.status);
+        }
+        fs::create_dir_all(options.work_path())?;
+        let args = self.restore_args(options)?;
+
+        // Keep the namespace fd open and at a known number while criu runs.
+        let network = external_network(&self.spec)
+            .map(|path| File::open(path).with_context(|| format!("failed to open {}", path)))
+            .transpose()?;
+        let mut command = Command::new(CRIU);
+        command.args(&args);
+        if let Some(network) = &network {
+            command.args(&[
+                "--inherit-fd".into(),
+                format!("fd[{}]:{}", network.as_raw_fd(), EXTERNAL_NETWORK_KEY),
+            ]);
+            clear_cloexec(network)?;
+        }
+
+        self.create_state_dir()?;
+        self.save_metadata(self)?;
+        let status = match command.status() {
+            Ok(status) => status,
+            Err(e) => {
+                self.remove_metadata()?;
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    bail!("{} is not installed or not in PATH", CRIU);
+                }
+                return Err(e.into());
+            }
+        };
+        if !status.success() {
+            self.remove_metadata()?;
+            bail!(
+                "criu restore failed, see {}",
+                options.work_path().join(RESTORE_LOG).display()
+            );
+        }
+
+        let pid_file = options.work_path().join(RESTORE_PID_FILE);
+        let pid: i32 = fs::read_to_string(&pid_file)?.trim().parse()?;
+        fs::remove_file(&pid_file)?;
+        if let Some(resources) = self.spec.linux.as_ref().and_then(|l| l.resources.as_ref()) {
+            self.cgroup()?.set(resources)?;
+        }
+
+        self.status = Status::Running;
+        self.pid = Some(pid);
+        self.start_time = start_time(Pid::from_raw(pid)).ok();
+        self.created = Some(Utc::now());
+        self.save_metadata(self)?;
+        Ok(Pid::from_raw(pid))
+    }
+}
+
+fn clear_cloexec(file: &File) -> Result<()> {
+    let ret = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETFD, 0) };
+    Errno::result(ret)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::container::specs::Mount;
+    use crate::container::testutil;
+
+    fn init_container() -> Container {
+        let mut spec = Spec::default();
+        spec.root.path = "rootfs".into();
+        spec.mounts.push(Mount {
+            destination: "/data".into(),
+            type_: "none".into(),
+            source: "/srv/data".into(),
+            options: Some(vec!["rbind".into(), "ro".into()]),
+        });
+        let mut container = Container::new(
+            testutil::TEST_ROOT.as_ref(),
+            "container-a",
+            Path::new("/bundle"),
+            spec,
+        );
+        container.status = Status::Running;
+        container.pid = Some(42);
+        container
+    }
+
+    fn value_of<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+        args.iter()
+            .position(|arg| arg == flag)
+            .map(|i| args[i + 1].as_str())
+    }
+
+    #[test]
+    fn dump_args_should_describe_container() {
+        let container = init_container();
+        let options = CheckpointOptions {
+            image_path: "/images".into(),
+            leave_running: true,
+            tcp_established: true,
+            ..CheckpointOptions::default()
+        };
+
+        let args = container.dump_args(&options).unwrap();
+        assert_eq!(args[0], "dump");
+        assert_eq!(value_of(&args, "--tree"), Some("42"));
+        assert_eq!(value_of(&args, "--images-dir"), Some("/images"));
+        assert_eq!(value_of(&args, "--work-dir"), Some("/images"));
+        assert_eq!(value_of(&args, "--root"), Some("/bundle/rootfs"));
+        assert_eq!(value_of(&args, "--ext-mount-map"), Some("/data:/data"));
+        assert!(args.contains(&"--leave-running".into()));
+        assert!(args.contains(&"--tcp-established".into()));
+    }
+
+    #[test]
+    fn restore_args_should_map_mounts_and_cgroup() {
+        let mut container = init_container();
+        container.status = Status::Creating;
+        let options = CheckpointOptions {
+            image_path: "/images".into(),
+            work_path: Some("/work".into()),
+            ..CheckpointOptions::default()
+        };
+
+        let args = container.restore_args(&options).unwrap();
+        assert_eq!(args[0], "restore");
+        assert_eq!(value_of(&args, "--work-dir"), Some("/work"));
+        assert_eq!(value_of(&args, "--pidfile"), Some("/work/restore.pid"));
+        assert_eq!(value_of(&args, "--cgroup-root"), Some("/runt/container-a"));
+        assert_eq!(value_of(&args, "--ext-mount-map"), Some("/data:/srv/data"));
+        assert!(!args.contains(&"--tcp-established".into()));
+    }
+
+    #[test]
+    fn stopped_container_should_not_be_checkpointed() {
+        let mut container = init_container();
+        container.status = Status::Stopped;
+        let options = CheckpointOptions {
+            image_path: "/images".into(),
+            ..CheckpointOptions::default()
+        };
+
+        assert!(container.checkpoint(&options).is_err());
+    }
+}
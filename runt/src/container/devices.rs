@@ -0,0 +1,249 @@
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use nix::errno::Errno;
+use nix::mount::{mount, MsFlags};
+use nix::sys::stat::{makedev, SFlag};
+use nix::unistd::{fchownat, isatty, symlinkat, unlinkat, FchownatFlags, Gid, Uid, UnlinkatFlags};
+
+use crate::container::securejoin::RootedPath;
+use crate::container::specs::{LinuxDevice, LinuxNamespaceType, Spec};
+
+const NONE: Option<&'static str> = None;
+
+/// Symlinks every /dev has, from the link to its target.
+const DEV_SYMLINKS: &[(&str, &str)] = &[
+    ("/dev/fd", "/proc/self/fd"),
+    ("/dev/stdin", "/proc/self/fd/0"),
+    ("/dev/stdout", "/proc/self/fd/1"),
+    ("/dev/stderr", "/proc/self/fd/2"),
+    ("/dev/ptmx", "pts/ptmx"),
+];
+
+/// Devices created in every container besides the ones of the spec.
+pub fn default_devices() -> Vec<LinuxDevice> {
+    [
+        ("/dev/null", 1, 3),
+        ("/dev/zero", 1, 5),
+        ("/dev/full", 1, 7),
+        ("/dev/random", 1, 8),
+        ("/dev/urandom", 1, 9),
+        ("/dev/tty", 5, 0),
+    ]
+    .iter()
+    .map(|&(path, major, minor)| LinuxDevice {
+        path: path.into(),
+        type_: "c".into(),
+        major,
+        minor,
+        file_mode: Some(0o666),
+        uid: Some(0),
+        gid: Some(0),
+    })
+    .collect()
+}
+
+/// Populate /dev in `rootfs` with the default devices, the devices of the
+/// spec and the usual symlinks. Nothing is done when /dev is bind mounted
+/// from the host.
+pub fn setup(spec: &Spec, rootfs: &Path) -> Result<()> {
+    let binds_dev = spec.mounts.iter().any(|mount| {
+        mount.destination.trim_end_matches('/') == "/dev"
+            && (mount.type_ == "bind"
+                || mount
+                    .options
+                    .iter()
+                    .flatten()
+                    .any(|option| option == "bind" || option == "rbind"))
+    });
+    if binds_dev {
+        return Ok(());
+    }
+
+    let linux = spec.linux.as_ref();
+    let in_user_namespace = linux
+        .and_then(|linux| linux.namespaces.as_ref())
+        .is_some_and(|namespaces| {
+            namespaces
+                .iter()
+                .any(|ns| ns.type_ == LinuxNamespaceType::User)
+        });
+    let spec_devices = linux
+        .and_then(|linux| linux.devices.as_deref())
+        .unwrap_or_default();
+    let defaults = default_devices();
+    let defaults = defaults
+        .iter()
+        .filter(|device| spec_devices.iter().all(|d| d.path != device.path));
+    for device in defaults.chain(spec_devices) {
+        // Device nodes cannot be created in a user namespace, so the ones of
+        // the host are used instead.
+        let result = if in_user_namespace {
+            bind_device(device, rootfs)
+        } else {
+            create_device(device, rootfs)
+        };
+        result.with_context(|| format!("failed to create device {}", device.path))?;
+    }
+
+    for (link, target) in DEV_SYMLINKS {
+        let (parent, name) = RootedPath::create_parent(rootfs, Path::new(link))?;
+        match symlinkat(*target, Some(parent.as_raw_fd()), name) {
+            Err(nix::Error::Sys(Errno::EEXIST)) => {}
+            result => result.with_context(|| format!("failed to create {}", link))?,
+        }
+    }
+
+    let terminal = spec
+        .process
+        .as_ref()
+        .and_then(|process| process.terminal)
+        .unwrap_or(false);
+    if terminal && isatty(0).unwrap_or(false) {
+        setup_console(rootfs).context("failed to set up /dev/console")?;
+    }
+    Ok(())
+}
+
+fn device_kind(device: &LinuxDevice) -> Result<SFlag> {
+    let kind = match device.type_.as_str() {
+        "c" | "u" => SFlag::S_IFCHR,
+        "b" => SFlag::S_IFBLK,
+        "p" => SFlag::S_IFIFO,
+        type_ => bail!("unknown device type: {}", type_),
+    };
+    Ok(kind)
+}
+
+fn create_device(device: &LinuxDevice, rootfs: &Path) -> Result<()> {
+    let kind = device_kind(device)?;
+    // Everything is done relative to the parent directory and without
+    // following the name, so a symlink cannot redirect it out of the rootfs.
+    let (parent, name) = RootedPath::create_parent(rootfs, Path::new(&device.path))?;
+    match unlinkat(Some(parent.as_raw_fd()), name, UnlinkatFlags::NoRemoveDir) {
+        Err(nix::Error::Sys(Errno::ENOENT)) => {}
+        result => result?,
+    }
+    let mode = device.file_mode.unwrap_or(0o666);
+    let name_c = CString::new(name.as_bytes())?;
+    let ret = unsafe {
+        libc::mknodat(
+            parent.as_raw_fd(),
+            name_c.as_ptr(),
+            kind.bits() | mode,
+            makedev(device.major as u64, device.minor as u64),
+        )
+    };
+    Errno::result(ret)?;
+    // mknod(2) is subject to the umask. fchmodat(2) cannot leave a symlink
+    // alone, so the node is changed through its descriptor instead.
+    let node = parent.open_child(name)?;
+    fs::set_permissions(node.proc_path(), fs::Permissions::from_mode(mode))?;
+    fchownat(
+        Some(parent.as_raw_fd()),
+        name,
+        device.uid.map(Uid::from_raw),
+        device.gid.map(Gid::from_raw),
+        FchownatFlags::NoFollowSymlink,
+    )?;
+    Ok(())
+}
+
+fn bind_device(device: &LinuxDevice, rootfs: &Path) -> Result<()> {
+    device_kind(device)?;
+    let target = RootedPath::create_file(rootfs, Path::new(&device.path))?;
+    mount(
+        Some(device.path.as_str()),
+        &target.proc_path(),
+        NONE,
+        MsFlags::MS_BIND,
+        NONE,
+    )?;
+    Ok(())
+}
+
+/// Bind mount the terminal of the init onto /dev/console.
+fn setup_console(rootfs: &Path) -> Result<()> {
+    let tty = fs::read_link("/proc/self/fd/0")?;
+    let target = RootedPath::create_file(rootfs, Path::new("/dev/console"))?;
+    mount(
+        Some(&tty),
+        &target.proc_path(),
+        NONE,
+        MsFlags::MS_BIND,
+        NONE,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    #[test]
+    fn devices_should_be_created_with_mode_and_owner() {
+        let rootfs = tempfile::tempdir().unwrap().into_path();
+        let device = LinuxDevice {
+            path: "/dev/input/null".into(),
+            type_: "c".into(),
+            major: 1,
+            minor: 3,
+            file_mode: Some(0o620),
+            uid: Some(1000),
+            gid: Some(5),
+        };
+        create_device(&device, &rootfs).unwrap();
+
+        let metadata = fs::metadata(rootfs.join("dev/input/null")).unwrap();
+        assert!(metadata.file_type().is_char_device());
+        assert_eq!(metadata.rdev(), makedev(1, 3));
+        assert_eq!(metadata.mode() & 0o777, 0o620);
+        assert_eq!((metadata.uid(), metadata.gid()), (1000, 5));
+        fs::remove_dir_all(&rootfs).unwrap();
+    }
+
+    #[test]
+    fn devices_should_not_follow_symlinks_out_of_rootfs() {
+        use std::os::unix::fs::symlink;
+
+        let rootfs = tempfile::tempdir().unwrap().into_path();
+        let outside = tempfile::tempdir().unwrap().into_path();
+        fs::write(outside.join("null"), "host").unwrap();
+        fs::create_dir(rootfs.join("dev")).unwrap();
+        symlink(&outside, rootfs.join("dev/input")).unwrap();
+        symlink(outside.join("null"), rootfs.join("dev/null")).unwrap();
+        let mut devices = default_devices();
+        devices.truncate(1);
+        devices.push(LinuxDevice {
+            path: "/dev/input/null".into(),
+            ..devices[0].clone()
+        });
+        for device in &devices {
+            create_device(device, &rootfs).unwrap();
+        }
+
+        let in_rootfs = rootfs.join(outside.strip_prefix("/").unwrap());
+        for path in &[rootfs.join("dev/null"), in_rootfs.join("null")] {
+            let metadata = fs::symlink_metadata(path).unwrap();
+            assert!(metadata.file_type().is_char_device(), "{}", path.display());
+        }
+        assert_eq!(fs::read_to_string(outside.join("null")).unwrap(), "host");
+        fs::remove_dir_all(&rootfs).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn unknown_device_types_should_be_rejected() {
+        let mut device = default_devices().remove(0);
+        device.type_ = "x".into();
+        let err = device_kind(&device).unwrap_err();
+        assert_eq!(err.to_string(), "unknown device type: x");
+    }
+}
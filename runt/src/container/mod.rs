@@ -1,18 +1,42 @@
-use std::fs::{self, File};
-use std::os::unix::io::AsRawFd;
-use std::path::PathBuf;
+use std::fs::{self, DirBuilder, File};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Utc};
-use nix::fcntl::{flock, FlockArg};
-use nix::unistd::{Uid, User};
+use libc::c_int;
+use nix::errno::Errno;
+use nix::fcntl::{flock, FlockArg, OFlag};
+use nix::sys::signal::{SigSet, SigmaskHow};
+use nix::sys::wait::waitpid;
+use nix::unistd::{close, dup2, fork, pipe2, setsid, ForkResult, Pid, Uid, User};
 use serde_derive::{Deserialize, Serialize};
 
-use crate::container::specs::{Spec, State, Status, OCI_VERSION};
+use crate::config;
+use crate::container::cgroups::Cgroup;
+use crate::container::seccomp::notify::Listener;
+use crate::container::specs::{
+    Linux, LinuxNamespace, LinuxResources, LinuxSeccomp, Process, Spec, State, Status, OCI_VERSION,
+};
 
+pub mod capabilities;
+pub mod cgroups;
+pub mod checkpoint;
+pub mod devices;
+pub mod mounts;
+pub mod namespaces;
 pub mod process;
+pub mod resources;
+pub mod rootfs;
+pub mod seccomp;
+pub mod securejoin;
 pub mod specs;
+pub mod stats;
 mod syscallutils;
+pub mod user;
+pub mod userns;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Container {
@@ -21,69 +45,356 @@ pub struct Container {
     pub bundle: PathBuf,
     pub status: Status,
     pub pid: Option<i32>,
+    /// Start time of the init in clock ticks after boot, which tells it
+    /// apart from a later process reusing its pid.
+    #[serde(default)]
+    pub start_time: Option<u64>,
     pub created: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// Directory holding the state of every container, set by `--root`.
+    #[serde(skip)]
+    pub root: PathBuf,
+    /// Chroot into the rootfs instead of pivot_root(2).
+    #[serde(default)]
+    pub no_pivot: bool,
+    /// Warn about and leave out the capabilities the kernel doesn't know,
+    /// for the init and the processes started by `exec`.
+    #[serde(default)]
+    pub ignore_unknown_caps: bool,
 }
 
 impl Container {
     // bundle: must absolute path
-    pub fn new(id: &str, bundle: &PathBuf, spec: Spec) -> Self {
+    pub fn new(root: &Path, id: &str, bundle: &Path, spec: Spec) -> Self {
         Container {
             id: id.into(),
             bundle: bundle.to_path_buf(),
             spec,
             status: Status::Creating,
             pid: None,
+            start_time: None,
             created: None,
+            exit_code: None,
+            root: root.to_path_buf(),
+            no_pivot: false,
+            ignore_unknown_caps: false,
         }
     }
 
+    /// Root filesystem of the container, relative to the bundle unless absolute.
+    pub fn rootfs(&self) -> PathBuf {
+        self.bundle.join(&self.spec.root.path)
+    }
+
+    pub fn namespaces(&self) -> &[LinuxNamespace] {
+        self.spec
+            .linux
+            .as_ref()
+            .and_then(|linux| linux.namespaces.as_deref())
+            .unwrap_or(&[])
+    }
+
+    pub fn seccomp(&self) -> Option<&LinuxSeccomp> {
+        self.spec
+            .linux
+            .as_ref()
+            .and_then(|linux| linux.seccomp.as_ref())
+    }
+
+    /// Directory holding the state of this container.
+    pub fn state_dir(&self) -> PathBuf {
+        self.root.join(&self.id)
+    }
+
     pub fn create(&mut self) -> Result<()> {
         // assert_eq!(container.status, Status::Creating)
-        self.save_metadata(&self)?;
+        let process = self.spec.process.as_mut();
+        if let Some(caps) = process.and_then(|process| process.capabilities.as_mut()) {
+            capabilities::check(caps, self.ignore_unknown_caps)?;
+        }
+        let cgroup = self.joined_cgroup()?;
+        let resources = self.spec.linux.as_ref().and_then(|l| l.resources.clone());
+        if cgroup.is_none() && resources.is_some() {
+            bail!("resources cannot be set without a cgroup delegated to the user");
+        }
+        self.create_state_dir()?;
+        self.save_metadata(self)?;
 
         // -----
         // container creating
         // -----
 
-        let process = self.spec.process.clone().expect("process must be set: ");
-        match process.spawn(&self.bundle)? {
-            // Parent process
-            Some(child_pid) => {
-                self.status = Status::Created;
-                self.created = Some(Utc::now());
-                self.pid = Some(child_pid.as_raw());
-                self.save_metadata(&self)?;
-                // waitpid(pid, )
-                // self.status = Status::Stopperd;
-                // self.pid = None;
-                // self.save_metadata(&self)?;
+        if let Err(e) = self.spawn_init(cgroup.as_ref(), resources) {
+            // Leave no half created container behind for `list` to show.
+            if let Some(cgroup) = &cgroup {
+                if let Err(e) = cgroup.remove() {
+                    eprintln!("failed to remove the cgroup of {}: {}", self.id, e);
+                }
             }
-            // Child process
-            None => std::process::exit(0),
+            self.remove_metadata()?;
+            return Err(e);
         }
         Ok(())
     }
 
-    pub fn start(&self) -> Result<()> {
+    fn spawn_init(
+        &mut self,
+        cgroup: Option<&Cgroup>,
+        resources: Option<LinuxResources>,
+    ) -> Result<()> {
         let process = self.spec.process.clone().expect("process must be set: ");
-        for i in 1..5 {
-            let result = process.trigger_container_start(&self.bundle);
-            if result.is_ok() {
-                break;
+        let child_pid = process.spawn(self)?;
+        let result = cgroup.map_or(Ok(()), |cgroup| {
+            cgroup.apply(child_pid)?;
+            resources.map_or(Ok(()), |resources| cgroup.set(&resources))
+        });
+        if let Err(e) = result {
+            send_signal(child_pid, libc::SIGKILL)?;
+            return Err(e);
+        }
+        self.status = Status::Created;
+        self.created = Some(Utc::now());
+        self.pid = Some(child_pid.as_raw());
+        self.start_time = start_time(child_pid).ok();
+        self.save_metadata(self)?;
+        Ok(())
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        match self.status {
+            Status::Created => {}
+            Status::Running => bail!("container {} is already running", self.id),
+            status => bail!("cannot start a container in {} status", status),
+        }
+
+        let process = self.spec.process.clone().expect("process must be set: ");
+        process.trigger_container_start(&self.state_dir())?;
+
+        self.status = Status::Running;
+        self.save_metadata(self)?;
+        Ok(())
+    }
+
+    /// Wait for the init to exit while forwarding signals to it.
+    /// Only usable by the process which created the container, after making
+    /// itself a subreaper with `set_child_subreaper`.
+    pub fn wait(&mut self) -> Result<i32> {
+        let pid = Pid::from_raw(self.pid.expect("container pid must be set: "));
+        let exit_code = wait_forwarding_signals(pid)?;
+
+        self.status = Status::Stopped;
+        self.exit_code = Some(exit_code);
+        self.save_metadata(self)?;
+        Ok(exit_code)
+    }
+
+    /// Start an additional process inside the running container and return
+    /// its pid. `console` becomes the controlling terminal of the process.
+    pub fn exec(&self, process: &Process, console: Option<RawFd>) -> Result<Pid> {
+        if self.status != Status::Running {
+            bail!("cannot exec in a container in {} status", self.status);
+        }
+        let init_pid = Pid::from_raw(self.pid.expect("container pid must be set: "));
+        let cgroup = self.joined_cgroup()?;
+        process.validate()?;
+        let mut process = process.clone();
+        if let Some(caps) = process.capabilities.as_mut() {
+            capabilities::check(caps, self.ignore_unknown_caps)?;
+        }
+        let filter = self.seccomp().map(seccomp::compile).transpose()?;
+        let listener = match (&filter, self.seccomp()) {
+            (Some(filter), Some(seccomp)) if filter.notifies() => {
+                Some(Listener::connect(seccomp, self.state()?)?)
             }
-            if i == 5 && result.is_err() {
-                return result;
+            _ => None,
+        };
+
+        let (reader, writer) = pipe2(OFlag::O_CLOEXEC)?;
+        match fork()? {
+            ForkResult::Parent { child } => {
+                close(writer)?;
+                let mut reader = BufReader::new(unsafe { File::from_raw_fd(reader) });
+                let mut message = String::new();
+                reader.read_line(&mut message)?;
+                waitpid(child, None)?;
+                match message.trim_end().parse() {
+                    Ok(pid) => Ok(Pid::from_raw(pid)),
+                    Err(_) => bail!("failed to exec: {}", message.trim_end()),
+                }
+            }
+            ForkResult::Child => {
+                close(reader).ok();
+                let mut writer = unsafe { File::from_raw_fd(writer) };
+                let result = cgroup
+                    .map_or(Ok(()), |cgroup| cgroup.apply(Pid::this()))
+                    .and_then(|_| namespaces::enter(init_pid, self.start_time))
+                    .and_then(|_| {
+                        spawn_in_namespaces(&process, console, filter.as_ref(), listener.as_ref())
+                    });
+                let _ = match result {
+                    Ok(pid) => writeln!(writer, "{}", pid),
+                    Err(e) => writeln!(writer, "{}", e.to_string().replace('\n', " ")),
+                };
+                std::process::exit(0)
             }
         }
+    }
+
+    /// Freeze every process of the running container.
+    pub fn pause(&mut self) -> Result<()> {
+        match self.status {
+            Status::Running => {}
+            Status::Paused => bail!("container {} is already paused", self.id),
+            status => bail!("cannot pause a container in {} status", status),
+        }
+        self.delegated_cgroup("pause")?.freeze()?;
+        self.status = Status::Paused;
+        self.save_metadata(self)?;
+        Ok(())
+    }
+
+    /// Thaw the processes frozen by `pause`.
+    pub fn resume(&mut self) -> Result<()> {
+        if self.status != Status::Paused {
+            bail!("container {} is not paused", self.id);
+        }
+        self.delegated_cgroup("resume")?.thaw()?;
+        self.status = Status::Running;
+        self.save_metadata(self)?;
+        Ok(())
+    }
+
+    /// Change the resource limits of the container and remember them.
+    pub fn update(&mut self, resources: &LinuxResources) -> Result<()> {
+        match self.status {
+            Status::Created | Status::Running | Status::Paused => {}
+            status => bail!("cannot update a container in {} status", status),
+        }
+        let mut merged = self
+            .spec
+            .linux
+            .as_ref()
+            .and_then(|linux| linux.resources.clone())
+            .unwrap_or_default();
+        resources::merge(&mut merged, resources);
+        self.delegated_cgroup("update")?
+            .set(&resources::cgroup_update(&merged, resources))?;
+
+        let linux = self.spec.linux.get_or_insert_with(Linux::default);
+        linux.resources = Some(merged);
+        self.save_metadata(self)?;
+        Ok(())
+    }
+
+    /// Send `signal` to the init, or to every process of the container.
+    pub fn kill(&self, signal: c_int, all: bool) -> Result<()> {
+        match self.status {
+            Status::Created | Status::Running | Status::Paused => {}
+            status => bail!("cannot kill a container in {} status", status),
+        }
+
+        // Without a cgroup, the init is the only process known.
+        let cgroup = if all { self.joined_cgroup()? } else { None };
+        let pids = match cgroup {
+            Some(cgroup) => cgroup.pids()?,
+            None => vec![],
+        };
+        if pids.is_empty() {
+            let pid = self.pid.expect("container pid must be set: ");
+            send_signal(Pid::from_raw(pid), signal)?;
+        }
+        for pid in pids {
+            send_signal(pid, signal)?;
+        }
         Ok(())
     }
 
     pub fn delete(&self) -> Result<()> {
+        // A created init is still blocked waiting for `runt start`, and a
+        // forced delete may leave processes behind.
+        let cgroup = self.cgroup()?;
+        if let (Status::Created, Some(pid))
+        | (Status::Running, Some(pid))
+        | (Status::Paused, Some(pid)) = (self.status, self.pid)
+        {
+            send_signal(Pid::from_raw(pid), libc::SIGKILL)?;
+        }
+        for pid in cgroup.pids()? {
+            send_signal(pid, libc::SIGKILL)?;
+        }
+        // Frozen processes only die once thawed.
+        if self.status == Status::Paused {
+            cgroup.thaw()?;
+        }
+        cgroup.remove()?;
         self.remove_metadata()?;
 
         Ok(())
     }
 
+    /// Mark the container stopped if its init has exited behind our back.
+    pub fn refresh_status(&mut self) -> Result<()> {
+        let pid = match (self.status, self.pid) {
+            (Status::Created, Some(pid))
+            | (Status::Running, Some(pid))
+            | (Status::Paused, Some(pid)) => pid,
+            _ => return Ok(()),
+        };
+        let pid = Pid::from_raw(pid);
+        let reused = self
+            .start_time
+            .is_some_and(|time| start_time(pid).ok() != Some(time));
+        if !is_alive(pid) || reused {
+            self.status = Status::Stopped;
+            self.save_metadata(self)?;
+        }
+        Ok(())
+    }
+
+    /// Claim the id by creating the state directory, which fails when a
+    /// container with the same id exists.
+    fn create_state_dir(&self) -> Result<()> {
+        // Other users have no business reading the container states.
+        let mut builder = DirBuilder::new();
+        builder.mode(0o700);
+        builder
+            .recursive(true)
+            .create(&self.root)
+            .with_context(|| format!("failed to create {}", self.root.display()))?;
+        match builder.recursive(false).create(self.state_dir()) {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                bail!("container {} already exists", self.id)
+            }
+            result => {
+                result.with_context(|| format!("failed to create {}", self.state_dir().display()))
+            }
+        }
+    }
+
+    pub fn cgroup(&self) -> Result<Cgroup> {
+        Cgroup::new(&self.id, &self.spec)
+    }
+
+    /// The cgroup the processes of the container join, if any. Rootless
+    /// containers only have one when the user has been delegated it.
+    fn joined_cgroup(&self) -> Result<Option<Cgroup>> {
+        let cgroup = self.cgroup()?;
+        if config::is_rootless() && !cgroup.is_delegated()? {
+            return Ok(None);
+        }
+        Ok(Some(cgroup))
+    }
+
+    fn delegated_cgroup(&self, action: &str) -> Result<Cgroup> {
+        self.joined_cgroup()?.with_context(|| {
+            format!(
+                "cannot {} a container without a cgroup delegated to the user",
+                action
+            )
+        })
+    }
+
     pub fn state(&self) -> Result<State> {
         let owner = User::from_uid(Uid::effective())?.expect("contaienr owner not detected: ");
         Ok(State {
@@ -92,7 +403,7 @@ impl Container {
             status: self.status,
             pid: self.pid,
             bundle: self.bundle.clone(),
-            rootfs: PathBuf::from(&self.spec.root.path).canonicalize()?,
+            rootfs: self.rootfs().canonicalize()?,
             owner: owner.name,
             annotation: None,
             created: self.created,
@@ -100,22 +411,151 @@ impl Container {
     }
 }
 
-pub static DEFAULT_META_ROOT: &str = "/tmp/runt";
+/// Fork the exec'd process. It has to be a new child to be in the pid
+/// namespace joined by its parent.
+fn spawn_in_namespaces(
+    process: &Process,
+    console: Option<RawFd>,
+    filter: Option<&seccomp::Filter>,
+    listener: Option<&Listener>,
+) -> Result<Pid> {
+    match fork()? {
+        ForkResult::Parent { child } => Ok(child),
+        ForkResult::Child => {
+            let result = set_console(console).and_then(|_| process.exec(filter, listener));
+            if let Err(e) = result {
+                eprintln!("failed to exec: {}", e);
+            }
+            std::process::exit(1)
+        }
+    }
+}
+
+fn set_console(console: Option<RawFd>) -> Result<()> {
+    if let Some(console) = console {
+        setsid()?;
+        let ret = unsafe { libc::ioctl(console, libc::TIOCSCTTY, 0) };
+        Errno::result(ret)?;
+        for fd in 0..3 {
+            dup2(console, fd)?;
+        }
+        close(console)?;
+    }
+    Ok(())
+}
+
+/// Adopt the descendants orphaned by intermediate forks, such as the init
+/// and the processes started by `exec`, so they can be waited for.
+pub fn set_child_subreaper() -> Result<()> {
+    let ret = unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) };
+    Errno::result(ret)?;
+    Ok(())
+}
+
+/// Wait for `pid` to exit while forwarding the signals runt receives to it.
+/// `pid` must be a child of runt.
+pub fn wait_forwarding_signals(pid: Pid) -> Result<i32> {
+    let signals = SigSet::all();
+    let old_mask = signals.thread_swap_mask(SigmaskHow::SIG_BLOCK)?;
+    let exit_code = forward_signals(pid, &signals);
+    old_mask.thread_set_mask()?;
+    exit_code
+}
+
+fn forward_signals(pid: Pid, signals: &SigSet) -> Result<i32> {
+    let timeout = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 100_000_000,
+    };
+    loop {
+        // SIGCHLD may be consumed by another thread, so poll waitpid
+        // instead of relying on it. nix has no `Signal` for realtime
+        // signals, so the status and the signals stay raw numbers.
+        let mut status = 0;
+        let ret = unsafe { libc::waitpid(pid.as_raw(), &mut status, libc::WNOHANG) };
+        Errno::result(ret)?;
+        if ret == pid.as_raw() {
+            if libc::WIFEXITED(status) {
+                return Ok(libc::WEXITSTATUS(status));
+            }
+            if libc::WIFSIGNALED(status) {
+                return Ok(128 + libc::WTERMSIG(status));
+            }
+        }
+
+        let signal =
+            unsafe { libc::sigtimedwait(signals.as_ref(), std::ptr::null_mut(), &timeout) };
+        if signal < 0 || signal == libc::SIGCHLD {
+            continue;
+        }
+        let ret = unsafe { libc::kill(pid.as_raw(), signal) };
+        match Errno::result(ret) {
+            Ok(_) | Err(nix::Error::Sys(Errno::ESRCH)) => {}
+            Err(e) => eprintln!("failed to forward signal {}: {}", signal, e),
+        }
+    }
+}
+
+/// Whether `pid` is running. An exited init may linger as a zombie until
+/// whoever inherited it reaps it.
+fn is_alive(pid: Pid) -> bool {
+    match proc_stat(pid) {
+        Ok(fields) => fields.first().is_some_and(|state| state != "Z"),
+        Err(_) => false,
+    }
+}
+
+/// Start time of `pid` in clock ticks after boot.
+pub fn start_time(pid: Pid) -> Result<u64> {
+    let fields = proc_stat(pid)?;
+    // starttime is the 22nd field, the state the 3rd.
+    let time = fields
+        .get(19)
+        .with_context(|| format!("no start time in /proc/{}/stat", pid))?;
+    Ok(time.parse()?)
+}
+
+/// Fields of /proc/<pid>/stat from the state on.
+fn proc_stat(pid: Pid) -> Result<Vec<String>> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid))?;
+    // <pid> (<comm>) <state> ...; comm may itself contain ") ".
+    let i = stat
+        .rfind(')')
+        .with_context(|| format!("malformed /proc/{}/stat", pid))?;
+    Ok(stat[i + 1..].split_whitespace().map(String::from).collect())
+}
+
+/// kill(2) which treats an already exited process as success. The signal
+/// is a raw number so that realtime signals can be sent too.
+fn send_signal(pid: Pid, signal: c_int) -> Result<()> {
+    let ret = unsafe { libc::kill(pid.as_raw(), signal) };
+    match Errno::result(ret) {
+        Ok(_) | Err(nix::Error::Sys(Errno::ESRCH)) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
 pub static METADATA_FILE: &str = "state.json";
 
 pub trait MetadataManager {
     fn save_metadata(&self, container: &Container) -> Result<()>;
     fn remove_metadata(&self) -> Result<()>;
-    fn load(container_id: &str) -> Result<Container>;
+    fn load(root: &Path, container_id: &str) -> Result<Container>;
+    fn ids(root: &Path) -> Result<Vec<String>>;
     fn lock(&self, file: &File) -> Result<()>;
     fn unlock(&self, file: &File) -> Result<()>;
 }
 
 impl MetadataManager for Container {
     fn save_metadata(&self, container: &Container) -> Result<()> {
-        let metadata_dir = PathBuf::from(DEFAULT_META_ROOT).join(&container.id);
+        let metadata_dir = container.state_dir();
         if !metadata_dir.exists() {
-            fs::create_dir_all(&metadata_dir)?;
+            // Other users have no business reading the container states.
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(&metadata_dir)
+                .with_context(|| format!("failed to create {}", metadata_dir.display()))?;
         }
         let statefile = File::create(metadata_dir.join(METADATA_FILE))?;
 
@@ -128,23 +568,40 @@ impl MetadataManager for Container {
     }
 
     fn remove_metadata(&self) -> Result<()> {
-        let metadata_dir = PathBuf::from(DEFAULT_META_ROOT).join(&self.id);
+        let metadata_dir = self.state_dir();
         if metadata_dir.exists() {
             fs::remove_dir_all(&metadata_dir)?;
         }
         Ok(())
     }
 
-    fn load(container_id: &str) -> Result<Container> {
-        let statefile_path = PathBuf::from(DEFAULT_META_ROOT)
-            .join(&container_id)
-            .join(METADATA_FILE);
+    fn load(root: &Path, container_id: &str) -> Result<Container> {
+        let statefile_path = root.join(container_id).join(METADATA_FILE);
 
-        let statefile = File::open(statefile_path)?;
-        let container: Container = serde_json::from_reader(statefile)?;
+        let statefile = File::open(statefile_path)
+            .with_context(|| format!("container {} does not exist", container_id))?;
+        let mut container: Container = serde_json::from_reader(statefile)?;
+        container.root = root.to_path_buf();
         Ok(container)
     }
 
+    fn ids(root: &Path) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut ids = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                ids.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
     fn lock(&self, file: &File) -> Result<()> {
         let fd = file.as_raw_fd();
         flock(fd, FlockArg::LockExclusive)?;
@@ -162,36 +619,85 @@ impl MetadataManager for Container {
 pub mod testutil {
     use super::*;
     use std::fs;
+    use std::process::Command;
 
     use uuid::Uuid;
 
     use crate::specutil;
 
+    /// State root shared by the tests, kept apart from the real containers.
+    pub static TEST_ROOT: &str = "/tmp/runt-test";
+
+    /// `args` with `--root` pointing at the test state root.
+    pub fn args_with_root<'a>(args: &[&'a str]) -> Vec<&'a str> {
+        let mut with_root = vec![args[0], "--root", TEST_ROOT];
+        with_root.extend_from_slice(&args[1..]);
+        with_root
+    }
+
     pub fn init_bundle_dir() -> Result<PathBuf> {
         let bundle = tempfile::tempdir()?.into_path();
         fs::create_dir_all(&bundle)?;
         Ok(bundle)
     }
 
-    pub fn init_rootfs_dir(bundle: &PathBuf) -> Result<PathBuf> {
+    /// Programs available under /bin in the test rootfs.
+    const ROOTFS_PROGRAMS: &[&str] = &["sh", "sleep", "true", "cat", "head", "tail", "ls"];
+
+    /// Create a rootfs holding `ROOTFS_PROGRAMS` and the libraries they need.
+    pub fn init_rootfs_dir(bundle: &Path) -> Result<PathBuf> {
         let dir_name = Uuid::new_v4().to_string();
         let rootfs = bundle.join(dir_name);
-        fs::create_dir_all(&rootfs)?;
+        fs::create_dir_all(rootfs.join("tmp"))?;
+        for program in ROOTFS_PROGRAMS {
+            let path = Path::new("/bin").join(program).canonicalize()?;
+            install(&path, &rootfs.join("bin").join(program))?;
+            for library in libraries(&path)? {
+                install(
+                    &library.canonicalize()?,
+                    &rootfs.join(library.strip_prefix("/")?),
+                )?;
+            }
+        }
         Ok(rootfs)
     }
 
-    pub fn init_spec_file(bundle: &PathBuf, rootfs: &PathBuf) -> Result<()> {
+    /// Shared libraries loaded by `program`, as listed by ldd.
+    fn libraries(program: &Path) -> Result<Vec<PathBuf>> {
+        let output = Command::new("ldd").arg(program).output()?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .filter(|word| word.starts_with('/'))
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    /// Hard link `source` to `target`, or copy it across filesystems.
+    fn install(source: &Path, target: &Path) -> Result<()> {
+        if target.exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(target.parent().unwrap())?;
+        if fs::hard_link(source, target).is_err() {
+            fs::copy(source, target)?;
+        }
+        Ok(())
+    }
+
+    pub fn init_spec_file(bundle: &Path, rootfs: &Path) -> Result<()> {
         let mut spec = Spec::default();
-        spec.root.path = rootfs.clone().to_str().unwrap().to_string();
+        spec.root.path = rootfs.to_str().unwrap().to_string();
+        // The default `sh` would read the stdin of the tests.
+        spec.process.as_mut().unwrap().args = vec!["true".into()];
 
-        specutil::write(&bundle, &spec)?;
+        specutil::write(bundle, &spec)?;
         Ok(())
     }
 
     pub fn cleanup(paths: &[&PathBuf]) -> Result<()> {
         for path in paths {
             if path.exists() {
-                fs::remove_dir_all(&path)?;
+                fs::remove_dir_all(path)?;
             }
         }
         Ok(())
@@ -214,9 +720,10 @@ pub mod test {
         testutil::init_spec_file(&bundle, &rootfs).unwrap();
         let spec = specutil::load(&bundle).unwrap();
 
-        let meta_dir = PathBuf::from(DEFAULT_META_ROOT).join(&container_id);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
 
-        let mut container = Container::new(&container_id, &bundle, spec);
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
         assert_eq!(container.id, container_id);
         assert_eq!(container.bundle, bundle);
         assert_eq!(container.status, Status::Creating);
@@ -225,6 +732,796 @@ pub mod test {
 
         assert_eq!(container.status, Status::Created);
         assert!(container.start().is_ok());
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn state_should_be_kept_under_root() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let spec = specutil::load(&bundle).unwrap();
+        let root = bundle.join("state");
+
+        let mut container = Container::new(&root, &container_id, &bundle, spec);
+        container.create().unwrap();
+
+        let state_dir = root.join(&container_id);
+        assert_eq!(container.state_dir(), state_dir);
+        let mode = fs::metadata(&state_dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        assert!(state_dir.join("start_trigger.fifo").exists());
+        assert!(!bundle.join("start_trigger.fifo").exists());
+        assert_eq!(Container::ids(&root).unwrap(), vec![container_id.clone()]);
+        assert_eq!(Container::load(&root, &container_id).unwrap().root, root);
+
+        container.start().unwrap();
+        assert!(container.delete().is_ok());
+        assert!(!state_dir.exists());
+        testutil::cleanup(&[&bundle]).unwrap();
+    }
+
+    #[test]
+    fn spec_mounts_should_be_mounted() {
+        use crate::container::specs::Mount;
+
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        fs::create_dir(bundle.join("data")).unwrap();
+        spec.mounts.push(Mount {
+            destination: "/mnt/data".into(),
+            type_: "bind".into(),
+            source: bundle.join("data").to_str().unwrap().into(),
+            options: Some(vec!["rbind".into(), "ro".into(), "nosuid".into()]),
+        });
+        spec.mounts.push(Mount {
+            destination: "/etc/config.json".into(),
+            type_: "none".into(),
+            source: bundle.join("config.json").to_str().unwrap().into(),
+            options: Some(vec!["bind".into()]),
+        });
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        let pid = container.pid.unwrap();
+        let mounts = fs::read_to_string(format!("/proc/{}/mounts", pid)).unwrap();
+        let options_of = |destination: &str| -> Vec<String> {
+            mounts
+                .lines()
+                .rev()
+                .map(|line| line.split(' ').collect::<Vec<_>>())
+                .find(|fields| fields[1] == destination)
+                .unwrap_or_else(|| panic!("{} is not mounted:\n{}", destination, mounts))[3]
+                .split(',')
+                .map(String::from)
+                .collect()
+        };
+        for destination in &["/proc", "/dev", "/dev/pts", "/dev/shm", "/dev/mqueue"] {
+            options_of(destination);
+        }
+        let dev = options_of("/dev");
+        assert!(dev.contains(&"nosuid".into()), "{:?}", dev);
+        assert!(dev.contains(&"mode=755".into()), "{:?}", dev);
+        assert!(options_of("/dev/mqueue").contains(&"ro".into()));
+        assert!(options_of("/").contains(&"ro".into()));
+        let data = options_of("/mnt/data");
+        assert!(data.contains(&"ro".into()), "{:?}", data);
+        assert!(data.contains(&"nosuid".into()), "{:?}", data);
+        assert!(options_of("/etc/config.json").contains(&"rw".into()));
+        assert!(rootfs.join("etc/config.json").is_file());
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn relative_bind_sources_should_be_resolved_against_the_bundle() {
+        use crate::container::specs::Mount;
+
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        fs::create_dir(bundle.join("data")).unwrap();
+        fs::write(bundle.join("data/marker"), "bundle").unwrap();
+        spec.mounts.push(Mount {
+            destination: "/mnt/data".into(),
+            type_: "bind".into(),
+            source: "data".into(),
+            options: Some(vec!["rbind".into()]),
+        });
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        let pid = container.pid.unwrap();
+        let marker = format!("/proc/{}/root/mnt/data/marker", pid);
+        assert_eq!(fs::read_to_string(&marker).unwrap(), "bundle");
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn mounts_should_not_escape_rootfs_through_symlinks() {
+        use crate::container::specs::Mount;
+        use std::os::unix::fs::symlink;
+
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        let outside = bundle.join("outside");
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("marker"), "host").unwrap();
+        symlink(&outside, rootfs.join("absolute")).unwrap();
+        symlink("../../../../../../../..", rootfs.join("tmp/relative")).unwrap();
+        for destination in &["/absolute", "/tmp/relative/escaped"] {
+            spec.mounts.push(Mount {
+                destination: destination.to_string(),
+                type_: "tmpfs".into(),
+                source: "tmpfs".into(),
+                options: None,
+            });
+        }
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        let pid = container.pid.unwrap();
+        let mounts = fs::read_to_string(format!("/proc/{}/mounts", pid)).unwrap();
+        let mount_points: Vec<&str> = mounts
+            .lines()
+            .map(|line| line.split(' ').nth(1).unwrap())
+            .collect();
+        // Both symlinks are followed as if the rootfs were /.
+        assert!(
+            mount_points.contains(&outside.to_str().unwrap()),
+            "{}",
+            mounts
+        );
+        assert!(mount_points.contains(&"/escaped"), "{}", mounts);
+        assert!(rootfs.join(outside.strip_prefix("/").unwrap()).is_dir());
+        assert!(rootfs.join("escaped").is_dir());
+        assert!(!Path::new("/escaped").exists());
+        assert!(outside.join("marker").exists());
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn masked_and_readonly_paths_should_be_applied() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        fs::create_dir_all(rootfs.join("tmp/secret")).unwrap();
+        fs::write(rootfs.join("tmp/secret/key"), "secret").unwrap();
+        let linux = spec.linux.as_mut().unwrap();
+        let masked_paths = linux.masked_paths.as_mut().unwrap();
+        masked_paths.push("/tmp/secret".into());
+        masked_paths.push("/nonexistent".into());
+        linux.readonly_paths.as_mut().unwrap().push("/tmp".into());
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        let pid = container.pid.unwrap();
+        let mounts = fs::read_to_string(format!("/proc/{}/mounts", pid)).unwrap();
+        let find = |destination: &str| -> Vec<String> {
+            mounts
+                .lines()
+                .rev()
+                .map(|line| line.split(' ').collect::<Vec<_>>())
+                .find(|fields| fields[1] == destination)
+                .unwrap_or_else(|| panic!("{} is not mounted:\n{}", destination, mounts))
+                .iter()
+                .map(|field| field.to_string())
+                .collect()
+        };
+        let timer_list = find("/proc/timer_list");
+        assert_ne!(timer_list[2], "proc", "{:?}", timer_list);
+        let secret = find("/tmp/secret");
+        assert_eq!(secret[2], "tmpfs");
+        assert!(secret[3].starts_with("ro,"), "{:?}", secret);
+        for path in &["/proc/sys", "/tmp"] {
+            let options = find(path)[3].clone();
+            for option in &["ro", "nosuid", "nodev", "noexec"] {
+                assert!(options.split(',').any(|o| o == *option), "{}", options);
+            }
+        }
+        let root = format!("/proc/{}/root", pid);
+        assert_eq!(fs::read(format!("{}/proc/timer_list", root)).unwrap(), b"");
+        assert!(fs::read_dir(format!("{}/tmp/secret", root))
+            .unwrap()
+            .next()
+            .is_none());
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn devices_should_be_created_in_dev() {
+        use crate::container::specs::LinuxDevice;
+        use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        spec.linux.as_mut().unwrap().devices = Some(vec![LinuxDevice {
+            path: "/dev/null".into(),
+            type_: "c".into(),
+            major: 1,
+            minor: 3,
+            file_mode: Some(0o600),
+            uid: None,
+            gid: None,
+        }]);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        let dev = PathBuf::from(format!("/proc/{}/root/dev", container.pid.unwrap()));
+        for (name, rdev) in &[("null", (1, 3)), ("zero", (1, 5)), ("tty", (5, 0))] {
+            let metadata = fs::metadata(dev.join(name)).unwrap();
+            assert!(metadata.file_type().is_char_device(), "{}", name);
+            assert_eq!(metadata.rdev(), nix::sys::stat::makedev(rdev.0, rdev.1));
+        }
+        let null = fs::metadata(dev.join("null")).unwrap();
+        assert_eq!(null.mode() & 0o777, 0o600);
+        let urandom = fs::metadata(dev.join("urandom")).unwrap();
+        assert_eq!(urandom.mode() & 0o777, 0o666);
+        for (link, target) in &[("fd", "/proc/self/fd"), ("stderr", "/proc/self/fd/2")] {
+            assert_eq!(fs::read_link(dev.join(link)).unwrap(), Path::new(target));
+        }
+        assert_eq!(
+            fs::read_link(dev.join("ptmx")).unwrap(),
+            Path::new("pts/ptmx")
+        );
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn process_should_run_with_spec_env_cwd_and_user() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        fs::create_dir_all(rootfs.join("etc")).unwrap();
+        fs::write(
+            rootfs.join("etc/passwd"),
+            "root:x:0:0:root:/root:/bin/sh\napp:x:1234:2345::/tmp:/bin/sh\n",
+        )
+        .unwrap();
+        fs::write(rootfs.join("etc/group"), "wheel:x:10:root,app\n").unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        let process = spec.process.as_mut().unwrap();
+        process.args = vec!["sleep".into(), "10".into()];
+        process.env = Some(vec!["PATH=/bin".into(), "FOO=bar".into()]);
+        process.cwd = "/tmp".into();
+        process.user.username = Some("app".into());
+        process.user.additional_gids = Some(vec![20]);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        container.start().unwrap();
+        let pid = container.pid.unwrap();
+        // Wait for the exec.
+        let proc_dir = PathBuf::from(format!("/proc/{}", pid));
+        for _ in 0..100 {
+            if fs::read_to_string(proc_dir.join("comm")).unwrap() == "sleep\n" {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let environ = fs::read(proc_dir.join("environ")).unwrap();
+        assert_eq!(environ, b"PATH=/bin\0FOO=bar\0");
+        assert_eq!(
+            fs::read_link(proc_dir.join("cwd")).unwrap(),
+            Path::new("/tmp")
+        );
+        let status = fs::read_to_string(proc_dir.join("status")).unwrap();
+        let field = |name: &str| -> String {
+            let line = status.lines().find(|l| l.starts_with(name)).unwrap();
+            line[name.len()..]
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(field("Uid:"), "1234 1234 1234 1234");
+        assert_eq!(field("Gid:"), "2345 2345 2345 2345");
+        assert_eq!(field("Groups:"), "10 20");
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    /// Run the process of the default spec, changed by `setup`, to
+    /// completion and return its exit code.
+    fn run_to_exit(setup: impl FnOnce(&mut Process)) -> i32 {
+        run_spec_to_exit(|spec| setup(spec.process.as_mut().unwrap()))
+    }
+
+    fn run_spec_to_exit(setup: impl FnOnce(&mut Spec)) -> i32 {
+        set_child_subreaper().unwrap();
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        setup(&mut spec);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        container.start().unwrap();
+        let exit_code = container.wait().unwrap();
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+        exit_code
+    }
+
+    #[test]
+    fn realtime_signals_should_be_forwarded_while_waiting() {
+        set_child_subreaper().unwrap();
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        let rtmin = libc::SIGRTMIN();
+        let script = format!("trap 'exit 3' {}; : > /tmp/ready; sleep 30 & wait", rtmin);
+        spec.process.as_mut().unwrap().args = sh(&script);
+        spec.root.readonly = Some(false);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        container.start().unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let waiter = std::thread::spawn(move || {
+            // Keep the signal pending for the wait rather than let it kill
+            // the tests, should it arrive first.
+            unsafe {
+                let mut set: libc::sigset_t = std::mem::zeroed();
+                libc::sigemptyset(&mut set);
+                libc::sigaddset(&mut set, rtmin);
+                libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+            }
+            sender
+                .send(unsafe { libc::syscall(libc::SYS_gettid) })
+                .unwrap();
+            let exit_code = container.wait().unwrap();
+            (container, exit_code)
+        });
+        let tid = receiver.recv().unwrap();
+        let start = std::time::Instant::now();
+        while !rootfs.join("tmp/ready").exists() {
+            assert!(start.elapsed().as_secs() < 10, "init did not get ready");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        unsafe { libc::syscall(libc::SYS_tgkill, libc::getpid(), tid, rtmin) };
+
+        let (container, exit_code) = waiter.join().unwrap();
+        assert_eq!(exit_code, 3);
+        assert_eq!(container.status, Status::Stopped);
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".into(), "-c".into(), script.into()]
+    }
+
+    /// Shell script exiting with 1 unless the capability sets in
+    /// /proc/self/status match `expected`, a list of `CapXxx: mask`.
+    fn check_caps_script(expected: &[(&str, &str)]) -> String {
+        let cases: Vec<String> = expected
+            .iter()
+            .map(|(set, mask)| format!("{}:) test $v = {} || exit 1;;", set, mask))
+            .collect();
+        format!(
+            "while read k v; do case $k in {} esac; done < /proc/self/status",
+            cases.join(" ")
+        )
+    }
+
+    #[test]
+    fn capabilities_should_be_applied_to_root() {
+        // CAP_KILL, CAP_NET_BIND_SERVICE and CAP_AUDIT_WRITE.
+        let mask = "0000000020000420";
+        let script = check_caps_script(&[("CapEff", mask), ("CapPrm", mask), ("CapBnd", mask)]);
+        assert_eq!(run_to_exit(|process| process.args = sh(&script)), 0);
+        // The script can fail.
+        let script = check_caps_script(&[("CapEff", "000001ffffffffff")]);
+        assert_eq!(run_to_exit(|process| process.args = sh(&script)), 1);
+    }
+
+    #[test]
+    fn capabilities_should_be_kept_across_setuid() {
+        let mask = "0000000020000420";
+        let script = check_caps_script(&[("CapEff", mask), ("CapAmb", mask), ("CapBnd", mask)]);
+        let exit_code = run_to_exit(|process| {
+            process.args = sh(&script);
+            process.user.uid = 1000;
+        });
+        assert_eq!(exit_code, 0);
+    }
+
+    #[test]
+    fn rlimits_and_oom_score_adj_should_be_set_before_dropping_privileges() {
+        use crate::container::specs::POSIXRlimit;
+
+        let script = r#"
+            test "$(ulimit -n)" = 100 && test "$(ulimit -Hn)" = 200 || exit 1
+            read score < /proc/self/oom_score_adj && test "$score" = 500 || exit 2
+            while read k v; do
+                case $k in NoNewPrivs:) test $v = 1 || exit 3;; esac
+            done < /proc/self/status
+        "#;
+        let exit_code = run_to_exit(|process| {
+            process.args = sh(script);
+            process.user.uid = 1000;
+            process.oom_score_adj = Some(500);
+            process.rlimits = Some(vec![POSIXRlimit {
+                type_: "RLIMIT_NOFILE".into(),
+                hard: 200,
+                soft: 100,
+            }]);
+        });
+        assert_eq!(exit_code, 0);
+    }
+
+    #[test]
+    fn seccomp_filter_should_be_loaded_with_and_without_no_new_privs() {
+        use crate::container::specs::{LinuxSeccomp, LinuxSyscall};
+
+        let script = r#"
+            kill -0 $$ 2>/dev/null && exit 1
+            while read k v; do
+                case $k in Seccomp:) test $v = 2 || exit 2;; esac
+            done < /proc/self/status
+        "#;
+        for &no_new_privileges in &[false, true] {
+            let exit_code = run_spec_to_exit(|spec| {
+                let process = spec.process.as_mut().unwrap();
+                process.args = sh(script);
+                process.user.uid = 1000;
+                process.no_new_privileges = Some(no_new_privileges);
+                spec.linux.as_mut().unwrap().seccomp = Some(LinuxSeccomp {
+                    default_action: "SCMP_ACT_ALLOW".into(),
+                    default_errno_ret: None,
+                    architectures: None,
+                    flags: None,
+                    listener_path: None,
+                    listener_metadata: None,
+                    syscalls: Some(vec![LinuxSyscall {
+                        names: vec!["kill".into()],
+                        action: "SCMP_ACT_ERRNO".into(),
+                        errno_ret: None,
+                        args: None,
+                    }]),
+                });
+            });
+            assert_eq!(exit_code, 0, "no_new_privileges: {}", no_new_privileges);
+        }
+    }
+
+    #[test]
+    fn seccomp_notifications_should_go_to_the_agent() {
+        use crate::container::seccomp::notify::{agent, SECCOMP_FD_NAME};
+        use crate::container::specs::{LinuxSeccomp, LinuxSyscall};
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("agent.sock");
+        // Refuse kill(2) on behalf of the kernel.
+        let agent = agent::spawn(&socket, |_| Err(libc::EPERM)).unwrap();
+
+        let script = "kill -0 $$ 2>/dev/null && exit 1; exit 0";
+        let exit_code = run_spec_to_exit(|spec| {
+            spec.process.as_mut().unwrap().args = sh(script);
+            spec.linux.as_mut().unwrap().seccomp = Some(LinuxSeccomp {
+                default_action: "SCMP_ACT_ALLOW".into(),
+                default_errno_ret: None,
+                architectures: None,
+                flags: None,
+                listener_path: Some(socket.to_string_lossy().into()),
+                listener_metadata: Some("emulate".into()),
+                syscalls: Some(vec![LinuxSyscall {
+                    names: vec!["kill".into()],
+                    action: "SCMP_ACT_NOTIFY".into(),
+                    errno_ret: None,
+                    args: None,
+                }]),
+            });
+        });
+        assert_eq!(exit_code, 0);
+
+        let (state, syscalls) = agent.join().unwrap().unwrap();
+        assert_eq!(state.fds, vec![SECCOMP_FD_NAME.to_string()]);
+        assert_eq!(state.metadata.as_deref(), Some("emulate"));
+        assert_eq!(state.state.status, Status::Creating);
+        assert_eq!(state.state.pid, Some(state.pid));
+        assert_eq!(syscalls, vec![libc::SYS_kill as i32]);
+    }
+
+    #[test]
+    fn unknown_capabilities_should_be_rejected_unless_ignored() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        let caps = spec
+            .process
+            .as_mut()
+            .unwrap()
+            .capabilities
+            .as_mut()
+            .unwrap();
+        caps.bounding.as_mut().unwrap().push("CAP_FOO".into());
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container = Container::new(
+            testutil::TEST_ROOT.as_ref(),
+            &container_id,
+            &bundle,
+            spec.clone(),
+        );
+        let err = container.create().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("unknown capability: CAP_FOO (expected one of "));
+        assert!(!meta_dir.exists());
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.ignore_unknown_caps = true;
+        container.create().unwrap();
+        let loaded = Container::load(testutil::TEST_ROOT.as_ref(), &container_id).unwrap();
+        assert!(loaded.ignore_unknown_caps);
+        let caps = loaded.spec.process.unwrap().capabilities.unwrap();
+        assert!(!caps.bounding.unwrap().contains(&"CAP_FOO".into()));
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    fn same_namespace(pid: i32, name: &str) -> bool {
+        let theirs = fs::read_link(format!("/proc/{}/ns/{}", pid, name)).unwrap();
+        let ours = fs::read_link(format!("/proc/self/ns/{}", name)).unwrap();
+        theirs == ours
+    }
+
+    #[test]
+    fn namespaces_should_be_created_or_joined() {
+        use crate::container::specs::{LinuxNamespace, LinuxNamespaceType};
+
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        let namespaces = spec.linux.as_mut().unwrap().namespaces.as_mut().unwrap();
+        for namespace in namespaces.iter_mut() {
+            if namespace.type_ == LinuxNamespaceType::Network {
+                namespace.path = Some("/proc/self/ns/net".into());
+            }
+        }
+        namespaces.push(LinuxNamespace {
+            type_: LinuxNamespaceType::Cgroup,
+            path: None,
+        });
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        let pid = container.pid.unwrap();
+        for name in &["pid", "ipc", "uts", "mnt"] {
+            assert!(!same_namespace(pid, name), "{} namespace is shared", name);
+        }
+        assert!(same_namespace(pid, "net"));
+        // The cgroup namespace is created right before exec.
+        assert!(same_namespace(pid, "cgroup"));
+        let status = fs::read_to_string(format!("/proc/{}/status", pid)).unwrap();
+        let nspid = status.lines().find(|l| l.starts_with("NSpid:")).unwrap();
+        assert!(nspid.ends_with("\t1"), "{}", nspid);
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    fn user_namespace_spec(spec: &mut Spec, root_host_id: u32) {
+        use crate::container::specs::{LinuxIDMapping, LinuxNamespace, LinuxNamespaceType};
+
+        let mappings = vec![
+            LinuxIDMapping {
+                container_id: 0,
+                host_id: root_host_id,
+                size: 1,
+            },
+            LinuxIDMapping {
+                container_id: 1000,
+                host_id: 101_000,
+                size: 10,
+            },
+        ];
+        let linux = spec.linux.as_mut().unwrap();
+        linux.namespaces.as_mut().unwrap().push(LinuxNamespace {
+            type_: LinuxNamespaceType::User,
+            path: None,
+        });
+        linux.uid_mappings = Some(mappings.clone());
+        linux.gid_mappings = Some(mappings);
+        // The tty group isn't mapped.
+        for mount in spec.mounts.iter_mut() {
+            if let Some(options) = mount.options.as_mut() {
+                options.retain(|option| option != "gid=5");
+            }
+        }
+    }
+
+    #[test]
+    fn ids_should_be_mapped_in_user_namespace() {
+        let check_map = |file| {
+            format!(
+                "{{ read c h s && test \"$c $h $s\" = \"0 0 1\" && read c h s && \
+                 test \"$c $h $s\" = \"1000 101000 10\"; }} < /proc/self/{} || exit 1",
+                file
+            )
+        };
+        let script = format!("{}; {}", check_map("uid_map"), check_map("gid_map"));
+        let exit_code = run_spec_to_exit(|spec| {
+            user_namespace_spec(spec, 0);
+            let process = spec.process.as_mut().unwrap();
+            process.args = sh(&script);
+            process.user.uid = 1000;
+            process.user.gid = 1000;
+        });
+        assert_eq!(exit_code, 0);
+    }
+
+    #[test]
+    fn unmapped_rootfs_should_be_rejected() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        user_namespace_spec(&mut spec, 100_000);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        let err = container.create().unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("is owned by uid 0, which is not mapped in the user namespace"),
+            "{}",
+            err
+        );
+        assert!(!meta_dir.exists());
+        for path in container.cgroup().unwrap().paths().unwrap() {
+            assert!(!path.exists(), "{}", path.display());
+        }
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn existing_id_should_be_rejected() {
+        use std::os::unix::fs::MetadataExt;
+
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let spec = specutil::load(&bundle).unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container = Container::new(
+            testutil::TEST_ROOT.as_ref(),
+            &container_id,
+            &bundle,
+            spec.clone(),
+        );
+        container.create().unwrap();
+        let fifo = fs::metadata(meta_dir.join("start_trigger.fifo")).unwrap();
+
+        let mut duplicate =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        let err = duplicate.create().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("container {} already exists", container_id)
+        );
+        let loaded = Container::load(testutil::TEST_ROOT.as_ref(), &container_id).unwrap();
+        assert_eq!(loaded.pid, container.pid);
+        let fifo_after = fs::metadata(meta_dir.join("start_trigger.fifo")).unwrap();
+        assert_eq!(fifo_after.ino(), fifo.ino());
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn failed_create_should_leave_nothing_behind() {
+        use crate::container::specs::LinuxCPU;
+
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        let resources = spec
+            .linux
+            .as_mut()
+            .unwrap()
+            .resources
+            .get_or_insert_with(Default::default);
+        resources.cpu = Some(LinuxCPU {
+            cpus: Some("100000".into()),
+            ..Default::default()
+        });
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        assert!(container.create().is_err());
+        assert!(!meta_dir.exists());
+        for path in container.cgroup().unwrap().paths().unwrap() {
+            assert!(!path.exists(), "{}", path.display());
+        }
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn duplicate_namespaces_should_be_rejected() {
+        use crate::container::specs::{LinuxNamespace, LinuxNamespaceType};
+
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        let linux = spec.linux.as_mut().unwrap();
+        linux.namespaces.as_mut().unwrap().push(LinuxNamespace {
+            type_: LinuxNamespaceType::Uts,
+            path: Some("/proc/self/ns/uts".into()),
+        });
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        let err = container.create().unwrap_err();
+        assert_eq!(err.to_string(), "duplicate uts namespace");
+
         testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
     }
 
@@ -236,9 +1533,10 @@ pub mod test {
         testutil::init_spec_file(&bundle, &rootfs).unwrap();
         let spec = specutil::load(&bundle).unwrap();
 
-        let meta_dir = PathBuf::from(DEFAULT_META_ROOT).join(&container_id);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
 
-        let mut container = Container::new(&container_id, &bundle, spec);
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
         let state = container.state().unwrap();
         let owner = User::from_uid(Uid::effective()).unwrap().unwrap();
 
@@ -253,6 +1551,7 @@ pub mod test {
         let state = container.state().unwrap();
         assert!(state.created.is_some());
         assert!(container.start().is_ok());
+        assert!(container.delete().is_ok());
 
         testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
     }
@@ -265,13 +1564,14 @@ pub mod test {
         testutil::init_spec_file(&bundle, &rootfs).unwrap();
         let spec = specutil::load(&bundle).unwrap();
 
-        let meta_dir = PathBuf::from(DEFAULT_META_ROOT).join(&container_id);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
 
-        let mut container = Container::new(&container_id, &bundle, spec);
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
 
         assert!(container.create().is_ok());
 
-        let loaded_container = Container::load(&container.id);
+        let loaded_container = Container::load(testutil::TEST_ROOT.as_ref(), &container.id);
 
         let loaded_container = loaded_container.unwrap();
 
@@ -280,6 +1580,7 @@ pub mod test {
         assert_eq!(loaded_container.bundle, container.bundle);
         assert_eq!(loaded_container.status, container.status);
         assert!(container.start().is_ok());
+        assert!(container.delete().is_ok());
 
         testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
     }
@@ -292,9 +1593,10 @@ pub mod test {
         testutil::init_spec_file(&bundle, &rootfs).unwrap();
         let spec = specutil::load(&bundle).unwrap();
 
-        let meta_dir = PathBuf::from(DEFAULT_META_ROOT).join(&container_id);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
 
-        let mut container = Container::new(&container_id, &bundle, spec);
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
         assert!(container.create().is_ok());
         assert!(container.start().is_ok());
 
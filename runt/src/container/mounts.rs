@@ -0,0 +1,260 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use nix::mount::{mount, MsFlags};
+use nix::sys::stat::Mode;
+
+use crate::container::rootfs::propagation_flags;
+use crate::container::securejoin::{secure_join, RootedPath};
+use crate::container::specs::Mount;
+
+const NONE: Option<&'static str> = None;
+
+/// Mount options of a `specs::Mount` in the form mount(2) takes them.
+#[derive(Debug, PartialEq)]
+pub struct MountOptions {
+    pub flags: MsFlags,
+    /// Propagation types, applied one by one once the mount is made.
+    pub propagation: Vec<MsFlags>,
+    /// Filesystem specific options such as `mode=755`, joined by commas.
+    pub data: String,
+}
+
+impl MountOptions {
+    pub fn parse(options: &[String]) -> MountOptions {
+        let mut parsed = MountOptions {
+            flags: MsFlags::empty(),
+            propagation: vec![],
+            data: String::new(),
+        };
+        let mut data = vec![];
+        for option in options {
+            if let Some(flags) = propagation_flags(option) {
+                parsed.propagation.push(flags);
+            } else if let Some((clear, flag)) = option_flag(option) {
+                if clear {
+                    parsed.flags.remove(flag);
+                } else {
+                    parsed.flags.insert(flag);
+                }
+            } else {
+                data.push(option.as_str());
+            }
+        }
+        parsed.data = data.join(",");
+        parsed
+    }
+
+    fn is_bind(&self) -> bool {
+        self.flags.contains(MsFlags::MS_BIND)
+    }
+
+    fn data(&self) -> Option<&str> {
+        Some(self.data.as_str()).filter(|data| !data.is_empty())
+    }
+}
+
+/// The flag an option sets, or clears when the first value is true.
+fn option_flag(option: &str) -> Option<(bool, MsFlags)> {
+    let flag = match option {
+        "defaults" => (false, MsFlags::empty()),
+        "ro" => (false, MsFlags::MS_RDONLY),
+        "rw" => (true, MsFlags::MS_RDONLY),
+        "nosuid" => (false, MsFlags::MS_NOSUID),
+        "suid" => (true, MsFlags::MS_NOSUID),
+        "nodev" => (false, MsFlags::MS_NODEV),
+        "dev" => (true, MsFlags::MS_NODEV),
+        "noexec" => (false, MsFlags::MS_NOEXEC),
+        "exec" => (true, MsFlags::MS_NOEXEC),
+        "sync" => (false, MsFlags::MS_SYNCHRONOUS),
+        "async" => (true, MsFlags::MS_SYNCHRONOUS),
+        "dirsync" => (false, MsFlags::MS_DIRSYNC),
+        "remount" => (false, MsFlags::MS_REMOUNT),
+        "mand" => (false, MsFlags::MS_MANDLOCK),
+        "nomand" => (true, MsFlags::MS_MANDLOCK),
+        "atime" => (true, MsFlags::MS_NOATIME),
+        "noatime" => (false, MsFlags::MS_NOATIME),
+        "diratime" => (true, MsFlags::MS_NODIRATIME),
+        "nodiratime" => (false, MsFlags::MS_NODIRATIME),
+        "relatime" => (false, MsFlags::MS_RELATIME),
+        "norelatime" => (true, MsFlags::MS_RELATIME),
+        "strictatime" => (false, MsFlags::MS_STRICTATIME),
+        "nostrictatime" => (true, MsFlags::MS_STRICTATIME),
+        "bind" => (false, MsFlags::MS_BIND),
+        "rbind" => (false, MsFlags::MS_BIND | MsFlags::MS_REC),
+        _ => return None,
+    };
+    Some(flag)
+}
+
+/// Perform the mounts of the spec under `rootfs`, in order. Relative bind
+/// sources are taken from `bundle`.
+pub fn mount_all(mounts: &[Mount], bundle: &Path, rootfs: &Path) -> Result<()> {
+    for entry in mounts {
+        mount_entry(entry, bundle, rootfs).with_context(|| {
+            format!("failed to mount {} on {}", entry.source, entry.destination)
+        })?;
+    }
+    Ok(())
+}
+
+fn mount_entry(entry: &Mount, bundle: &Path, rootfs: &Path) -> Result<()> {
+    let mut options = MountOptions::parse(entry.options.as_deref().unwrap_or_default());
+    if entry.type_ == "bind" {
+        options.flags.insert(MsFlags::MS_BIND);
+    }
+    let destination = Path::new(&entry.destination);
+
+    if options.is_bind() {
+        let source = bundle.join(&entry.source);
+        create_destination(rootfs, destination, source.is_dir())?;
+        // Only MS_REC is taken into account by a bind mount, the other
+        // flags need a remount.
+        let flags = options.flags & (MsFlags::MS_BIND | MsFlags::MS_REC);
+        let target = RootedPath::open(rootfs, destination)?;
+        mount(Some(&source), &target.proc_path(), NONE, flags, NONE)?;
+        let remount_flags = options.flags - MsFlags::MS_REC;
+        if remount_flags != MsFlags::MS_BIND {
+            let target = RootedPath::open(rootfs, destination)?;
+            mount(
+                NONE,
+                &target.proc_path(),
+                NONE,
+                remount_flags | MsFlags::MS_REMOUNT,
+                NONE,
+            )?;
+        }
+    } else {
+        create_destination(rootfs, destination, true)?;
+        let target = RootedPath::open(rootfs, destination)?;
+        mount(
+            Some(entry.source.as_str()),
+            &target.proc_path(),
+            Some(entry.type_.as_str()),
+            options.flags,
+            options.data(),
+        )?;
+    }
+
+    if !options.propagation.is_empty() {
+        let target = RootedPath::open(rootfs, destination)?;
+        for flags in options.propagation {
+            mount(NONE, &target.proc_path(), NONE, flags, NONE)?;
+        }
+    }
+    Ok(())
+}
+
+/// Hide `paths` inside `rootfs`: files behind a bind mount of /dev/null
+/// and directories behind an empty read-only tmpfs. Missing paths are
+/// skipped.
+pub fn mask_paths(paths: &[String], rootfs: &Path) -> Result<()> {
+    for path in paths {
+        let path = Path::new(path);
+        if !secure_join(rootfs, path)?.exists() {
+            continue;
+        }
+        let target = RootedPath::open(rootfs, path)?;
+        let result = if target.is_dir()? {
+            mount(
+                Some("tmpfs"),
+                &target.proc_path(),
+                Some("tmpfs"),
+                MsFlags::MS_RDONLY,
+                NONE,
+            )
+        } else {
+            mount(
+                Some("/dev/null"),
+                &target.proc_path(),
+                NONE,
+                MsFlags::MS_BIND,
+                NONE,
+            )
+        };
+        result.with_context(|| format!("failed to mask {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Bind mount `paths` inside `rootfs` onto themselves read-only. Missing
+/// paths are skipped.
+pub fn readonly_paths(paths: &[String], rootfs: &Path) -> Result<()> {
+    for path in paths {
+        let path = Path::new(path);
+        if !secure_join(rootfs, path)?.exists() {
+            continue;
+        }
+        remount_readonly(rootfs, path)
+            .with_context(|| format!("failed to make {} read-only", path.display()))?;
+    }
+    Ok(())
+}
+
+fn remount_readonly(rootfs: &Path, path: &Path) -> Result<()> {
+    let target = RootedPath::open(rootfs, path)?;
+    let flags = MsFlags::MS_BIND | MsFlags::MS_REC;
+    mount(
+        Some(&target.proc_path()),
+        &target.proc_path(),
+        NONE,
+        flags,
+        NONE,
+    )?;
+    // The descriptor opened before the mount refers to what it covers.
+    let target = RootedPath::open(rootfs, path)?;
+    let flags = MsFlags::MS_BIND
+        | MsFlags::MS_REMOUNT
+        | MsFlags::MS_RDONLY
+        | MsFlags::MS_NOSUID
+        | MsFlags::MS_NODEV
+        | MsFlags::MS_NOEXEC;
+    mount(NONE, &target.proc_path(), NONE, flags, NONE)?;
+    Ok(())
+}
+
+/// Create `destination` inside `rootfs` as a directory, or as an empty
+/// file to bind mount a file on.
+fn create_destination(rootfs: &Path, destination: &Path, is_dir: bool) -> Result<()> {
+    if is_dir {
+        RootedPath::mkdir_all(rootfs, destination, Mode::from_bits_truncate(0o755))?;
+    } else if RootedPath::create_file(rootfs, destination)?.is_dir()? {
+        bail!("{} is a directory", destination.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(options: &[&str]) -> MountOptions {
+        let options: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+        MountOptions::parse(&options)
+    }
+
+    #[test]
+    fn options_should_be_parsed_into_flags() {
+        let options = parse(&["nosuid", "strictatime", "mode=755", "size=65536k"]);
+        assert_eq!(options.flags, MsFlags::MS_NOSUID | MsFlags::MS_STRICTATIME);
+        assert_eq!(options.data, "mode=755,size=65536k");
+        assert!(options.propagation.is_empty());
+
+        let options = parse(&["rbind", "ro", "rslave", "private"]);
+        assert_eq!(
+            options.flags,
+            MsFlags::MS_BIND | MsFlags::MS_REC | MsFlags::MS_RDONLY
+        );
+        assert_eq!(
+            options.propagation,
+            vec![MsFlags::MS_SLAVE | MsFlags::MS_REC, MsFlags::MS_PRIVATE]
+        );
+        assert_eq!(options.data(), None);
+    }
+
+    #[test]
+    fn later_options_should_override_earlier_ones() {
+        let options = parse(&["ro", "noexec", "rw", "exec", "nodev"]);
+        assert_eq!(options.flags, MsFlags::MS_NODEV);
+    }
+}
@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use nix::fcntl::{open, OFlag};
+use nix::sched::{setns, unshare, CloneFlags};
+use nix::sys::stat::{stat, Mode};
+use nix::unistd::{close, Pid};
+
+use crate::container;
+use crate::container::specs::{LinuxNamespace, LinuxNamespaceType};
+
+/// Namespace files under /proc/<pid>/ns, in the order they must be joined.
+/// The user namespace comes first so the others can be entered with the
+/// capabilities it grants, and mnt comes last because it changes /proc.
+const NAMESPACES: &[(&str, CloneFlags)] = &[
+    ("user", CloneFlags::CLONE_NEWUSER),
+    ("cgroup", CloneFlags::CLONE_NEWCGROUP),
+    ("ipc", CloneFlags::CLONE_NEWIPC),
+    ("uts", CloneFlags::CLONE_NEWUTS),
+    ("net", CloneFlags::CLONE_NEWNET),
+    ("pid", CloneFlags::CLONE_NEWPID),
+    ("mnt", CloneFlags::CLONE_NEWNS),
+];
+
+/// Join every namespace of `pid` which differs from ours. Joining a pid
+/// namespace only applies to children, so the caller has to fork afterwards.
+/// With `start_time`, the namespaces are only joined if `pid` still started
+/// then, rather than being a reused pid.
+pub fn enter(pid: Pid, start_time: Option<u64>) -> Result<()> {
+    let mut targets = Vec::new();
+    for (name, flag) in NAMESPACES {
+        let target = format!("/proc/{}/ns/{}", pid, name);
+        let own = format!("/proc/self/ns/{}", name);
+        if !Path::new(&own).exists() {
+            // Not supported by this kernel.
+            continue;
+        }
+        let (target_stat, own_stat) = (stat(target.as_str())?, stat(own.as_str())?);
+        if target_stat.st_dev == own_stat.st_dev && target_stat.st_ino == own_stat.st_ino {
+            continue;
+        }
+        // Open everything up front: /proc changes once we are in the mnt namespace.
+        let fd = open(
+            target.as_str(),
+            OFlag::O_RDONLY | OFlag::O_CLOEXEC,
+            Mode::empty(),
+        )?;
+        targets.push((name, fd, *flag));
+    }
+    // Checked once the files are open, so they belong to the process seen.
+    if let Some(start_time) = start_time {
+        if container::start_time(pid).ok() != Some(start_time) {
+            for (_, fd, _) in targets {
+                close(fd)?;
+            }
+            bail!("process {} is not the container init anymore", pid);
+        }
+    }
+
+    for (name, fd, flag) in targets {
+        setns(fd, flag).with_context(|| format!("failed to join {} namespace", name))?;
+        close(fd)?;
+    }
+    Ok(())
+}
+
+/// Name of the namespace file under /proc/<pid>/ns.
+fn name(type_: &LinuxNamespaceType) -> &'static str {
+    match type_ {
+        LinuxNamespaceType::Mount => "mnt",
+        LinuxNamespaceType::Cgroup => "cgroup",
+        LinuxNamespaceType::Uts => "uts",
+        LinuxNamespaceType::Ipc => "ipc",
+        LinuxNamespaceType::User => "user",
+        LinuxNamespaceType::Pid => "pid",
+        LinuxNamespaceType::Network => "net",
+    }
+}
+
+/// The namespace types are defined by their clone(2) flag.
+fn flag(type_: &LinuxNamespaceType) -> CloneFlags {
+    CloneFlags::from_bits_truncate(type_.clone() as libc::c_int)
+}
+
+/// Reject duplicate namespace types and the ones this kernel lacks.
+pub fn validate(namespaces: &[LinuxNamespace]) -> Result<()> {
+    for (i, namespace) in namespaces.iter().enumerate() {
+        let name = name(&namespace.type_);
+        if namespaces[..i].iter().any(|ns| ns.type_ == namespace.type_) {
+            bail!("duplicate {} namespace", name);
+        }
+        if !Path::new("/proc/self/ns").join(name).exists() {
+            bail!("{} namespace is not supported by the kernel", name);
+        }
+    }
+    Ok(())
+}
+
+/// Join the namespaces which have a path and create the others. A new pid
+/// namespace only applies to children, so the caller has to fork afterwards.
+/// The cgroup namespace is left to `unshare_cgroup`.
+pub fn setup(namespaces: &[LinuxNamespace]) -> Result<()> {
+    let mut targets = Vec::new();
+    for (name, flag_) in NAMESPACES {
+        let path = namespaces
+            .iter()
+            .filter(|ns| flag(&ns.type_) == *flag_)
+            .find_map(|ns| ns.path.as_deref());
+        if let Some(path) = path {
+            // Open everything up front: the paths may be under a /proc
+            // which changes once we are in another mnt namespace.
+            let fd = open(path, OFlag::O_RDONLY | OFlag::O_CLOEXEC, Mode::empty())
+                .with_context(|| format!("failed to open {} namespace {}", name, path))?;
+            targets.push((name, path, fd, *flag_));
+        }
+    }
+    for (name, path, fd, flag_) in targets {
+        setns(fd, flag_).with_context(|| format!("failed to join {} namespace {}", name, path))?;
+        close(fd)?;
+    }
+
+    let flags = namespaces
+        .iter()
+        .filter(|ns| ns.path.is_none() && ns.type_ != LinuxNamespaceType::Cgroup)
+        .fold(CloneFlags::empty(), |flags, ns| flags | flag(&ns.type_));
+    unshare(flags).context("failed to create namespaces")?;
+    Ok(())
+}
+
+/// Create the cgroup namespace requested in `namespaces`, if any. This has to
+/// wait until the process is in the container cgroup, which becomes the root
+/// of the namespace.
+pub fn unshare_cgroup(namespaces: &[LinuxNamespace]) -> Result<()> {
+    let new_cgroup = namespaces
+        .iter()
+        .any(|ns| ns.type_ == LinuxNamespaceType::Cgroup && ns.path.is_none());
+    if new_cgroup {
+        unshare(CloneFlags::CLONE_NEWCGROUP).context("failed to create cgroup namespace")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn namespace(type_: LinuxNamespaceType, path: Option<&str>) -> LinuxNamespace {
+        LinuxNamespace {
+            type_,
+            path: path.map(String::from),
+        }
+    }
+
+    #[test]
+    fn flags_should_match_clone_flags() {
+        assert_eq!(flag(&LinuxNamespaceType::Mount), CloneFlags::CLONE_NEWNS);
+        assert_eq!(flag(&LinuxNamespaceType::Network), CloneFlags::CLONE_NEWNET);
+        assert_eq!(flag(&LinuxNamespaceType::User), CloneFlags::CLONE_NEWUSER);
+        assert_eq!(
+            flag(&LinuxNamespaceType::Cgroup),
+            CloneFlags::CLONE_NEWCGROUP
+        );
+    }
+
+    #[test]
+    fn duplicate_namespaces_should_be_rejected() {
+        let namespaces = vec![
+            namespace(LinuxNamespaceType::Pid, None),
+            namespace(LinuxNamespaceType::Network, None),
+            namespace(LinuxNamespaceType::Pid, Some("/proc/1/ns/pid")),
+        ];
+        let err = validate(&namespaces).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate pid namespace");
+
+        assert!(validate(&namespaces[..2]).is_ok());
+    }
+
+    #[test]
+    fn namespaces_of_a_reused_pid_should_not_be_entered() {
+        let err = enter(Pid::this(), Some(0)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("process {} is not the container init anymore", Pid::this())
+        );
+        let start_time = container::start_time(Pid::this()).unwrap();
+        assert!(enter(Pid::this(), Some(start_time)).is_ok());
+    }
+}
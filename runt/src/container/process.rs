@@ -1,71 +1,534 @@
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use nix::errno::Errno;
+use nix::fcntl::{open, OFlag};
 use nix::sys::stat::Mode;
-use nix::unistd::{fork, mkfifo, ForkResult, Pid};
+use nix::sys::wait::waitpid;
+use nix::unistd::AccessFlags;
+use nix::unistd::{access, chdir, close, fork, mkfifo, pipe2, setgid, setgroups, setuid};
+use nix::unistd::{ForkResult, Gid, Pid, Uid};
 
-use crate::container::specs::Process;
+use crate::container::capabilities;
+use crate::container::namespaces;
+use crate::container::rootfs;
+use crate::container::seccomp::notify::Listener;
+use crate::container::seccomp::{self, Filter};
+use crate::container::specs::{LinuxNamespace, Process, RlimitType};
 use crate::container::syscallutils;
+use crate::container::user;
+use crate::container::userns;
+use crate::container::Container;
 
 const START_TRIGGER_FIFO: &str = "start_trigger.fifo";
+const START_TRIGGER_MESSAGE: &[u8] = b"bang";
+const INIT_READY_MESSAGE: &str = "ready";
+/// Sent on the sync pipe once the user namespace exists, and answered once
+/// its ids are mapped.
+const MAP_IDS_MESSAGE: &str = "map ids";
+const IDS_MAPPED_MESSAGE: &str = "ids mapped";
+const OOM_SCORE_ADJ_FILE: &str = "/proc/self/oom_score_adj";
+/// PATH used when the process environment has none.
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
 
-impl Process {
-    pub fn wait_for_writing(&self, bundle: &PathBuf) -> Result<()> {
-        let fifo_path = bundle.join(START_TRIGGER_FIFO);
-        let mut file = File::create(&fifo_path)?;
-        let mode = 0o0660;
-        mkfifo(&fifo_path, Mode::from_bits_truncate(mode))?;
-        file.write_all(b"bang")?;
+/// Read a single line rather than up to EOF: a concurrent fork may hold a
+/// copy of the write end until it execs.
+fn read_message(reader: &mut impl BufRead) -> Result<String> {
+    let mut message = String::new();
+    reader.read_line(&mut message)?;
+    Ok(message.trim_end().to_string())
+}
 
+impl Process {
+    /// Block the container init until `runt start` writes to the trigger fifo.
+    pub fn wait_for_writing(&self, fifo_fd: RawFd) -> Result<()> {
+        let mut fifo = unsafe { File::from_raw_fd(fifo_fd) };
+        let mut buf = [0; START_TRIGGER_MESSAGE.len()];
+        fifo.read_exact(&mut buf)?;
+        if buf != START_TRIGGER_MESSAGE {
+            bail!("unexpected start trigger: {:?}", buf);
+        }
         Ok(())
     }
 
-    pub fn trigger_container_start(&self, bundle: &PathBuf) -> Result<()> {
-        let fifo_path = bundle.join(START_TRIGGER_FIFO);
-        let mut file = File::open(&fifo_path)?;
-        let mut buf = String::new();
-        file.read_to_string(&mut buf)?;
+    /// Release the container init blocked in `wait_for_writing`.
+    pub fn trigger_container_start(&self, state_dir: &Path) -> Result<()> {
+        let fifo_path = state_dir.join(START_TRIGGER_FIFO);
+        if !fifo_path.exists() {
+            bail!("start trigger {} does not exist", fifo_path.display());
+        }
+        // O_NONBLOCK makes open(2) fail with ENXIO instead of hanging
+        // when the init died before it could start reading.
+        let fd = match open(
+            &fifo_path,
+            OFlag::O_WRONLY | OFlag::O_NONBLOCK,
+            Mode::empty(),
+        ) {
+            Ok(fd) => fd,
+            Err(nix::Error::Sys(Errno::ENXIO)) => bail!("container init is not waiting for start"),
+            Err(e) => return Err(e.into()),
+        };
+        let mut fifo = unsafe { File::from_raw_fd(fd) };
+        fifo.write_all(START_TRIGGER_MESSAGE)?;
+        fs::remove_file(&fifo_path)?;
 
         Ok(())
     }
 
-    pub fn spawn(&self, bundle: &PathBuf) -> Result<Option<Pid>> {
+    /// Fork the init of `container` and wait until it is ready to be
+    /// started. The start trigger fifo is created in the state directory.
+    pub fn spawn(&self, container: &Container) -> Result<Pid> {
+        self.validate()?;
+        let filter = container.seccomp().map(seccomp::compile).transpose()?;
+        let namespaces = container.namespaces();
+        namespaces::validate(namespaces)?;
+        userns::validate(&container.spec, namespaces, &container.rootfs())?;
+        let fifo_path = container.state_dir().join(START_TRIGGER_FIFO);
+        if fifo_path.exists() {
+            fs::remove_file(&fifo_path)?;
+        }
+        mkfifo(&fifo_path, Mode::from_bits_truncate(0o600))?;
+        // The init may no longer be allowed to open what runt can once it is
+        // in a user namespace, so open both here for it to inherit. O_RDWR
+        // keeps the fifo open without a writer, so the read in
+        // wait_for_writing blocks until `runt start` instead of hitting EOF.
+        let fifo_fd = open(&fifo_path, OFlag::O_RDWR | OFlag::O_CLOEXEC, Mode::empty())?;
+        let listener = match (&filter, container.seccomp()) {
+            (Some(filter), Some(seccomp)) if filter.notifies() => {
+                Some(Listener::connect(seccomp, container.state()?)?)
+            }
+            _ => None,
+        };
+
+        // An intermediate child sets up the namespaces and reports the pid
+        // of the init it forks into them on the sync pipe. Before that, it
+        // waits on the map pipe for the ids of a new user namespace to be
+        // mapped. The init reports on the ready pipe.
+        let (sync_reader, sync_writer) = pipe2(OFlag::O_CLOEXEC)?;
+        let (map_reader, map_writer) = pipe2(OFlag::O_CLOEXEC)?;
+        let (ready_reader, ready_writer) = pipe2(OFlag::O_CLOEXEC)?;
         match fork()? {
-            ForkResult::Parent { child } => Ok(Some(child)),
+            ForkResult::Parent { child } => {
+                close(fifo_fd)?;
+                close(sync_writer)?;
+                close(map_reader)?;
+                close(ready_writer)?;
+                let mut sync = BufReader::new(unsafe { File::from_raw_fd(sync_reader) });
+                let mut message = read_message(&mut sync)?;
+                let mut map = unsafe { File::from_raw_fd(map_writer) };
+                if message == MAP_IDS_MESSAGE {
+                    // Closing the map pipe on failure makes the child give up.
+                    if let Err(e) = userns::write_mappings(child, &container.spec) {
+                        drop(map);
+                        waitpid(child, None)?;
+                        return Err(e);
+                    }
+                    writeln!(map, "{}", IDS_MAPPED_MESSAGE)?;
+                    message = read_message(&mut sync)?;
+                }
+                drop(map);
+                waitpid(child, None)?;
+                let init = match message.parse() {
+                    Ok(pid) => Pid::from_raw(pid),
+                    Err(_) => bail!("container init failed: {}", message),
+                };
+                let message = read_message(&mut BufReader::new(unsafe {
+                    File::from_raw_fd(ready_reader)
+                }))?;
+                if message != INIT_READY_MESSAGE {
+                    bail!("container init failed: {}", message);
+                }
+                Ok(init)
+            }
             ForkResult::Child => {
-                self.wait_for_writing(bundle)?;
-                syscallutils::execve_(&self.args[0], &self.args, &[])?;
-                Ok(None)
+                close(sync_reader).ok();
+                close(map_writer).ok();
+                close(ready_reader).ok();
+                let mut sync = unsafe { File::from_raw_fd(sync_writer) };
+                let result = namespaces::setup(namespaces)
+                    .and_then(|_| {
+                        if userns::is_new(namespaces) {
+                            wait_for_mappings(&mut sync, map_reader)
+                        } else {
+                            Ok(close(map_reader)?)
+                        }
+                    })
+                    .and_then(|_| Ok(fork()?));
+                match result {
+                    Ok(ForkResult::Parent { child }) => {
+                        let _ = writeln!(sync, "{}", child);
+                        std::process::exit(0)
+                    }
+                    Ok(ForkResult::Child) => {
+                        drop(sync);
+                        self.run_init(container, fifo_fd, listener, ready_writer, filter.as_ref())
+                    }
+                    Err(e) => {
+                        let _ = writeln!(sync, "{}", e.to_string().replace('\n', " "));
+                        std::process::exit(1)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Body of the container init, which never returns.
+    fn run_init(
+        &self,
+        container: &Container,
+        fifo_fd: RawFd,
+        listener: Option<Listener>,
+        ready_writer: RawFd,
+        filter: Option<&Filter>,
+    ) -> ! {
+        let mut ready = unsafe { File::from_raw_fd(ready_writer) };
+        if let Err(e) = rootfs::setup(
+            &container.spec,
+            &container.bundle,
+            &container.rootfs(),
+            container.no_pivot,
+        ) {
+            let _ = writeln!(ready, "{}", e.to_string().replace('\n', " "));
+            std::process::exit(1)
+        }
+        let _ = writeln!(ready, "{}", INIT_READY_MESSAGE);
+        drop(ready);
+
+        // The parent has already returned, so stderr is the only
+        // place left to report a failure.
+        if let Err(e) = self.start(fifo_fd, container.namespaces(), filter, listener.as_ref()) {
+            eprintln!("container init failed: {}", e);
+        }
+        std::process::exit(1)
+    }
+
+    /// Wait for `runt start` and exec the container process.
+    fn start(
+        &self,
+        fifo_fd: RawFd,
+        namespaces: &[LinuxNamespace],
+        filter: Option<&Filter>,
+        listener: Option<&Listener>,
+    ) -> Result<()> {
+        self.wait_for_writing(fifo_fd)?;
+        // By now `runt create` has moved the init into its cgroup.
+        namespaces::unshare_cgroup(namespaces)?;
+        self.exec(filter, listener)
+    }
+
+    /// Switch to the limits, user and capabilities of the process and exec it
+    /// under `filter`, whose listener goes to `listener`.
+    pub fn exec(&self, filter: Option<&Filter>, listener: Option<&Listener>) -> Result<()> {
+        self.set_rlimits()?;
+        // Lowering the score needs CAP_SYS_RESOURCE, so do it as root.
+        self.set_oom_score_adj()?;
+        // Without no_new_privs, loading the filter needs CAP_SYS_ADMIN, which
+        // the user may not keep. It then applies to the rest of the setup.
+        let no_new_privileges = self.no_new_privileges.unwrap_or(false);
+        if let (Some(filter), false) = (filter, no_new_privileges) {
+            filter.install(listener)?;
+        }
+
+        // The bounding set can only be shrunk while still privileged.
+        if let Some(caps) = &self.capabilities {
+            capabilities::drop_bounding(caps)?;
+        }
+        prctl(libc::PR_SET_KEEPCAPS, 1)?;
+        self.set_user()?;
+        prctl(libc::PR_SET_KEEPCAPS, 0)?;
+        if let Some(caps) = &self.capabilities {
+            capabilities::apply(caps)?;
+        }
+
+        chdir(self.cwd.as_str())
+            .with_context(|| format!("failed to change to cwd {}", self.cwd))?;
+        // After this, a seccomp filter can be loaded without CAP_SYS_ADMIN.
+        if no_new_privileges {
+            prctl(libc::PR_SET_NO_NEW_PRIVS, 1)?;
+        }
+
+        let env = self.env.clone().unwrap_or_default();
+        let file = match self.args.first() {
+            Some(file) => file,
+            None => bail!("process args must not be empty"),
+        };
+        let path = find_executable(file, &env)?;
+        if let (Some(filter), true) = (filter, no_new_privileges) {
+            filter.install(listener)?;
+        }
+        syscallutils::execve_(&path.to_string_lossy(), &self.args, &env)?;
+        Ok(())
+    }
+
+    /// Reject what would only make the process fail once forked.
+    pub fn validate(&self) -> Result<()> {
+        if self.args.is_empty() {
+            bail!("process args must not be empty");
+        }
+        for rlimit in self.rlimits.iter().flatten() {
+            rlimit_resource(&rlimit.type_)?;
+            if rlimit.soft > rlimit.hard {
+                bail!(
+                    "soft limit of {} is above its hard limit: {} > {}",
+                    rlimit.type_,
+                    rlimit.soft,
+                    rlimit.hard
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn set_rlimits(&self) -> Result<()> {
+        for rlimit in self.rlimits.iter().flatten() {
+            let resource = rlimit_resource(&rlimit.type_)?;
+            let limit = libc::rlimit {
+                rlim_cur: rlimit.soft,
+                rlim_max: rlimit.hard,
+            };
+            let ret = unsafe { libc::setrlimit(resource, &limit) };
+            Errno::result(ret).with_context(|| format!("failed to set {}", rlimit.type_))?;
+        }
+        Ok(())
+    }
+
+    fn set_oom_score_adj(&self) -> Result<()> {
+        if let Some(score) = self.oom_score_adj {
+            fs::write(OOM_SCORE_ADJ_FILE, score.to_string())
+                .with_context(|| format!("failed to write {}", OOM_SCORE_ADJ_FILE))?;
+        }
+        Ok(())
+    }
+
+    /// Switch to the user of the process. A user name is looked up in the
+    /// container, and grants the groups it is a member of there.
+    fn set_user(&self) -> Result<()> {
+        let (uid, gid, mut groups) = match &self.user.username {
+            Some(name) => {
+                let resolved = user::resolve(name)?;
+                (resolved.uid, resolved.gid, resolved.groups)
+            }
+            None => (self.user.uid, self.user.gid, vec![]),
+        };
+        groups.extend(self.user.additional_gids.iter().flatten());
+        let gids: Vec<Gid> = groups.into_iter().map(Gid::from_raw).collect();
+        // There are no groups to drop where setgroups(2) is denied.
+        if !gids.is_empty() || !userns::setgroups_denied() {
+            setgroups(&gids)?;
+        }
+        setgid(Gid::from_raw(gid))?;
+        setuid(Uid::from_raw(uid))?;
+        Ok(())
+    }
+}
+
+/// Ask the parent on `sync` to map the ids of the user namespace just
+/// created, wait for it on `map_reader`, then become its root.
+fn wait_for_mappings(sync: &mut File, map_reader: RawFd) -> Result<()> {
+    writeln!(sync, "{}", MAP_IDS_MESSAGE)?;
+    let mut map = BufReader::new(unsafe { File::from_raw_fd(map_reader) });
+    if read_message(&mut map)? != IDS_MAPPED_MESSAGE {
+        bail!("failed to map the ids of the user namespace");
+    }
+    setgid(Gid::from_raw(0)).context("failed to switch to gid 0 in the user namespace")?;
+    setuid(Uid::from_raw(0)).context("failed to switch to uid 0 in the user namespace")?;
+    Ok(())
+}
+
+#[cfg(target_env = "gnu")]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type RlimitResource = libc::c_int;
+
+fn rlimit_resource(type_: &RlimitType) -> Result<RlimitResource> {
+    type_.check()?;
+    let resource = match type_ {
+        RlimitType::Cpu => libc::RLIMIT_CPU,
+        RlimitType::Fsize => libc::RLIMIT_FSIZE,
+        RlimitType::Data => libc::RLIMIT_DATA,
+        RlimitType::Stack => libc::RLIMIT_STACK,
+        RlimitType::Core => libc::RLIMIT_CORE,
+        RlimitType::Rss => libc::RLIMIT_RSS,
+        RlimitType::Nproc => libc::RLIMIT_NPROC,
+        RlimitType::Nofile => libc::RLIMIT_NOFILE,
+        RlimitType::Memlock => libc::RLIMIT_MEMLOCK,
+        RlimitType::As => libc::RLIMIT_AS,
+        RlimitType::Locks => libc::RLIMIT_LOCKS,
+        RlimitType::Sigpending => libc::RLIMIT_SIGPENDING,
+        RlimitType::Msgqueue => libc::RLIMIT_MSGQUEUE,
+        RlimitType::Nice => libc::RLIMIT_NICE,
+        RlimitType::Rtprio => libc::RLIMIT_RTPRIO,
+        RlimitType::Rttime => libc::RLIMIT_RTTIME,
+        RlimitType::Unknown(_) => unreachable!("unknown rlimit types are checked"),
+    };
+    Ok(resource)
+}
+
+/// Look `file` up in the PATH of `env` as execvpe(3) would, but with the
+/// environment of the process rather than the one of runt. A name holding
+/// a slash is used as it is.
+fn find_executable(file: &str, env: &[String]) -> Result<PathBuf> {
+    if file.contains('/') {
+        check_executable(Path::new(file))?;
+        return Ok(PathBuf::from(file));
+    }
+    let path_env = env
+        .iter()
+        .find_map(|var| var.strip_prefix("PATH="))
+        .unwrap_or(DEFAULT_PATH);
+    let mut denied = None;
+    for dir in path_env.split(':') {
+        let dir = if dir.is_empty() { "." } else { dir };
+        let candidate = Path::new(dir).join(file);
+        if fs::metadata(&candidate).is_err() {
+            continue;
+        }
+        match check_executable(&candidate) {
+            Ok(()) => return Ok(candidate),
+            Err(e) => {
+                denied.get_or_insert(e);
             }
         }
     }
+    match denied {
+        Some(e) => Err(e),
+        None => bail!("{}: executable file not found in $PATH", file),
+    }
+}
+
+fn check_executable(path: &Path) -> Result<()> {
+    let metadata = match fs::metadata(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            bail!("{}: no such file or directory", path.display())
+        }
+        result => result?,
+    };
+    if metadata.is_dir() || access(path, AccessFlags::X_OK).is_err() {
+        bail!(
+            "{}: permission denied, not an executable file",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn prctl(option: libc::c_int, arg: libc::c_ulong) -> Result<()> {
+    let ret = unsafe { libc::prctl(option, arg, 0, 0, 0) };
+    Errno::result(ret)?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+
+    use std::fs;
 
     use crate::container::specs::Spec;
-    use crate::container::testutil;
+    use crate::container::{testutil, Container};
 
     #[test]
     fn wait_for_writing_should_be_success() {
-        let spec = Spec::default();
-        let mut process = spec.process.clone().unwrap();
-        process.args = vec!["/proc/self/exec".to_string()];
+        let mut spec = Spec::default();
         let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        spec.root.path = rootfs.to_str().unwrap().to_string();
+        let mut process = spec.process.clone().unwrap();
+        process.args = vec!["/bin/true".to_string()];
+        let container = Container::new(&bundle, "state", &bundle, spec);
+        fs::create_dir(container.state_dir()).unwrap();
 
-        if let Some(_pid) = process.spawn(&bundle).unwrap() {
-            for i in 1..5 {
-                let result = process.trigger_container_start(&bundle);
-                if result.is_ok() {
-                    break;
-                }
-                if i == 5 && result.is_err() {
-                    panic!("{:?}", result);
-                }
-            }
+        let _pid = process.spawn(&container).unwrap();
+        assert!(process
+            .trigger_container_start(&container.state_dir())
+            .is_ok());
+        testutil::cleanup(&[&bundle]).unwrap();
+    }
+
+    #[test]
+    fn executable_should_be_looked_up_in_process_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = testutil::init_bundle_dir().unwrap();
+        for (name, mode) in &[
+            ("bin/tool", 0o755),
+            ("data/tool", 0o644),
+            ("data/doc", 0o644),
+        ] {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(*mode)).unwrap();
         }
+        let env = vec![format!("PATH={0}/data:{0}/bin", dir.display())];
+
+        assert_eq!(find_executable("tool", &env).unwrap(), dir.join("bin/tool"));
+        let err = find_executable("doc", &env).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}: permission denied, not an executable file",
+                dir.join("data/doc").display()
+            )
+        );
+        let err = find_executable("missing", &env).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "missing: executable file not found in $PATH"
+        );
+        let err = find_executable("/nonexistent/tool", &env).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/nonexistent/tool: no such file or directory"
+        );
+        // runt's own PATH is not used.
+        assert!(find_executable("sh", &env).is_err());
+        testutil::cleanup(&[&dir]).unwrap();
+    }
+
+    #[test]
+    fn invalid_rlimits_should_be_rejected() {
+        use crate::container::specs::POSIXRlimit;
+
+        let mut process = Spec::default().process.unwrap();
+        assert!(process.validate().is_ok());
+
+        process.rlimits = Some(vec![POSIXRlimit {
+            type_: "RLIMIT_FOO".into(),
+            hard: 1,
+            soft: 1,
+        }]);
+        let err = process.validate().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("unknown rlimit type: RLIMIT_FOO (expected one of RLIMIT_CPU, "));
+
+        process.rlimits = Some(vec![POSIXRlimit {
+            type_: "RLIMIT_NOFILE".into(),
+            hard: 1024,
+            soft: 2048,
+        }]);
+        let err = process.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "soft limit of RLIMIT_NOFILE is above its hard limit: 2048 > 1024"
+        );
+
+        process.rlimits = None;
+        process.args.clear();
+        assert!(process.validate().is_err());
+    }
+
+    #[test]
+    fn trigger_without_init_should_be_failed() {
+        let spec = Spec::default();
+        let process = spec.process.unwrap();
+        let bundle = testutil::init_bundle_dir().unwrap();
+
+        assert!(process.trigger_container_start(&bundle).is_err());
+        testutil::cleanup(&[&bundle]).unwrap();
     }
 }
@@ -0,0 +1,492 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::container::cgroups::{Cgroup, CgroupVersion};
+use crate::container::specs::{
+    LinuxBlockIO, LinuxCPU, LinuxHugepageLimit, LinuxMemory, LinuxResources, LinuxThrottleDevice,
+};
+
+impl Cgroup {
+    /// Write `resources` into the container cgroup. Limits which are not
+    /// set are left as they are.
+    pub fn set(&self, resources: &LinuxResources) -> Result<()> {
+        match self.version() {
+            CgroupVersion::V1 => self.set_v1(resources),
+            CgroupVersion::V2 => self.set_v2(resources),
+        }
+    }
+
+    fn set_v1(&self, resources: &LinuxResources) -> Result<()> {
+        if let Some(memory) = &resources.memory {
+            set_memory_v1(&self.required_path("memory")?, memory)?;
+        }
+        if let Some(cpu) = &resources.cpu {
+            if has_cpu_limits(cpu) {
+                set_cpu_v1(&self.required_path("cpu")?, cpu)?;
+            }
+            if cpu.cpus.is_some() || cpu.mems.is_some() {
+                set_cpuset(&self.required_path("cpuset")?, cpu)?;
+            }
+        }
+        if let Some(pids) = &resources.pids {
+            write(
+                &self.required_path("pids")?,
+                "pids.max",
+                pids_max(pids.limit),
+            )?;
+        }
+        if let Some(block_io) = &resources.block_io {
+            set_blkio_v1(&self.required_path("blkio")?, block_io)?;
+        }
+        if let Some(limits) = &resources.hugepage_limits {
+            set_hugetlb(&self.required_path("hugetlb")?, limits, "limit_in_bytes")?;
+        }
+        Ok(())
+    }
+
+    fn set_v2(&self, resources: &LinuxResources) -> Result<()> {
+        let path = self.required_path("memory")?;
+        if let Some(memory) = &resources.memory {
+            set_memory_v2(&path, memory)?;
+        }
+        if let Some(cpu) = &resources.cpu {
+            set_cpu_v2(&path, cpu)?;
+            set_cpuset(&path, cpu)?;
+        }
+        if let Some(pids) = &resources.pids {
+            write(&path, "pids.max", pids_max(pids.limit))?;
+        }
+        if let Some(block_io) = &resources.block_io {
+            set_io_v2(&path, block_io)?;
+        }
+        if let Some(limits) = &resources.hugepage_limits {
+            set_hugetlb(&path, limits, "max")?;
+        }
+        Ok(())
+    }
+
+    fn required_path(&self, subsystem: &str) -> Result<std::path::PathBuf> {
+        self.subsystem_path(subsystem)?
+            .ok_or_else(|| anyhow!("{} cgroup is not mounted", subsystem))
+    }
+}
+
+/// The limits to write for `update` once merged into `merged`. v2 derives
+/// the swap limit from the memory one, so a swap update carries the memory
+/// limit in effect along.
+pub fn cgroup_update(merged: &LinuxResources, update: &LinuxResources) -> LinuxResources {
+    let mut update = update.clone();
+    if let (Some(memory), Some(merged)) = (update.memory.as_mut(), merged.memory.as_ref()) {
+        if memory.swap.is_some() && memory.limit.is_none() {
+            memory.limit = merged.limit;
+        }
+    }
+    update
+}
+
+/// Overlay the limits set in `update` onto `base`.
+pub fn merge(base: &mut LinuxResources, update: &LinuxResources) {
+    macro_rules! overlay {
+        ($base:expr, $update:expr, $($field:ident),*) => {
+            $(
+                if $update.$field.is_some() {
+                    $base.$field = $update.$field.clone();
+                }
+            )*
+        };
+    }
+
+    if let Some(memory) = &update.memory {
+        let base = base.memory.get_or_insert_with(LinuxMemory::default);
+        overlay!(
+            base,
+            memory,
+            limit,
+            reservation,
+            swap,
+            kernel,
+            kernel_tcp,
+            swappiness,
+            disable_oom_killer
+        );
+    }
+    if let Some(cpu) = &update.cpu {
+        let base = base.cpu.get_or_insert_with(LinuxCPU::default);
+        overlay!(
+            base,
+            cpu,
+            shares,
+            quota,
+            period,
+            realtime_runtime,
+            realtime_period,
+            cpus,
+            mems
+        );
+    }
+    if let Some(block_io) = &update.block_io {
+        let base = base.block_io.get_or_insert_with(LinuxBlockIO::default);
+        overlay!(
+            base,
+            block_io,
+            weight,
+            leaf_weight,
+            weight_device,
+            throttle_read_bps_device,
+            throttle_write_bps_device,
+            throttle_read_iops_device,
+            throttle_write_iops_device
+        );
+    }
+    overlay!(base, update, devices, pids, hugepage_limits, network, rdma);
+}
+
+fn write<T: ToString>(dir: &Path, file: &str, value: T) -> Result<()> {
+    let path = dir.join(file);
+    let value = value.to_string();
+    fs::write(&path, &value)
+        .with_context(|| format!("failed to write {} to {}", value, path.display()))
+}
+
+fn pids_max(limit: i64) -> String {
+    if limit > 0 {
+        limit.to_string()
+    } else {
+        "max".into()
+    }
+}
+
+fn set_memory_v1(path: &Path, memory: &LinuxMemory) -> Result<()> {
+    match (memory.limit, memory.swap) {
+        (Some(limit), Some(swap)) => {
+            // memory.limit_in_bytes may never exceed memory.memsw.limit_in_bytes,
+            // so the order depends on whether the limits grow or shrink.
+            let current: i64 = fs::read_to_string(path.join("memory.memsw.limit_in_bytes"))?
+                .trim()
+                .parse()?;
+            if swap == -1 || swap > current {
+                write(path, "memory.memsw.limit_in_bytes", swap)?;
+                write(path, "memory.limit_in_bytes", limit)?;
+            } else {
+                write(path, "memory.limit_in_bytes", limit)?;
+                write(path, "memory.memsw.limit_in_bytes", swap)?;
+            }
+        }
+        (Some(limit), None) => write(path, "memory.limit_in_bytes", limit)?,
+        (None, Some(swap)) => write(path, "memory.memsw.limit_in_bytes", swap)?,
+        (None, None) => {}
+    }
+    if let Some(reservation) = memory.reservation {
+        write(path, "memory.soft_limit_in_bytes", reservation)?;
+    }
+    if let Some(kernel) = memory.kernel {
+        write(path, "memory.kmem.limit_in_bytes", kernel)?;
+    }
+    if let Some(kernel_tcp) = memory.kernel_tcp {
+        write(path, "memory.kmem.tcp.limit_in_bytes", kernel_tcp)?;
+    }
+    if let Some(swappiness) = memory.swappiness {
+        write(path, "memory.swappiness", swappiness)?;
+    }
+    if let Some(disable_oom_killer) = memory.disable_oom_killer {
+        write(path, "memory.oom_control", disable_oom_killer as u8)?;
+    }
+    Ok(())
+}
+
+/// v2 takes `max` for unlimited where v1 takes -1.
+fn memory_max(value: i64) -> String {
+    if value == -1 {
+        "max".into()
+    } else {
+        value.to_string()
+    }
+}
+
+fn set_memory_v2(path: &Path, memory: &LinuxMemory) -> Result<()> {
+    if let Some(limit) = memory.limit {
+        write(path, "memory.max", memory_max(limit))?;
+    }
+    if let Some(reservation) = memory.reservation {
+        write(path, "memory.low", memory_max(reservation))?;
+    }
+    if let Some(swap) = memory.swap {
+        // The spec limits memory and swap combined, v2 only the swap.
+        let swap_max = match (swap, memory.limit) {
+            (-1, _) => "max".into(),
+            (swap, Some(limit)) if limit > 0 => (swap - limit).max(0).to_string(),
+            (_, _) => return Err(anyhow!("memory swap needs a memory limit on cgroup v2")),
+        };
+        write(path, "memory.swap.max", swap_max)?;
+    }
+    Ok(())
+}
+
+fn has_cpu_limits(cpu: &LinuxCPU) -> bool {
+    cpu.shares.is_some()
+        || cpu.quota.is_some()
+        || cpu.period.is_some()
+        || cpu.realtime_runtime.is_some()
+        || cpu.realtime_period.is_some()
+}
+
+fn set_cpu_v1(path: &Path, cpu: &LinuxCPU) -> Result<()> {
+    if let Some(shares) = cpu.shares {
+        write(path, "cpu.shares", shares)?;
+    }
+    // The period first: the kernel checks the quota against it.
+    if let Some(period) = cpu.period {
+        write(path, "cpu.cfs_period_us", period)?;
+    }
+    if let Some(quota) = cpu.quota {
+        write(path, "cpu.cfs_quota_us", quota)?;
+    }
+    if let Some(period) = cpu.realtime_period {
+        write(path, "cpu.rt_period_us", period)?;
+    }
+    if let Some(runtime) = cpu.realtime_runtime {
+        write(path, "cpu.rt_runtime_us", runtime)?;
+    }
+    Ok(())
+}
+
+/// cpu.shares range [2, 262144] onto cpu.weight range [1, 10000].
+fn shares_to_weight(shares: u64) -> u64 {
+    1 + (shares.clamp(2, 262_144) - 2) * 9999 / 262_142
+}
+
+fn set_cpu_v2(path: &Path, cpu: &LinuxCPU) -> Result<()> {
+    if let Some(shares) = cpu.shares {
+        write(path, "cpu.weight", shares_to_weight(shares))?;
+    }
+    if cpu.quota.is_some() || cpu.period.is_some() {
+        // cpu.max holds "<quota> <period>"; keep whichever is not updated.
+        let current = fs::read_to_string(path.join("cpu.max"))?;
+        let mut fields = current.split_whitespace();
+        let current_quota = fields.next().unwrap_or("max").to_string();
+        let current_period = fields.next().unwrap_or("100000").to_string();
+        let quota = match cpu.quota {
+            Some(quota) if quota > 0 => quota.to_string(),
+            Some(_) => "max".into(),
+            None => current_quota,
+        };
+        let period = cpu
+            .period
+            .map(|period| period.to_string())
+            .unwrap_or(current_period);
+        write(path, "cpu.max", format!("{} {}", quota, period))?;
+    }
+    Ok(())
+}
+
+fn set_cpuset(path: &Path, cpu: &LinuxCPU) -> Result<()> {
+    if let Some(cpus) = &cpu.cpus {
+        write(path, "cpuset.cpus", cpus)?;
+    }
+    if let Some(mems) = &cpu.mems {
+        write(path, "cpuset.mems", mems)?;
+    }
+    Ok(())
+}
+
+fn set_throttle(path: &Path, file: &str, devices: &Option<Vec<LinuxThrottleDevice>>) -> Result<()> {
+    for device in devices.iter().flatten() {
+        write(
+            path,
+            file,
+            format!("{}:{} {}", device.major, device.minor, device.rate),
+        )?;
+    }
+    Ok(())
+}
+
+fn set_blkio_v1(path: &Path, block_io: &LinuxBlockIO) -> Result<()> {
+    if let Some(weight) = block_io.weight {
+        // Kernels using BFQ only provide the bfq prefixed file.
+        let file = if path.join("blkio.weight").exists() {
+            "blkio.weight"
+        } else {
+            "blkio.bfq.weight"
+        };
+        write(path, file, weight)?;
+    }
+    if let Some(leaf_weight) = block_io.leaf_weight {
+        write(path, "blkio.leaf_weight", leaf_weight)?;
+    }
+    for device in block_io.weight_device.iter().flatten() {
+        if let Some(weight) = device.weight {
+            write(
+                path,
+                "blkio.weight_device",
+                format!("{}:{} {}", device.major, device.minor, weight),
+            )?;
+        }
+        if let Some(leaf_weight) = device.leaf_weight {
+            write(
+                path,
+                "blkio.leaf_weight_device",
+                format!("{}:{} {}", device.major, device.minor, leaf_weight),
+            )?;
+        }
+    }
+    set_throttle(
+        path,
+        "blkio.throttle.read_bps_device",
+        &block_io.throttle_read_bps_device,
+    )?;
+    set_throttle(
+        path,
+        "blkio.throttle.write_bps_device",
+        &block_io.throttle_write_bps_device,
+    )?;
+    set_throttle(
+        path,
+        "blkio.throttle.read_iops_device",
+        &block_io.throttle_read_iops_device,
+    )?;
+    set_throttle(
+        path,
+        "blkio.throttle.write_iops_device",
+        &block_io.throttle_write_iops_device,
+    )?;
+    Ok(())
+}
+
+/// blkio.weight range [10, 1000] onto io.weight range [1, 10000].
+fn blkio_weight_to_io_weight(weight: u16) -> u64 {
+    let weight = u64::from(weight.clamp(10, 1000));
+    1 + (weight - 10) * 9999 / 990
+}
+
+fn set_io_v2(path: &Path, block_io: &LinuxBlockIO) -> Result<()> {
+    if let Some(weight) = block_io.weight {
+        write(
+            path,
+            "io.weight",
+            format!("default {}", blkio_weight_to_io_weight(weight)),
+        )?;
+    }
+    for device in block_io.weight_device.iter().flatten() {
+        if let Some(weight) = device.weight {
+            write(
+                path,
+                "io.weight",
+                format!(
+                    "{}:{} {}",
+                    device.major,
+                    device.minor,
+                    blkio_weight_to_io_weight(weight)
+                ),
+            )?;
+        }
+    }
+    let throttles = [
+        ("rbps", &block_io.throttle_read_bps_device),
+        ("wbps", &block_io.throttle_write_bps_device),
+        ("riops", &block_io.throttle_read_iops_device),
+        ("wiops", &block_io.throttle_write_iops_device),
+    ];
+    for (key, devices) in throttles.iter() {
+        for device in devices.iter().flatten() {
+            write(
+                path,
+                "io.max",
+                format!("{}:{} {}={}", device.major, device.minor, key, device.rate),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn set_hugetlb(path: &Path, limits: &[LinuxHugepageLimit], suffix: &str) -> Result<()> {
+    for limit in limits {
+        write(
+            path,
+            &format!("hugetlb.{}.{}", limit.page_size, suffix),
+            limit.limit,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::container::specs::LinuxPids;
+
+    #[test]
+    fn update_should_be_merged() {
+        let mut base = LinuxResources {
+            memory: Some(LinuxMemory {
+                limit: Some(1024),
+                reservation: Some(512),
+                ..LinuxMemory::default()
+            }),
+            pids: Some(LinuxPids { limit: 10 }),
+            ..LinuxResources::default()
+        };
+        let update = LinuxResources {
+            memory: Some(LinuxMemory {
+                limit: Some(2048),
+                ..LinuxMemory::default()
+            }),
+            cpu: Some(LinuxCPU {
+                quota: Some(50000),
+                ..LinuxCPU::default()
+            }),
+            ..LinuxResources::default()
+        };
+
+        merge(&mut base, &update);
+        let memory = base.memory.unwrap();
+        assert_eq!(memory.limit, Some(2048));
+        assert_eq!(memory.reservation, Some(512));
+        assert_eq!(base.cpu.unwrap().quota, Some(50000));
+        assert_eq!(base.pids.unwrap().limit, 10);
+    }
+
+    #[test]
+    fn swap_update_should_use_the_stored_memory_limit() {
+        let mut merged = LinuxResources {
+            memory: Some(LinuxMemory {
+                limit: Some(1024),
+                ..LinuxMemory::default()
+            }),
+            ..LinuxResources::default()
+        };
+        let update = LinuxResources {
+            memory: Some(LinuxMemory {
+                swap: Some(4096),
+                ..LinuxMemory::default()
+            }),
+            ..LinuxResources::default()
+        };
+        merge(&mut merged, &update);
+
+        let path = tempfile::tempdir().unwrap().into_path();
+        let memory = cgroup_update(&merged, &update).memory.unwrap();
+        assert_eq!(memory.limit, Some(1024));
+        set_memory_v2(&path, &memory).unwrap();
+        assert_eq!(
+            fs::read_to_string(path.join("memory.swap.max")).unwrap(),
+            "3072"
+        );
+        assert!(set_memory_v2(&path, update.memory.as_ref().unwrap()).is_err());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn v1_values_should_be_converted_for_v2() {
+        assert_eq!(shares_to_weight(2), 1);
+        assert_eq!(shares_to_weight(1024), 39);
+        assert_eq!(shares_to_weight(262_144), 10000);
+        assert_eq!(blkio_weight_to_io_weight(10), 1);
+        assert_eq!(blkio_weight_to_io_weight(1000), 10000);
+        assert_eq!(pids_max(0), "max");
+        assert_eq!(pids_max(32), "32");
+        assert_eq!(memory_max(-1), "max");
+    }
+}
@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use nix::fcntl::{open, OFlag};
+use nix::mount::{mount, umount2, MntFlags, MsFlags};
+use nix::sys::stat::Mode;
+use nix::sys::statvfs::{statvfs, FsFlags};
+use nix::unistd::{chdir, chroot, close, fchdir, pivot_root};
+
+use crate::container::specs::{LinuxNamespaceType, Spec};
+use crate::container::{devices, mounts};
+
+const NONE: Option<&'static str> = None;
+
+/// Set up the filesystem of the container in `rootfs` and make it the root
+/// of the calling process, which must be in the container mount namespace.
+/// Relative paths of the spec are resolved against `bundle`.
+/// `no_pivot` moves the rootfs over / and chroots instead of pivot_root(2),
+/// which does not work on ramfs.
+pub fn setup(spec: &Spec, bundle: &Path, rootfs: &Path, no_pivot: bool) -> Result<()> {
+    let has_mount_namespace = spec
+        .linux
+        .as_ref()
+        .and_then(|linux| linux.namespaces.as_ref())
+        .is_some_and(|namespaces| {
+            namespaces
+                .iter()
+                .any(|ns| ns.type_ == LinuxNamespaceType::Mount)
+        });
+    if !has_mount_namespace {
+        bail!("a mount namespace is required to set up the rootfs");
+    }
+    let propagation = match spec
+        .linux
+        .as_ref()
+        .and_then(|linux| linux.rootfs_propagation.as_deref())
+    {
+        Some(name) => Some(
+            propagation_flags(name)
+                .with_context(|| format!("unknown rootfs propagation: {}", name))?,
+        ),
+        None => None,
+    };
+
+    prepare_root(rootfs, propagation)?;
+    mounts::mount_all(&spec.mounts, bundle, rootfs)?;
+    devices::setup(spec, rootfs)?;
+    if let Some(linux) = &spec.linux {
+        mounts::mask_paths(linux.masked_paths.as_deref().unwrap_or_default(), rootfs)?;
+        mounts::readonly_paths(linux.readonly_paths.as_deref().unwrap_or_default(), rootfs)?;
+    }
+    if no_pivot {
+        move_root(rootfs)?;
+    } else {
+        pivot_into(rootfs)?;
+    }
+
+    if let Some(flags) = propagation {
+        mount(NONE, "/", NONE, flags, NONE).context("failed to set rootfs propagation")?;
+    }
+    if spec.root.readonly.unwrap_or(false) {
+        remount_readonly(Path::new("/")).context("failed to make the rootfs read-only")?;
+    }
+    Ok(())
+}
+
+/// Mount flags for a propagation type such as `rslave` or `private`.
+pub fn propagation_flags(name: &str) -> Option<MsFlags> {
+    let flags = match name {
+        "shared" => MsFlags::MS_SHARED,
+        "rshared" => MsFlags::MS_SHARED | MsFlags::MS_REC,
+        "slave" => MsFlags::MS_SLAVE,
+        "rslave" => MsFlags::MS_SLAVE | MsFlags::MS_REC,
+        "private" => MsFlags::MS_PRIVATE,
+        "rprivate" => MsFlags::MS_PRIVATE | MsFlags::MS_REC,
+        "unbindable" => MsFlags::MS_UNBINDABLE,
+        "runbindable" => MsFlags::MS_UNBINDABLE | MsFlags::MS_REC,
+        _ => return None,
+    };
+    Some(flags)
+}
+
+/// Keep the mounts made from now on out of the host, then turn the rootfs
+/// into a mount point as pivot_root(2) and MS_MOVE require.
+fn prepare_root(rootfs: &Path, propagation: Option<MsFlags>) -> Result<()> {
+    // A shared rootfs cannot be pivoted into, so it only becomes shared
+    // once the container is in it.
+    let flags = match propagation {
+        Some(flags) if flags.contains(MsFlags::MS_PRIVATE) => flags | MsFlags::MS_REC,
+        _ => MsFlags::MS_SLAVE | MsFlags::MS_REC,
+    };
+    mount(NONE, "/", NONE, flags, NONE).context("failed to make / private")?;
+    mount(
+        Some(rootfs),
+        rootfs,
+        Some("bind"),
+        MsFlags::MS_BIND | MsFlags::MS_REC,
+        NONE,
+    )
+    .with_context(|| format!("failed to bind mount rootfs {}", rootfs.display()))?;
+    Ok(())
+}
+
+/// pivot_root(2) into `rootfs` and detach the old root. Pivoting "." onto
+/// itself stacks the old root under the new one, which saves a directory
+/// for it in the rootfs.
+fn pivot_into(rootfs: &Path) -> Result<()> {
+    let flags = OFlag::O_DIRECTORY | OFlag::O_RDONLY | OFlag::O_CLOEXEC;
+    let old_root = open("/", flags, Mode::empty())?;
+    let new_root = open(rootfs, flags, Mode::empty())?;
+    let result = fchdir(new_root)
+        .and_then(|_| pivot_root(".", "."))
+        .context("failed to pivot_root")
+        .and_then(|_| {
+            fchdir(old_root)?;
+            // Unmounting must not propagate to the host.
+            mount(NONE, ".", NONE, MsFlags::MS_SLAVE | MsFlags::MS_REC, NONE)?;
+            umount2(".", MntFlags::MNT_DETACH).context("failed to detach the old root")
+        });
+    close(old_root)?;
+    close(new_root)?;
+    result?;
+    chdir("/")?;
+    Ok(())
+}
+
+/// Move the rootfs over / and chroot into it.
+fn move_root(rootfs: &Path) -> Result<()> {
+    chdir(rootfs)?;
+    mount(Some(rootfs), "/", NONE, MsFlags::MS_MOVE, NONE)
+        .with_context(|| format!("failed to move rootfs {} to /", rootfs.display()))?;
+    chroot(".")?;
+    chdir("/")?;
+    Ok(())
+}
+
+/// Remount the bind mount at `path` read-only. The flags the mount already
+/// has are kept, since they cannot be cleared without privileges.
+pub fn remount_readonly(path: &Path) -> Result<()> {
+    let flags = MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY | mount_flags(path)?;
+    mount(NONE, path, NONE, flags, NONE)?;
+    Ok(())
+}
+
+/// Flags of the mount `path` is on, in the form mount(2) takes them.
+fn mount_flags(path: &Path) -> Result<MsFlags> {
+    let fs_flags = statvfs(path)?.flags();
+    let mut flags = MsFlags::empty();
+    for (fs_flag, flag) in &[
+        (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+        (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+        (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+        (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+        (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+        (FsFlags::ST_RELATIME, MsFlags::MS_RELATIME),
+    ] {
+        if fs_flags.contains(*fs_flag) {
+            flags |= *flag;
+        }
+    }
+    Ok(flags)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn propagation_should_be_parsed() {
+        assert_eq!(
+            propagation_flags("rslave"),
+            Some(MsFlags::MS_SLAVE | MsFlags::MS_REC)
+        );
+        assert_eq!(propagation_flags("private"), Some(MsFlags::MS_PRIVATE));
+        assert_eq!(propagation_flags("slaves"), None);
+    }
+
+    #[test]
+    fn rootfs_without_mount_namespace_should_be_rejected() {
+        let mut spec = Spec::default();
+        spec.linux.as_mut().unwrap().namespaces = None;
+
+        let err = setup(
+            &spec,
+            Path::new("/nonexistent"),
+            Path::new("/nonexistent"),
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a mount namespace is required to set up the rootfs"
+        );
+    }
+}
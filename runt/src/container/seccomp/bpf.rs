@@ -0,0 +1,108 @@
+use libc::{
+    BPF_ABS, BPF_ALU, BPF_AND, BPF_JA, BPF_JEQ, BPF_JGE, BPF_JGT, BPF_JMP, BPF_K, BPF_LD, BPF_RET,
+    BPF_W,
+};
+
+/// Most instructions the kernel accepts in a filter.
+pub const MAX_INSTRUCTIONS: usize = libc::BPF_MAXINSNS as usize;
+
+/// Conditional jumps comparing the accumulator with a constant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn code(self) -> u32 {
+        match self {
+            Comparison::Equal => BPF_JEQ,
+            Comparison::Greater => BPF_JGT,
+            Comparison::GreaterOrEqual => BPF_JGE,
+        }
+    }
+}
+
+/// A classic BPF instruction, laid out as `struct sock_filter`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instruction {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+impl Instruction {
+    fn new(code: u32, jt: u8, jf: u8, k: u32) -> Instruction {
+        Instruction {
+            code: code as u16,
+            jt,
+            jf,
+            k,
+        }
+    }
+
+    /// Load the 32-bit word at `offset` of `struct seccomp_data`.
+    pub fn load(offset: u32) -> Instruction {
+        Instruction::new(BPF_LD | BPF_W | BPF_ABS, 0, 0, offset)
+    }
+
+    pub fn and(mask: u32) -> Instruction {
+        Instruction::new(BPF_ALU | BPF_AND | BPF_K, 0, 0, mask)
+    }
+
+    /// Skip `jt` instructions when the comparison holds, `jf` otherwise.
+    pub fn jump_if(comparison: Comparison, k: u32, jt: u8, jf: u8) -> Instruction {
+        Instruction::new(BPF_JMP | comparison.code() | BPF_K, jt, jf, k)
+    }
+
+    pub fn jump(offset: u32) -> Instruction {
+        Instruction::new(BPF_JMP | BPF_JA, 0, 0, offset)
+    }
+
+    pub fn ret(value: u32) -> Instruction {
+        Instruction::new(BPF_RET | BPF_K, 0, 0, value)
+    }
+}
+
+/// Run `filter` on `data` the way the kernel does and return the value it
+/// returns. Only the instructions this module emits are supported.
+#[cfg(test)]
+pub fn run(filter: &[Instruction], data: &libc::seccomp_data) -> u32 {
+    let data = unsafe {
+        std::slice::from_raw_parts(
+            data as *const libc::seccomp_data as *const u8,
+            std::mem::size_of::<libc::seccomp_data>(),
+        )
+    };
+    let (mut a, mut pc) = (0u32, 0usize);
+    loop {
+        let insn = filter[pc];
+        pc += 1;
+        let code = insn.code as u32;
+        let k = insn.k as usize;
+        let taken = |holds: bool| if holds { insn.jt } else { insn.jf } as usize;
+        if code == BPF_LD | BPF_W | BPF_ABS {
+            assert_eq!(k % 4, 0, "misaligned load");
+            let mut word = [0; 4];
+            word.copy_from_slice(&data[k..k + 4]);
+            a = u32::from_ne_bytes(word);
+        } else if code == BPF_ALU | BPF_AND | BPF_K {
+            a &= insn.k;
+        } else if code == BPF_JMP | BPF_JA {
+            pc += k;
+        } else if code == BPF_JMP | BPF_JEQ | BPF_K {
+            pc += taken(a == insn.k);
+        } else if code == BPF_JMP | BPF_JGT | BPF_K {
+            pc += taken(a > insn.k);
+        } else if code == BPF_JMP | BPF_JGE | BPF_K {
+            pc += taken(a >= insn.k);
+        } else if code == BPF_RET | BPF_K {
+            return insn.k;
+        } else {
+            panic!("unsupported instruction: {:?}", insn);
+        }
+    }
+}
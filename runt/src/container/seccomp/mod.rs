@@ -0,0 +1,862 @@
+use std::convert::TryFrom;
+use std::os::unix::io::RawFd;
+
+use anyhow::{bail, Context, Result};
+use nix::errno::Errno;
+
+use crate::container::specs::{
+    self, LinuxSeccomp, LinuxSeccompAction, LinuxSeccompArg, LinuxSeccompOperator as Operator,
+};
+
+mod bpf;
+pub mod notify;
+mod syscalls;
+
+use bpf::{Comparison, Instruction};
+use notify::Listener;
+
+const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+const AUDIT_ARCH_I386: u32 = 0x4000_0003;
+const AUDIT_ARCH_AARCH64: u32 = 0xc000_00b7;
+/// Set in the numbers of x32 syscalls, which share the x86_64 audit arch.
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Offsets of the fields of `struct seccomp_data`.
+const NR_OFFSET: u32 = 0;
+const ARCH_OFFSET: u32 = 4;
+const ARGS_OFFSET: u32 = 16;
+const MAX_ARGS: u64 = 6;
+
+/// Returned for the syscalls of architectures the filter doesn't list.
+const BAD_ARCH_ACTION: u32 = libc::SECCOMP_RET_KILL_PROCESS;
+
+/// Made before the agent has the listener, so they cannot wait for it:
+/// sendmsg(2) hands the listener over and write(2) reports a failure to.
+const LISTENER_SYSCALLS: &[&str] = &["sendmsg", "write"];
+
+/// An architecture a filter can match the syscalls of.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Arch {
+    X86_64,
+    X32,
+    X86,
+    Aarch64,
+}
+
+impl Arch {
+    fn parse(arch: &specs::Arch) -> Result<Arch> {
+        arch.check()?;
+        let arch = match arch {
+            specs::Arch::X86_64 => Arch::X86_64,
+            specs::Arch::X32 => Arch::X32,
+            specs::Arch::X86 => Arch::X86,
+            specs::Arch::Aarch64 => Arch::Aarch64,
+            _ => bail!("unsupported seccomp architecture: {}", arch),
+        };
+        Ok(arch)
+    }
+
+    /// Architecture runt is built for, always included in a filter.
+    fn native() -> Option<Arch> {
+        if cfg!(all(target_arch = "x86_64", target_pointer_width = "64")) {
+            Some(Arch::X86_64)
+        } else if cfg!(target_arch = "x86_64") {
+            Some(Arch::X32)
+        } else if cfg!(target_arch = "x86") {
+            Some(Arch::X86)
+        } else if cfg!(target_arch = "aarch64") {
+            Some(Arch::Aarch64)
+        } else {
+            None
+        }
+    }
+
+    fn audit_arch(self) -> u32 {
+        match self {
+            Arch::X86_64 | Arch::X32 => AUDIT_ARCH_X86_64,
+            Arch::X86 => AUDIT_ARCH_I386,
+            Arch::Aarch64 => AUDIT_ARCH_AARCH64,
+        }
+    }
+
+    fn syscall(self, name: &str) -> Option<u32> {
+        let table = match self {
+            Arch::X86_64 => syscalls::X86_64,
+            Arch::X32 => syscalls::X32,
+            Arch::X86 => syscalls::X86,
+            Arch::Aarch64 => syscalls::AARCH64,
+        };
+        let nr = table.iter().find(|(n, _)| *n == name).map(|&(_, nr)| nr)?;
+        match self {
+            Arch::X32 => Some(nr | X32_SYSCALL_BIT),
+            _ => Some(nr),
+        }
+    }
+
+    /// Whether syscall arguments are 64 bits wide. Only the lower half of
+    /// the arguments of 32-bit architectures is compared.
+    fn is_64bit(self) -> bool {
+        match self {
+            Arch::X86_64 | Arch::Aarch64 => true,
+            Arch::X32 | Arch::X86 => false,
+        }
+    }
+}
+
+/// A check of a syscall argument. A masked comparison checks whether
+/// `arg & value == value_two`.
+#[derive(Clone, Debug)]
+struct Condition {
+    index: u32,
+    op: Operator,
+    value: u64,
+    value_two: u64,
+}
+
+impl Condition {
+    fn parse(arg: &LinuxSeccompArg) -> Result<Condition> {
+        if arg.index >= MAX_ARGS {
+            bail!("seccomp argument index out of range: {}", arg.index);
+        }
+        arg.op.check()?;
+        Ok(Condition {
+            index: arg.index as u32,
+            op: arg.op.clone(),
+            value: arg.value,
+            value_two: arg.value_two.unwrap_or(0),
+        })
+    }
+}
+
+struct Rule<'a> {
+    names: &'a [String],
+    action: u32,
+    conditions: Vec<Condition>,
+}
+
+/// Resolve an action such as SCMP_ACT_ERRNO, with `errno_ret` or with the
+/// errno in parentheses as in SCMP_ACT_ERRNO(1), to the value a filter
+/// returns. SCMP_ACT_TRACE takes its message the same way.
+fn parse_action(action: &LinuxSeccompAction, errno_ret: Option<u32>) -> Result<u32> {
+    let (action, data) = match action {
+        LinuxSeccompAction::Unknown(name) => {
+            match name.strip_suffix(')').and_then(|name| name.split_once('(')) {
+                Some((name, data)) => {
+                    let data = data
+                        .parse()
+                        .with_context(|| format!("invalid seccomp action: {}", action))?;
+                    (LinuxSeccompAction::from(name), Some(data))
+                }
+                None => (action.clone(), None),
+            }
+        }
+        _ => (action.clone(), None),
+    };
+    action.check()?;
+    let (ret, takes_data) = match action {
+        LinuxSeccompAction::Kill | LinuxSeccompAction::KillThread => {
+            (libc::SECCOMP_RET_KILL_THREAD, false)
+        }
+        LinuxSeccompAction::KillProcess => (libc::SECCOMP_RET_KILL_PROCESS, false),
+        LinuxSeccompAction::Trap => (libc::SECCOMP_RET_TRAP, false),
+        LinuxSeccompAction::Errno => (libc::SECCOMP_RET_ERRNO, true),
+        LinuxSeccompAction::Trace => (libc::SECCOMP_RET_TRACE, true),
+        LinuxSeccompAction::Log => (libc::SECCOMP_RET_LOG, false),
+        LinuxSeccompAction::Allow => (libc::SECCOMP_RET_ALLOW, false),
+        LinuxSeccompAction::Notify => (libc::SECCOMP_RET_USER_NOTIF, false),
+        LinuxSeccompAction::Unknown(_) => unreachable!("unknown actions are checked"),
+    };
+    match data.or(errno_ret) {
+        None if takes_data => Ok(ret | libc::EPERM as u32),
+        None => Ok(ret),
+        Some(_) if !takes_data => bail!("{} does not take an errno", action),
+        Some(data) if data > libc::SECCOMP_RET_DATA => {
+            bail!("errno of {} is out of range: {}", action, data)
+        }
+        Some(data) => Ok(ret | data),
+    }
+}
+
+fn parse_flag(name: &str) -> Result<libc::c_ulong> {
+    let flag = match name {
+        "SECCOMP_FILTER_FLAG_TSYNC" => libc::SECCOMP_FILTER_FLAG_TSYNC,
+        "SECCOMP_FILTER_FLAG_LOG" => libc::SECCOMP_FILTER_FLAG_LOG,
+        "SECCOMP_FILTER_FLAG_SPEC_ALLOW" => libc::SECCOMP_FILTER_FLAG_SPEC_ALLOW,
+        "SECCOMP_FILTER_FLAG_WAIT_KILLABLE_RECV" => libc::SECCOMP_FILTER_FLAG_WAIT_KILLABLE_RECV,
+        _ => bail!("unknown seccomp flag: {}", name),
+    };
+    Ok(flag)
+}
+
+/// A seccomp filter compiled from the spec, ready to be loaded.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    instructions: Vec<Instruction>,
+    flags: libc::c_ulong,
+}
+
+impl Filter {
+    /// Whether the filter has SCMP_ACT_NOTIFY rules, whose notifications go
+    /// to the agent on the listenerPath.
+    pub fn notifies(&self) -> bool {
+        self.flags & libc::SECCOMP_FILTER_FLAG_NEW_LISTENER != 0
+    }
+
+    /// Load the filter into the calling process and send its listener, if
+    /// any, to `listener`. Unless no_new_privs is set, this needs
+    /// CAP_SYS_ADMIN.
+    pub fn install(&self, listener: Option<&Listener>) -> Result<()> {
+        // Only the syscalls of `LISTENER_SYSCALLS` may be made between
+        // loading the filter and handing the listener over.
+        let mut notify = match (self.notifies(), listener) {
+            (false, _) => None,
+            (true, Some(listener)) => Some((listener, listener.message()?)),
+            (true, None) => bail!("no seccomp agent to send the listener to"),
+        };
+        let prog = libc::sock_fprog {
+            len: self.instructions.len() as u16,
+            filter: self.instructions.as_ptr() as *mut libc::sock_filter,
+        };
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                self.flags,
+                &prog as *const libc::sock_fprog,
+            )
+        };
+        let fd = Errno::result(ret).context("failed to load the seccomp filter")?;
+        // The listener is close-on-exec, so it is left open rather than
+        // closed with one more syscall.
+        if let Some((listener, message)) = notify.as_mut() {
+            listener.send(message, fd as RawFd)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compile `seccomp` into a BPF filter. The syscalls of each architecture
+/// are checked against the rules in the order of the spec and the first
+/// one matching decides; syscalls of architectures which are neither listed
+/// nor native kill the process.
+pub fn compile(seccomp: &LinuxSeccomp) -> Result<Filter> {
+    let native = match Arch::native() {
+        Some(arch) => arch,
+        None => bail!("seccomp is not supported on this architecture"),
+    };
+    let mut arches = vec![native];
+    for name in seccomp.architectures.iter().flatten() {
+        let arch = Arch::parse(name)?;
+        if !arches.contains(&arch) {
+            arches.push(arch);
+        }
+    }
+
+    let default_action = parse_action(&seccomp.default_action, seccomp.default_errno_ret)?;
+    if default_action == libc::SECCOMP_RET_USER_NOTIF {
+        bail!("SCMP_ACT_NOTIFY cannot be the default action");
+    }
+    let mut rules = vec![];
+    for syscall in seccomp.syscalls.iter().flatten() {
+        // Like libseccomp, names unknown to every architecture are left out
+        // so that one profile fits kernels of different ages.
+        for name in &syscall.names {
+            if arches.iter().all(|arch| arch.syscall(name).is_none()) {
+                eprintln!("warning: unknown syscall: {}, ignoring it", name);
+            }
+        }
+        let action = parse_action(&syscall.action, syscall.errno_ret)?;
+        if action == libc::SECCOMP_RET_USER_NOTIF {
+            if let Some(name) = syscall
+                .names
+                .iter()
+                .find(|name| LISTENER_SYSCALLS.contains(&name.as_str()))
+            {
+                bail!("{} cannot use SCMP_ACT_NOTIFY", name);
+            }
+        }
+        rules.push(Rule {
+            names: &syscall.names,
+            action,
+            conditions: syscall
+                .args
+                .iter()
+                .flatten()
+                .map(Condition::parse)
+                .collect::<Result<_>>()?,
+        });
+    }
+
+    // Dispatch on the audit arch, which x86_64 and x32 share.
+    let mut blocks: Vec<(u32, Vec<Instruction>)> = vec![];
+    for arch in &arches {
+        let audit_arch = arch.audit_arch();
+        if blocks.iter().any(|(a, _)| *a == audit_arch) {
+            continue;
+        }
+        let block = if audit_arch == AUDIT_ARCH_X86_64 {
+            let compile_if_listed = |arch| {
+                if arches.contains(&arch) {
+                    compile_arch(arch, &rules, default_action)
+                } else {
+                    Ok(vec![Instruction::ret(BAD_ARCH_ACTION)])
+                }
+            };
+            let x86_64 = compile_if_listed(Arch::X86_64)?;
+            let x32 = compile_if_listed(Arch::X32)?;
+            let mut block = vec![
+                Instruction::load(NR_OFFSET),
+                Instruction::jump_if(Comparison::GreaterOrEqual, X32_SYSCALL_BIT, 0, 1),
+                Instruction::jump(x86_64.len() as u32),
+            ];
+            block.extend(x86_64);
+            block.extend(x32);
+            block
+        } else {
+            compile_arch(*arch, &rules, default_action)?
+        };
+        blocks.push((audit_arch, block));
+    }
+
+    let mut instructions = vec![Instruction::load(ARCH_OFFSET)];
+    let header_len = 1 + 2 * blocks.len() + 1;
+    let mut block_start = header_len;
+    for (audit_arch, block) in &blocks {
+        instructions.push(Instruction::jump_if(Comparison::Equal, *audit_arch, 0, 1));
+        let offset = block_start - instructions.len() - 1;
+        instructions.push(Instruction::jump(offset as u32));
+        block_start += block.len();
+    }
+    instructions.push(Instruction::ret(BAD_ARCH_ACTION));
+    for (_, block) in blocks {
+        instructions.extend(block);
+    }
+    if instructions.len() > bpf::MAX_INSTRUCTIONS {
+        bail!(
+            "seccomp filter is too large: {} instructions",
+            instructions.len()
+        );
+    }
+
+    let mut flags = 0;
+    for name in seccomp.flags.iter().flatten() {
+        flags |= parse_flag(name)?;
+    }
+    if rules
+        .iter()
+        .any(|rule| rule.action == libc::SECCOMP_RET_USER_NOTIF)
+    {
+        if seccomp.listener_path.is_none() {
+            bail!("listenerPath must be set to use SCMP_ACT_NOTIFY");
+        }
+        flags |= libc::SECCOMP_FILTER_FLAG_NEW_LISTENER;
+    }
+    Ok(Filter {
+        instructions,
+        flags,
+    })
+}
+
+/// Check the syscalls of `arch` against every rule. The names unknown to
+/// the architecture are left out.
+fn compile_arch(arch: Arch, rules: &[Rule], default_action: u32) -> Result<Vec<Instruction>> {
+    let mut instructions = vec![];
+    // The syscall number is reloaded after a rule loading its arguments.
+    let mut nr_loaded = false;
+    for rule in rules {
+        for name in rule.names {
+            let nr = match arch.syscall(name) {
+                Some(nr) => nr,
+                None => continue,
+            };
+            let compiled = compile_rule(arch, nr, rule, !nr_loaded)
+                .with_context(|| format!("failed to compile the seccomp rule of {}", name))?;
+            instructions.extend(compiled);
+            nr_loaded = rule.conditions.is_empty();
+        }
+    }
+    instructions.push(Instruction::ret(default_action));
+    Ok(instructions)
+}
+
+/// Where a jump of a rule goes: to the next instruction, past the check it
+/// belongs to, or past the whole rule.
+#[derive(Clone, Copy)]
+enum Target {
+    Next,
+    Satisfied,
+    Unsatisfied,
+}
+
+enum Step {
+    Load(u32),
+    And(u32),
+    JumpIf(Comparison, u32, Target, Target),
+}
+
+/// Return the action of `rule` when the syscall is `nr` and every condition
+/// holds, and fall through to what follows otherwise.
+fn compile_rule(arch: Arch, nr: u32, rule: &Rule, load_nr: bool) -> Result<Vec<Instruction>> {
+    let mut nr_check = vec![];
+    if load_nr {
+        nr_check.push(Step::Load(NR_OFFSET));
+    }
+    nr_check.push(Step::JumpIf(
+        Comparison::Equal,
+        nr,
+        Target::Satisfied,
+        Target::Unsatisfied,
+    ));
+    let mut checks = vec![nr_check];
+    for condition in &rule.conditions {
+        checks.push(condition_steps(condition, arch.is_64bit()));
+    }
+
+    let len = checks.iter().map(Vec::len).sum::<usize>() + 1;
+    let mut instructions = Vec::with_capacity(len);
+    for check in checks {
+        let check_end = instructions.len() + check.len();
+        for step in check {
+            let pos = instructions.len();
+            let offset = |target| {
+                let end = match target {
+                    Target::Next => return Ok(0),
+                    Target::Satisfied => check_end,
+                    Target::Unsatisfied => len,
+                };
+                u8::try_from(end - pos - 1).context("too many argument conditions")
+            };
+            instructions.push(match step {
+                Step::Load(offset) => Instruction::load(offset),
+                Step::And(mask) => Instruction::and(mask),
+                Step::JumpIf(comparison, k, jt, jf) => {
+                    Instruction::jump_if(comparison, k, offset(jt)?, offset(jf)?)
+                }
+            });
+        }
+    }
+    instructions.push(Instruction::ret(rule.action));
+    Ok(instructions)
+}
+
+/// Compare an argument word by word, the upper half first. All the
+/// supported architectures are little-endian.
+fn condition_steps(condition: &Condition, is_64bit: bool) -> Vec<Step> {
+    use Comparison::*;
+    use Target::*;
+
+    let offset = ARGS_OFFSET + 8 * condition.index;
+    let (value, value_two) = (condition.value, condition.value_two);
+    let (high, low) = ((value >> 32) as u32, value as u32);
+    let (high_two, low_two) = ((value_two >> 32) as u32, value_two as u32);
+
+    let mut steps = vec![];
+    if is_64bit {
+        // Decide on the upper half unless it is equal.
+        steps.push(Step::Load(offset + 4));
+        match condition.op {
+            Operator::Equal => steps.push(Step::JumpIf(Equal, high, Next, Unsatisfied)),
+            Operator::NotEqual => steps.push(Step::JumpIf(Equal, high, Next, Satisfied)),
+            Operator::GreaterThan | Operator::GreaterOrEqual => {
+                steps.push(Step::JumpIf(Greater, high, Satisfied, Next));
+                steps.push(Step::JumpIf(Equal, high, Next, Unsatisfied));
+            }
+            Operator::LessThan | Operator::LessOrEqual => {
+                steps.push(Step::JumpIf(GreaterOrEqual, high, Next, Satisfied));
+                steps.push(Step::JumpIf(Equal, high, Next, Unsatisfied));
+            }
+            Operator::MaskedEqual => {
+                steps.push(Step::And(high));
+                steps.push(Step::JumpIf(Equal, high_two, Next, Unsatisfied));
+            }
+            Operator::Unknown(_) => unreachable!("unknown operators are checked"),
+        }
+    }
+    steps.push(Step::Load(offset));
+    let step = match condition.op {
+        Operator::Equal => Step::JumpIf(Equal, low, Satisfied, Unsatisfied),
+        Operator::NotEqual => Step::JumpIf(Equal, low, Unsatisfied, Satisfied),
+        Operator::GreaterThan => Step::JumpIf(Greater, low, Satisfied, Unsatisfied),
+        Operator::GreaterOrEqual => Step::JumpIf(GreaterOrEqual, low, Satisfied, Unsatisfied),
+        Operator::LessThan => Step::JumpIf(GreaterOrEqual, low, Unsatisfied, Satisfied),
+        Operator::LessOrEqual => Step::JumpIf(Greater, low, Unsatisfied, Satisfied),
+        Operator::MaskedEqual => {
+            steps.push(Step::And(low));
+            Step::JumpIf(Equal, low_two, Satisfied, Unsatisfied)
+        }
+        Operator::Unknown(_) => unreachable!("unknown operators are checked"),
+    };
+    steps.push(step);
+    steps
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::container::specs::LinuxSyscall;
+
+    const EPERM: u32 = libc::SECCOMP_RET_ERRNO | libc::EPERM as u32;
+
+    fn seccomp(default_action: &str, arches: &[&str], syscalls: Vec<LinuxSyscall>) -> LinuxSeccomp {
+        LinuxSeccomp {
+            default_action: default_action.into(),
+            default_errno_ret: None,
+            architectures: Some(arches.iter().map(|&arch| arch.into()).collect()),
+            flags: None,
+            listener_path: None,
+            listener_metadata: None,
+            syscalls: Some(syscalls),
+        }
+    }
+
+    fn syscall(names: &[&str], action: &str, args: &[(u64, u64, u64, &str)]) -> LinuxSyscall {
+        LinuxSyscall {
+            names: names.iter().map(|&name| name.into()).collect(),
+            action: action.into(),
+            errno_ret: None,
+            args: Some(
+                args.iter()
+                    .map(|&(index, value, value_two, op)| LinuxSeccompArg {
+                        index,
+                        value,
+                        value_two: Some(value_two),
+                        op: op.into(),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    fn run(filter: &Filter, arch: Arch, name: &str, args: [u64; 6]) -> u32 {
+        let data = libc::seccomp_data {
+            nr: arch.syscall(name).unwrap() as i32,
+            arch: arch.audit_arch(),
+            instruction_pointer: 0,
+            args,
+        };
+        bpf::run(&filter.instructions, &data)
+    }
+
+    /// An architecture other than the native one.
+    fn foreign_arch() -> Arch {
+        match Arch::native() {
+            Some(Arch::Aarch64) => Arch::X86,
+            _ => Arch::Aarch64,
+        }
+    }
+
+    #[test]
+    fn actions_should_be_resolved() {
+        let cases = [
+            ("SCMP_ACT_KILL", None, libc::SECCOMP_RET_KILL_THREAD),
+            ("SCMP_ACT_KILL_THREAD", None, libc::SECCOMP_RET_KILL_THREAD),
+            (
+                "SCMP_ACT_KILL_PROCESS",
+                None,
+                libc::SECCOMP_RET_KILL_PROCESS,
+            ),
+            ("SCMP_ACT_TRAP", None, libc::SECCOMP_RET_TRAP),
+            ("SCMP_ACT_ERRNO", None, EPERM),
+            ("SCMP_ACT_ERRNO", Some(38), libc::SECCOMP_RET_ERRNO | 38),
+            ("SCMP_ACT_ERRNO(13)", None, libc::SECCOMP_RET_ERRNO | 13),
+            ("SCMP_ACT_TRACE", None, libc::SECCOMP_RET_TRACE | 1),
+            ("SCMP_ACT_TRACE(7)", None, libc::SECCOMP_RET_TRACE | 7),
+            ("SCMP_ACT_LOG", None, libc::SECCOMP_RET_LOG),
+            ("SCMP_ACT_ALLOW", None, libc::SECCOMP_RET_ALLOW),
+            ("SCMP_ACT_NOTIFY", None, libc::SECCOMP_RET_USER_NOTIF),
+        ];
+        for &(action, errno_ret, expected) in &cases {
+            assert_eq!(
+                parse_action(&action.into(), errno_ret).unwrap(),
+                expected,
+                "{}",
+                action
+            );
+        }
+
+        let err = |action: &str, errno_ret| {
+            parse_action(&action.into(), errno_ret)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            err("SCMP_ACT_FOO", None),
+            format!(
+                "unknown seccomp action: SCMP_ACT_FOO (expected one of {})",
+                LinuxSeccompAction::VALUES.join(", ")
+            )
+        );
+        assert_eq!(
+            err("SCMP_ACT_ERRNO(x)", None),
+            "invalid seccomp action: SCMP_ACT_ERRNO(x)"
+        );
+        assert_eq!(
+            err("SCMP_ACT_ALLOW", Some(1)),
+            "SCMP_ACT_ALLOW does not take an errno"
+        );
+        assert_eq!(
+            err("SCMP_ACT_ERRNO", Some(0x10000)),
+            "errno of SCMP_ACT_ERRNO is out of range: 65536"
+        );
+    }
+
+    #[test]
+    fn first_matching_rule_should_decide() {
+        let filter = compile(&seccomp(
+            "SCMP_ACT_ALLOW",
+            &[],
+            vec![
+                syscall(
+                    &["mkdir", "mkdirat"],
+                    "SCMP_ACT_ERRNO",
+                    &[(1, 0o700, 0, "SCMP_CMP_EQ")],
+                ),
+                syscall(&["mkdirat"], "SCMP_ACT_KILL_PROCESS", &[]),
+                syscall(&["mkdirat"], "SCMP_ACT_LOG", &[]),
+            ],
+        ))
+        .unwrap();
+        let native = Arch::native().unwrap();
+        let run = |name, mode| run(&filter, native, name, [0, mode, 0, 0, 0, 0]);
+        assert_eq!(run("mkdirat", 0o700), EPERM);
+        assert_eq!(run("mkdirat", 0o755), libc::SECCOMP_RET_KILL_PROCESS);
+        assert_eq!(run("getpid", 0o700), libc::SECCOMP_RET_ALLOW);
+        if native.syscall("mkdir").is_some() {
+            assert_eq!(run("mkdir", 0o755), libc::SECCOMP_RET_ALLOW);
+        }
+    }
+
+    #[test]
+    fn comparisons_should_cover_64_bit_arguments() {
+        const VALUE: u64 = 0x1_0000_0005;
+        const MASK: u64 = 0xf0_0000_00f0;
+        const MASKED: u64 = 0x10_0000_0020;
+        let args = [
+            0,
+            4,
+            5,
+            6,
+            0xffff_ffff,
+            0x1_0000_0000,
+            0x1_0000_0004,
+            VALUE,
+            0x1_0000_0006,
+            0x2_0000_0005,
+            0x17_0000_0025,
+            0x10_0000_0021,
+            u64::MAX,
+        ];
+        let holds = |op, arg| match op {
+            "SCMP_CMP_NE" => arg != VALUE,
+            "SCMP_CMP_LT" => arg < VALUE,
+            "SCMP_CMP_LE" => arg <= VALUE,
+            "SCMP_CMP_EQ" => arg == VALUE,
+            "SCMP_CMP_GE" => arg >= VALUE,
+            "SCMP_CMP_GT" => arg > VALUE,
+            "SCMP_CMP_MASKED_EQ" => arg & MASK == MASKED,
+            _ => unreachable!(),
+        };
+        for &op in &[
+            "SCMP_CMP_NE",
+            "SCMP_CMP_LT",
+            "SCMP_CMP_LE",
+            "SCMP_CMP_EQ",
+            "SCMP_CMP_GE",
+            "SCMP_CMP_GT",
+            "SCMP_CMP_MASKED_EQ",
+        ] {
+            let (value, value_two) = match op {
+                "SCMP_CMP_MASKED_EQ" => (MASK, MASKED),
+                _ => (VALUE, 0),
+            };
+            let filter = compile(&seccomp(
+                "SCMP_ACT_ALLOW",
+                &["SCMP_ARCH_X86_64"],
+                vec![syscall(
+                    &["read"],
+                    "SCMP_ACT_ERRNO",
+                    &[(2, value, value_two, op)],
+                )],
+            ))
+            .unwrap();
+            for &arg in &args {
+                let expected = if holds(op, arg) {
+                    EPERM
+                } else {
+                    libc::SECCOMP_RET_ALLOW
+                };
+                let ret = run(&filter, Arch::X86_64, "read", [0, 0, arg, 0, 0, 0]);
+                assert_eq!(ret, expected, "{} {:#x}", op, arg);
+            }
+        }
+    }
+
+    #[test]
+    fn every_condition_of_a_rule_should_hold() {
+        let filter = compile(&seccomp(
+            "SCMP_ACT_ALLOW",
+            &["SCMP_ARCH_AARCH64"],
+            vec![syscall(
+                &["openat"],
+                "SCMP_ACT_ERRNO",
+                &[
+                    (0, 3, 0, "SCMP_CMP_EQ"),
+                    (2, 0o3, 0o1, "SCMP_CMP_MASKED_EQ"),
+                ],
+            )],
+        ))
+        .unwrap();
+        let run = |fd, flags| run(&filter, Arch::Aarch64, "openat", [fd, 0, flags, 0, 0, 0]);
+        assert_eq!(run(3, 0o101), EPERM);
+        assert_eq!(run(3, 0o102), libc::SECCOMP_RET_ALLOW);
+        assert_eq!(run(4, 0o101), libc::SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn only_lower_halves_should_be_compared_on_32_bit_archs() {
+        let filter = compile(&seccomp(
+            "SCMP_ACT_ALLOW",
+            &["SCMP_ARCH_X86"],
+            vec![syscall(
+                &["close"],
+                "SCMP_ACT_ERRNO",
+                &[(0, 5, 0, "SCMP_CMP_EQ")],
+            )],
+        ))
+        .unwrap();
+        let run = |fd| run(&filter, Arch::X86, "close", [fd, 0, 0, 0, 0, 0]);
+        assert_eq!(run(5), EPERM);
+        assert_eq!(run(0x1_0000_0005), EPERM);
+        assert_eq!(run(6), libc::SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn unlisted_archs_should_be_killed() {
+        let foreign = foreign_arch();
+        let filter = compile(&seccomp("SCMP_ACT_ALLOW", &[], vec![])).unwrap();
+        let ret = run(&filter, foreign, "read", [0; 6]);
+        assert_eq!(ret, libc::SECCOMP_RET_KILL_PROCESS);
+
+        let name = format!("SCMP_ARCH_{:?}", foreign).to_uppercase();
+        let filter = compile(&seccomp("SCMP_ACT_ALLOW", &[&name], vec![])).unwrap();
+        assert_eq!(
+            run(&filter, foreign, "read", [0; 6]),
+            libc::SECCOMP_RET_ALLOW
+        );
+    }
+
+    #[test]
+    fn x32_syscalls_should_be_told_apart() {
+        let rules = || vec![syscall(&["getpid"], "SCMP_ACT_ERRNO", &[])];
+        let filter = compile(&seccomp(
+            "SCMP_ACT_LOG",
+            &["SCMP_ARCH_X86_64", "SCMP_ARCH_X32"],
+            rules(),
+        ))
+        .unwrap();
+        assert_eq!(run(&filter, Arch::X86_64, "getpid", [0; 6]), EPERM);
+        assert_eq!(run(&filter, Arch::X32, "getpid", [0; 6]), EPERM);
+        assert_eq!(
+            run(&filter, Arch::X32, "read", [0; 6]),
+            libc::SECCOMP_RET_LOG
+        );
+
+        let filter = compile(&seccomp("SCMP_ACT_LOG", &["SCMP_ARCH_X86_64"], rules())).unwrap();
+        assert_eq!(
+            run(&filter, Arch::X32, "getpid", [0; 6]),
+            libc::SECCOMP_RET_KILL_PROCESS
+        );
+    }
+
+    #[test]
+    fn invalid_rules_should_be_rejected() {
+        let err = |arches: &[&str], syscall| {
+            compile(&seccomp("SCMP_ACT_ALLOW", arches, vec![syscall]))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            err(
+                &["SCMP_ARCH_PPC64"],
+                syscall(&["read"], "SCMP_ACT_LOG", &[])
+            ),
+            "unsupported seccomp architecture: SCMP_ARCH_PPC64"
+        );
+        assert_eq!(
+            err(
+                &[],
+                syscall(&["read"], "SCMP_ACT_LOG", &[(0, 1, 0, "SCMP_CMP_FOO")])
+            ),
+            "unknown seccomp operator: SCMP_CMP_FOO (expected one of SCMP_CMP_NE, SCMP_CMP_LT, \
+             SCMP_CMP_LE, SCMP_CMP_EQ, SCMP_CMP_GE, SCMP_CMP_GT, SCMP_CMP_MASKED_EQ)"
+        );
+        assert_eq!(
+            err(
+                &[],
+                syscall(&["read"], "SCMP_ACT_LOG", &[(6, 1, 0, "SCMP_CMP_EQ")])
+            ),
+            "seccomp argument index out of range: 6"
+        );
+    }
+
+    #[test]
+    fn unknown_syscalls_should_be_ignored() {
+        let rules = vec![syscall(&["no_such_syscall", "getpid"], "SCMP_ACT_LOG", &[])];
+        let filter = compile(&seccomp("SCMP_ACT_ALLOW", &[], rules)).unwrap();
+        assert_eq!(
+            run(&filter, Arch::X86_64, "getpid", [0; 6]),
+            libc::SECCOMP_RET_LOG
+        );
+        assert_eq!(
+            run(&filter, Arch::X86_64, "getppid", [0; 6]),
+            libc::SECCOMP_RET_ALLOW
+        );
+    }
+
+    #[test]
+    fn notify_should_need_a_listener() {
+        let notify = || vec![syscall(&["mknodat", "mount"], "SCMP_ACT_NOTIFY", &[])];
+        let err = compile(&seccomp("SCMP_ACT_ALLOW", &[], notify())).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "listenerPath must be set to use SCMP_ACT_NOTIFY"
+        );
+
+        let mut spec = seccomp("SCMP_ACT_ALLOW", &[], notify());
+        spec.listener_path = Some("/run/agent.sock".into());
+        spec.flags = Some(vec!["SECCOMP_FILTER_FLAG_LOG".into()]);
+        let filter = compile(&spec).unwrap();
+        assert!(filter.notifies());
+        assert_eq!(
+            filter.flags,
+            libc::SECCOMP_FILTER_FLAG_LOG | libc::SECCOMP_FILTER_FLAG_NEW_LISTENER
+        );
+        let native = Arch::native().unwrap();
+        assert_eq!(
+            run(&filter, native, "mknodat", [0; 6]),
+            libc::SECCOMP_RET_USER_NOTIF
+        );
+
+        spec.flags = Some(vec!["SECCOMP_FILTER_FLAG_FOO".into()]);
+        let err = compile(&spec).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown seccomp flag: SECCOMP_FILTER_FLAG_FOO"
+        );
+        spec.flags = None;
+        spec.syscalls = Some(vec![syscall(&["sendmsg"], "SCMP_ACT_NOTIFY", &[])]);
+        let err = compile(&spec).unwrap_err();
+        assert_eq!(err.to_string(), "sendmsg cannot use SCMP_ACT_NOTIFY");
+        spec.syscalls = Some(vec![syscall(&["close", "write"], "SCMP_ACT_NOTIFY", &[])]);
+        let err = compile(&spec).unwrap_err();
+        assert_eq!(err.to_string(), "write cannot use SCMP_ACT_NOTIFY");
+        spec.syscalls = None;
+        spec.default_action = "SCMP_ACT_NOTIFY".into();
+        let err = compile(&spec).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "SCMP_ACT_NOTIFY cannot be the default action"
+        );
+    }
+}
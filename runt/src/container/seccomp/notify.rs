@@ -0,0 +1,197 @@
+use std::fs::File;
+use std::mem;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::ptr;
+
+use anyhow::{bail, Context, Result};
+use nix::errno::Errno;
+use nix::fcntl::readlinkat;
+
+use crate::container::specs::{ContainerProcessState, LinuxSeccomp, State, OCI_VERSION};
+
+/// Name of the listener fd in `ContainerProcessState::fds`.
+pub const SECCOMP_FD_NAME: &str = "seccompFd";
+
+/// Connection to the agent on the listenerPath of the spec. It is made on
+/// the host, before the container process leaves its filesystem.
+pub struct Listener {
+    socket: UnixStream,
+    /// /proc of the host, to tell the pid of the process sending the fd.
+    proc_dir: File,
+    metadata: Option<String>,
+    state: State,
+}
+
+impl Listener {
+    pub fn connect(seccomp: &LinuxSeccomp, state: State) -> Result<Listener> {
+        let path = seccomp
+            .listener_path
+            .as_deref()
+            .context("listenerPath must be set to use SCMP_ACT_NOTIFY")?;
+        let socket = UnixStream::connect(path)
+            .with_context(|| format!("failed to connect to the seccomp agent on {}", path))?;
+        Ok(Listener {
+            socket,
+            proc_dir: File::open("/proc")?,
+            metadata: seccomp.listener_metadata.clone(),
+            state,
+        })
+    }
+
+    /// Build the message for the calling process, which is about to load
+    /// the filter.
+    pub fn message(&self) -> Result<Message> {
+        let pid = readlinkat(self.proc_dir.as_raw_fd(), "self")?
+            .to_string_lossy()
+            .parse()
+            .context("failed to find the pid of the container process")?;
+        let mut state = self.state.clone();
+        // The container process is the init unless the container runs.
+        state.pid = state.pid.or(Some(pid));
+        let state = serde_json::to_vec(&ContainerProcessState {
+            oci_version: OCI_VERSION.into(),
+            fds: vec![SECCOMP_FD_NAME.into()],
+            pid,
+            metadata: self.metadata.clone(),
+            state,
+        })?;
+        let space = unsafe { libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) };
+        Ok(Message {
+            state,
+            control: vec![0; space as usize],
+        })
+    }
+
+    /// Hand `fd`, the listener of the filter just loaded, to the agent along
+    /// with `message` in a single sendmsg(2).
+    pub fn send(&self, message: &mut Message, fd: RawFd) -> Result<()> {
+        let mut iov = libc::iovec {
+            iov_base: message.state.as_mut_ptr() as *mut libc::c_void,
+            iov_len: message.state.len(),
+        };
+        let mut header: libc::msghdr = unsafe { mem::zeroed() };
+        header.msg_iov = &mut iov;
+        header.msg_iovlen = 1;
+        header.msg_control = message.control.as_mut_ptr() as *mut libc::c_void;
+        header.msg_controllen = message.control.len();
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&header);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as usize;
+            ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+        }
+        let ret = unsafe { libc::sendmsg(self.socket.as_raw_fd(), &header, 0) };
+        let sent = Errno::result(ret).context("failed to send the seccomp listener")?;
+        if sent as usize != message.state.len() {
+            bail!("the seccomp agent only got part of the container state");
+        }
+        Ok(())
+    }
+}
+
+/// What the agent gets along with the listener. It is built before the
+/// filter is loaded: until the agent has the listener, any syscall it is
+/// notified of waits forever, and allocating memory may need brk(2) or
+/// mmap(2).
+pub struct Message {
+    state: Vec<u8>,
+    control: Vec<u8>,
+}
+
+/// A seccomp agent for tests, answering the notifications of a single
+/// container.
+#[cfg(test)]
+pub mod agent {
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+    use std::os::unix::net::UnixListener;
+    use std::path::Path;
+    use std::thread::{self, JoinHandle};
+
+    use nix::poll::{poll, PollFd, PollFlags};
+    use nix::sys::socket::{recvmsg, ControlMessageOwned, MsgFlags};
+    use nix::sys::uio::IoVec;
+
+    use super::*;
+
+    /// What the agent got: the state sent along with the listener and the
+    /// syscall numbers of the notifications.
+    pub type Received = (ContainerProcessState, Vec<i32>);
+
+    /// Listen on `path` and answer each notification with `respond`, whose
+    /// `Err` is an errno, until the processes under the filter are gone.
+    pub fn spawn<F>(path: &Path, respond: F) -> Result<JoinHandle<Result<Received>>>
+    where
+        F: Fn(&libc::seccomp_notif) -> std::result::Result<i64, i32> + Send + 'static,
+    {
+        let socket = UnixListener::bind(path)?;
+        Ok(thread::spawn(move || {
+            let (stream, _) = socket.accept()?;
+            let (fd, message) = receive(&stream)?;
+            let listener = unsafe { File::from_raw_fd(fd) };
+            // Read the rest of the state, if any, without waiting for EOF:
+            // the process may be notified before it execs.
+            let reader = message.as_slice().chain(&stream);
+            let state = match serde_json::Deserializer::from_reader(reader)
+                .into_iter()
+                .next()
+            {
+                Some(state) => state?,
+                None => bail!("no state received from the runtime"),
+            };
+
+            let mut syscalls = vec![];
+            loop {
+                let mut fds = [PollFd::new(listener.as_raw_fd(), PollFlags::POLLIN)];
+                poll(&mut fds, -1)?;
+                let revents = fds[0].revents().unwrap_or_else(PollFlags::empty);
+                if !revents.contains(PollFlags::POLLIN) {
+                    break;
+                }
+                let mut notif: libc::seccomp_notif = unsafe { std::mem::zeroed() };
+                let ret = unsafe {
+                    libc::ioctl(
+                        listener.as_raw_fd(),
+                        libc::SECCOMP_IOCTL_NOTIF_RECV,
+                        &mut notif,
+                    )
+                };
+                if Errno::result(ret).is_err() {
+                    // The process may have died meanwhile.
+                    continue;
+                }
+                syscalls.push(notif.data.nr);
+                let mut resp: libc::seccomp_notif_resp = unsafe { std::mem::zeroed() };
+                resp.id = notif.id;
+                match respond(&notif) {
+                    Ok(val) => resp.val = val,
+                    Err(errno) => resp.error = -errno,
+                }
+                unsafe { libc::ioctl(listener.as_raw_fd(), libc::SECCOMP_IOCTL_NOTIF_SEND, &resp) };
+            }
+            Ok((state, syscalls))
+        }))
+    }
+
+    fn receive(stream: &UnixStream) -> Result<(RawFd, Vec<u8>)> {
+        let mut buf = vec![0; 4096];
+        let mut cmsg = nix::cmsg_space!([RawFd; 1]);
+        let msg = recvmsg(
+            stream.as_raw_fd(),
+            &[IoVec::from_mut_slice(&mut buf)],
+            Some(&mut cmsg),
+            MsgFlags::empty(),
+        )?;
+        let fds = msg.cmsgs().find_map(|cmsg| match cmsg {
+            ControlMessageOwned::ScmRights(fds) => fds.first().copied(),
+            _ => None,
+        });
+        let len = msg.bytes;
+        match fds {
+            Some(fd) => Ok((fd, buf[..len].to_vec())),
+            None => bail!("no fd received from the runtime"),
+        }
+    }
+}
@@ -0,0 +1,499 @@
+use std::collections::VecDeque;
+use std::ffi::{CString, OsStr, OsString};
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use nix::errno::Errno;
+use nix::fcntl::{open, openat, readlink, readlinkat, OFlag};
+use nix::sys::stat::{fstat, mkdirat, Mode, SFlag};
+use nix::unistd::close;
+
+/// Most symlinks followed while resolving a single path.
+const MAX_SYMLINKS: usize = 255;
+
+/// Join `unsafe_path` to `root` as if `root` were the root directory,
+/// following the symlinks found in the rootfs without leaving it: absolute
+/// targets start over from `root` and `..` stops at it. Components which
+/// don't exist are kept as they are.
+pub fn secure_join(root: &Path, unsafe_path: &Path) -> Result<PathBuf> {
+    let mut pending: VecDeque<OsString> = components(unsafe_path).collect();
+    let mut resolved = PathBuf::new();
+    let mut links = 0;
+    while let Some(component) = pending.pop_front() {
+        if component == ".." {
+            resolved.pop();
+            continue;
+        }
+        let candidate = resolved.join(&component);
+        let metadata = match fs::symlink_metadata(root.join(&candidate)) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                resolved = candidate;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if !metadata.file_type().is_symlink() {
+            resolved = candidate;
+            continue;
+        }
+
+        links += 1;
+        if links > MAX_SYMLINKS {
+            bail!(
+                "too many levels of symbolic links in {}",
+                unsafe_path.display()
+            );
+        }
+        let target = fs::read_link(root.join(&candidate))?;
+        if target.is_absolute() {
+            resolved = PathBuf::new();
+        }
+        for component in components(&target).collect::<Vec<_>>().into_iter().rev() {
+            pending.push_front(component);
+        }
+    }
+    Ok(root.join(resolved))
+}
+
+/// Last component of `path`, which must name a file.
+fn file_name(path: &Path) -> Result<&OsStr> {
+    match path.file_name() {
+        Some(name) => Ok(name),
+        None => bail!("{} does not name a file", path.display()),
+    }
+}
+
+/// Components of `path` other than `/` and `.`.
+fn components(path: &Path) -> impl Iterator<Item = OsString> + '_ {
+    path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name.to_os_string()),
+        Component::ParentDir => Some("..".into()),
+        _ => None,
+    })
+}
+
+/// An O_PATH descriptor of a path resolved inside a rootfs. Operating on
+/// it through /proc/self/fd rather than on the path keeps a symlink swapped
+/// in meanwhile from redirecting the operation out of the rootfs.
+pub struct RootedPath {
+    fd: RawFd,
+}
+
+impl RootedPath {
+    /// Open `unsafe_path` inside `root`, with openat2(2) and RESOLVE_IN_ROOT
+    /// when the kernel has it and with `secure_join` otherwise.
+    pub fn open(root: &Path, unsafe_path: &Path) -> Result<RootedPath> {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("failed to resolve {}", root.display()))?;
+        let fd = match openat2_in_root(&root, unsafe_path) {
+            Err(nix::Error::Sys(Errno::ENOSYS))
+            | Err(nix::Error::Sys(Errno::E2BIG))
+            | Err(nix::Error::Sys(Errno::EPERM)) => open_joined(&root, unsafe_path)?,
+            result => result.with_context(|| {
+                format!(
+                    "failed to open {} in {}",
+                    unsafe_path.display(),
+                    root.display()
+                )
+            })?,
+        };
+        Ok(RootedPath { fd })
+    }
+
+    /// Open the directory `unsafe_path` inside `root`, first creating the
+    /// missing ones with `mode` like `mkdir -p`. Symlinks are followed the
+    /// way `secure_join` does, but each step is taken relative to the
+    /// descriptor of the parent directory, so a symlink swapped in cannot
+    /// redirect it out of the rootfs.
+    pub fn mkdir_all(root: &Path, unsafe_path: &Path, mode: Mode) -> Result<RootedPath> {
+        Ok(RootedPath::mkdir_resolved(root, unsafe_path, mode)?.0)
+    }
+
+    /// `mkdir_all`, also returning the path of the directory inside `root`.
+    fn mkdir_resolved(
+        root: &Path,
+        unsafe_path: &Path,
+        mode: Mode,
+    ) -> Result<(RootedPath, PathBuf)> {
+        let mut pending: VecDeque<OsString> = components(unsafe_path).collect();
+        let mut resolved = PathBuf::from("/");
+        let mut dir = RootedPath::open(root, &resolved)?;
+        let mut links = 0;
+        while let Some(component) = pending.pop_front() {
+            if component == ".." {
+                resolved.pop();
+                dir = RootedPath::open(root, &resolved)?;
+                continue;
+            }
+            let next = match openat(
+                dir.fd,
+                component.as_os_str(),
+                OFlag::O_PATH | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+                Mode::empty(),
+            ) {
+                Ok(fd) => RootedPath { fd },
+                Err(nix::Error::Sys(Errno::ENOENT)) => {
+                    match mkdirat(dir.fd, component.as_os_str(), mode) {
+                        Err(nix::Error::Sys(Errno::EEXIST)) => {}
+                        result => result.with_context(|| {
+                            format!("failed to create {}", resolved.join(&component).display())
+                        })?,
+                    }
+                    // Open what is there now on the next round.
+                    pending.push_front(component);
+                    continue;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("failed to open {}", resolved.join(&component).display())
+                    })
+                }
+            };
+            if next.file_type()? != SFlag::S_IFLNK {
+                resolved.push(&component);
+                dir = next;
+                continue;
+            }
+
+            links += 1;
+            if links > MAX_SYMLINKS {
+                bail!(
+                    "too many levels of symbolic links in {}",
+                    unsafe_path.display()
+                );
+            }
+            let target = PathBuf::from(readlinkat(dir.fd, component.as_os_str())?);
+            if target.is_absolute() {
+                resolved = PathBuf::from("/");
+                dir = RootedPath::open(root, &resolved)?;
+            }
+            for component in components(&target).collect::<Vec<_>>().into_iter().rev() {
+                pending.push_front(component);
+            }
+        }
+        if !dir.is_dir()? {
+            bail!("{} is not a directory", unsafe_path.display());
+        }
+        Ok((dir, resolved))
+    }
+
+    /// Open `unsafe_path` inside `root`, first creating it as an empty file
+    /// along with its missing parent directories when it doesn't exist. A
+    /// dangling symlink gets its target created.
+    pub fn create_file(root: &Path, unsafe_path: &Path) -> Result<RootedPath> {
+        let mut path = unsafe_path.to_path_buf();
+        for _ in 0..=MAX_SYMLINKS {
+            let name = file_name(&path)?;
+            let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+            let (parent, resolved) =
+                RootedPath::mkdir_resolved(root, parent, Mode::from_bits_truncate(0o755))?;
+            match openat(
+                parent.fd,
+                name,
+                OFlag::O_CREAT
+                    | OFlag::O_EXCL
+                    | OFlag::O_WRONLY
+                    | OFlag::O_NOFOLLOW
+                    | OFlag::O_CLOEXEC,
+                Mode::from_bits_truncate(0o666),
+            ) {
+                Ok(fd) => close(fd)?,
+                Err(nix::Error::Sys(Errno::EEXIST)) => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("failed to create {}", path.display()))
+                }
+            }
+            let file = parent.open_child(name)?;
+            if file.file_type()? != SFlag::S_IFLNK {
+                return Ok(file);
+            }
+            path = resolved.join(readlinkat(parent.fd, name)?);
+        }
+        bail!(
+            "too many levels of symbolic links in {}",
+            unsafe_path.display()
+        )
+    }
+
+    /// Open the parent directory of `unsafe_path` inside `root`, creating
+    /// the missing ones, along with the name of the file in it.
+    pub fn create_parent<'a>(
+        root: &Path,
+        unsafe_path: &'a Path,
+    ) -> Result<(RootedPath, &'a OsStr)> {
+        let name = file_name(unsafe_path)?;
+        let parent = unsafe_path.parent().unwrap_or_else(|| Path::new("/"));
+        let parent = RootedPath::mkdir_all(root, parent, Mode::from_bits_truncate(0o755))?;
+        Ok((parent, name))
+    }
+
+    /// Open `name` in the directory without following it.
+    pub fn open_child(&self, name: &OsStr) -> Result<RootedPath> {
+        let fd = openat(
+            self.fd,
+            name,
+            OFlag::O_PATH | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+            Mode::empty(),
+        )?;
+        Ok(RootedPath { fd })
+    }
+
+    /// Whether the opened file is a directory, without following it again.
+    pub fn is_dir(&self) -> Result<bool> {
+        Ok(self.file_type()? == SFlag::S_IFDIR)
+    }
+
+    fn file_type(&self) -> Result<SFlag> {
+        let stat = fstat(self.fd)?;
+        Ok(SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT)
+    }
+
+    /// Path standing for the opened file, e.g. as a mount target.
+    pub fn proc_path(&self) -> PathBuf {
+        PathBuf::from(format!("/proc/self/fd/{}", self.fd))
+    }
+
+    /// Where the opened file actually is.
+    pub fn real_path(&self) -> Result<PathBuf> {
+        Ok(readlink(&self.proc_path())?.into())
+    }
+}
+
+impl AsRawFd for RootedPath {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for RootedPath {
+    fn drop(&mut self) {
+        close(self.fd).ok();
+    }
+}
+
+fn openat2_in_root(root: &Path, unsafe_path: &Path) -> nix::Result<RawFd> {
+    let flags = OFlag::O_PATH | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC;
+    let root_fd = open(root, flags, Mode::empty())?;
+    let result = CString::new(relative(unsafe_path).as_bytes())
+        .map_err(|_| nix::Error::Sys(Errno::EINVAL))
+        .and_then(|path| {
+            let mut how: libc::open_how = unsafe { std::mem::zeroed() };
+            how.flags = (libc::O_PATH | libc::O_CLOEXEC) as u64;
+            how.resolve = libc::RESOLVE_IN_ROOT | libc::RESOLVE_NO_MAGICLINKS;
+            let ret = unsafe {
+                libc::syscall(
+                    libc::SYS_openat2,
+                    root_fd,
+                    path.as_ptr(),
+                    &how as *const libc::open_how,
+                    std::mem::size_of::<libc::open_how>(),
+                )
+            };
+            Errno::result(ret).map(|fd| fd as RawFd)
+        });
+    close(root_fd)?;
+    result
+}
+
+/// `unsafe_path` relative to the root, "." for the root itself.
+fn relative(unsafe_path: &Path) -> &OsStr {
+    let path = unsafe_path.as_os_str().as_bytes();
+    let start = path.iter().position(|&b| b != b'/').unwrap_or(path.len());
+    match &path[start..] {
+        b"" => OsStr::new("."),
+        rest => OsStr::from_bytes(rest),
+    }
+}
+
+/// Fallback for kernels without openat2(2): resolve the path in userspace,
+/// then check that the file opened is still inside `root`.
+fn open_joined(root: &Path, unsafe_path: &Path) -> Result<RawFd> {
+    let path = secure_join(root, unsafe_path)?;
+    let fd = open(
+        &path,
+        OFlag::O_PATH | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+        Mode::empty(),
+    )
+    .with_context(|| format!("failed to open {}", path.display()))?;
+    let opened = RootedPath { fd };
+    let real_path = opened.real_path()?;
+    if !real_path.starts_with(root) {
+        bail!(
+            "{} resolves to {} outside of {}",
+            unsafe_path.display(),
+            real_path.display(),
+            root.display()
+        );
+    }
+    let fd = opened.fd;
+    std::mem::forget(opened);
+    Ok(fd)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::os::unix::fs::symlink;
+
+    fn init_root() -> PathBuf {
+        let root = tempfile::tempdir().unwrap().into_path();
+        fs::create_dir_all(root.join("etc")).unwrap();
+        fs::create_dir_all(root.join("usr/lib")).unwrap();
+        root.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn plain_paths_should_be_joined() {
+        let root = init_root();
+        let join = |path: &str| secure_join(&root, Path::new(path)).unwrap();
+        assert_eq!(join("/etc"), root.join("etc"));
+        assert_eq!(join("etc/./passwd"), root.join("etc/passwd"));
+        assert_eq!(join("/nonexistent/dir"), root.join("nonexistent/dir"));
+        assert_eq!(join("/"), root);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn parent_dirs_should_stop_at_root() {
+        let root = init_root();
+        let join = |path: &str| secure_join(&root, Path::new(path)).unwrap();
+        assert_eq!(join("/../../etc"), root.join("etc"));
+        assert_eq!(join("/usr/../../../lib"), root.join("lib"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn absolute_symlinks_should_stay_in_root() {
+        let root = init_root();
+        symlink("/etc", root.join("host-etc")).unwrap();
+        symlink("/usr/lib", root.join("lib")).unwrap();
+        let join = |path: &str| secure_join(&root, Path::new(path)).unwrap();
+        assert_eq!(join("/host-etc/shadow"), root.join("etc/shadow"));
+        assert_eq!(join("/lib/../bin"), root.join("usr/bin"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn relative_symlinks_should_stay_in_root() {
+        let root = init_root();
+        symlink("../../../../../../tmp", root.join("usr/lib/escape")).unwrap();
+        symlink("../../..", root.join("etc/up")).unwrap();
+        let join = |path: &str| secure_join(&root, Path::new(path)).unwrap();
+        assert_eq!(join("/usr/lib/escape/x"), root.join("tmp/x"));
+        assert_eq!(join("/etc/up/proc"), root.join("proc"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn symlink_chains_should_be_followed() {
+        let root = init_root();
+        symlink("b", root.join("a")).unwrap();
+        symlink("/etc/c", root.join("b")).unwrap();
+        symlink("../../../../usr", root.join("etc/c")).unwrap();
+        let joined = secure_join(&root, Path::new("/a/lib")).unwrap();
+        assert_eq!(joined, root.join("usr/lib"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn symlink_loops_should_be_rejected() {
+        let root = init_root();
+        symlink("loop-b", root.join("loop-a")).unwrap();
+        symlink("/loop-a", root.join("loop-b")).unwrap();
+        let err = secure_join(&root, Path::new("/loop-a/x")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "too many levels of symbolic links in /loop-a/x"
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn opened_paths_should_be_inside_root() {
+        let root = init_root();
+        symlink("/", root.join("slash")).unwrap();
+        symlink("../../../../../etc", root.join("usr/etc")).unwrap();
+        symlink("/proc/self/root/etc", root.join("magic")).unwrap();
+
+        for path in &[
+            "/slash/etc",
+            "/usr/etc",
+            "/etc/../../etc",
+            "/slash/usr/../etc",
+        ] {
+            let opened = RootedPath::open(&root, Path::new(path)).unwrap();
+            assert_eq!(opened.real_path().unwrap(), root.join("etc"), "{}", path);
+        }
+        assert_eq!(
+            RootedPath::open(&root, Path::new("/"))
+                .unwrap()
+                .real_path()
+                .unwrap(),
+            root
+        );
+        assert!(RootedPath::open(&root, Path::new("/magic")).is_err());
+        assert!(RootedPath::open(&root, Path::new("/nonexistent")).is_err());
+
+        let fd = open_joined(&root, Path::new("/slash/usr/etc")).unwrap();
+        let opened = RootedPath { fd };
+        assert_eq!(opened.real_path().unwrap(), root.join("etc"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn missing_directories_should_be_created_inside_root() {
+        let root = init_root();
+        let outside = tempfile::tempdir().unwrap().into_path();
+        symlink(&outside, root.join("absolute")).unwrap();
+        symlink("../../../../../..", root.join("usr/relative")).unwrap();
+        let mode = Mode::from_bits_truncate(0o755);
+
+        for (path, created) in &[
+            ("/usr/lib/a/b", root.join("usr/lib/a/b")),
+            (
+                "/absolute/c",
+                root.join(outside.strip_prefix("/").unwrap()).join("c"),
+            ),
+            ("/usr/relative/d/../e", root.join("e")),
+        ] {
+            let dir = RootedPath::mkdir_all(&root, Path::new(path), mode).unwrap();
+            assert_eq!(&dir.real_path().unwrap(), created, "{}", path);
+        }
+        assert!(root.join("d").is_dir());
+        assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+
+        fs::write(root.join("etc/passwd"), "").unwrap();
+        let err = RootedPath::mkdir_all(&root, Path::new("/etc/passwd"), mode)
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "/etc/passwd is not a directory");
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn missing_files_should_be_created_inside_root() {
+        let root = init_root();
+        symlink("/etc/hostname", root.join("hostname")).unwrap();
+        symlink("/", root.join("slash")).unwrap();
+
+        let file = RootedPath::create_file(&root, Path::new("/hostname")).unwrap();
+        assert_eq!(file.real_path().unwrap(), root.join("etc/hostname"));
+        assert!(!file.is_dir().unwrap());
+        let file = RootedPath::create_file(&root, Path::new("/slash/dev/console")).unwrap();
+        assert_eq!(file.real_path().unwrap(), root.join("dev/console"));
+        assert!(RootedPath::create_file(&root, Path::new("/etc"))
+            .unwrap()
+            .is_dir()
+            .unwrap());
+        assert!(RootedPath::create_file(&root, Path::new("/")).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,11 +1,84 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::path::PathBuf;
 
+use anyhow::{bail, Result};
 use chrono::{DateTime, Utc};
 use serde_derive::{Deserialize, Serialize};
 
 pub static OCI_VERSION: &str = "1.0.1-dev";
 
+/// Define an enum of the names the spec allows for `$what`, serialized as
+/// those names. Names this version doesn't know deserialize to `Unknown`
+/// rather than fail, so that the spec can still be loaded and written back;
+/// `check` rejects them where they would be used.
+macro_rules! string_enum {
+    ($(#[$meta:meta])* $name:ident, $what:expr, { $($variant:ident = $value:expr,)* }) => {
+        $(#[$meta])*
+        #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+        #[serde(from = "String", into = "String")]
+        pub enum $name {
+            $($variant,)*
+            Unknown(String),
+        }
+
+        impl $name {
+            /// Every known name, in declaration order.
+            pub const VALUES: &'static [&'static str] = &[$($value),*];
+
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(value) => value,
+                }
+            }
+
+            /// Fail on a name this version doesn't know.
+            pub fn check(&self) -> Result<()> {
+                if let $name::Unknown(value) = self {
+                    bail!(
+                        "unknown {}: {} (expected one of {})",
+                        $what,
+                        value,
+                        Self::VALUES.join(", ")
+                    );
+                }
+                Ok(())
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(value: String) -> Self {
+                match value.as_str() {
+                    $($value => $name::$variant,)*
+                    _ => $name::Unknown(value),
+                }
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                value.to_string().into()
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> String {
+                match value {
+                    $name::Unknown(value) => value,
+                    known => known.as_str().into(),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.as_str())
+            }
+        }
+    };
+}
+
 #[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub enum Status {
@@ -15,10 +88,25 @@ pub enum Status {
     Created,
     #[serde(rename = "running")]
     Running,
+    #[serde(rename = "paused")]
+    Paused,
     #[serde(rename = "stopped")]
     Stopped,
 }
 
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            Status::Creating => "creating",
+            Status::Created => "created",
+            Status::Running => "running",
+            Status::Paused => "paused",
+            Status::Stopped => "stopped",
+        };
+        write!(f, "{}", status)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct State {
@@ -36,6 +124,18 @@ pub struct State {
     pub created: Option<DateTime<Utc>>,
 }
 
+/// Sent to a seccomp agent over SCM_RIGHTS, along with the fds it names.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerProcessState {
+    pub oci_version: String,
+    pub fds: Vec<String>,
+    pub pid: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<String>,
+    pub state: State,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Spec {
@@ -88,19 +188,66 @@ pub struct Process {
     pub selinux_label: Option<String>,
 }
 
+string_enum!(
+    /// Capabilities in the order of their numbers.
+    Capability, "capability", {
+        Chown = "CAP_CHOWN",
+        DacOverride = "CAP_DAC_OVERRIDE",
+        DacReadSearch = "CAP_DAC_READ_SEARCH",
+        Fowner = "CAP_FOWNER",
+        Fsetid = "CAP_FSETID",
+        Kill = "CAP_KILL",
+        Setgid = "CAP_SETGID",
+        Setuid = "CAP_SETUID",
+        Setpcap = "CAP_SETPCAP",
+        LinuxImmutable = "CAP_LINUX_IMMUTABLE",
+        NetBindService = "CAP_NET_BIND_SERVICE",
+        NetBroadcast = "CAP_NET_BROADCAST",
+        NetAdmin = "CAP_NET_ADMIN",
+        NetRaw = "CAP_NET_RAW",
+        IpcLock = "CAP_IPC_LOCK",
+        IpcOwner = "CAP_IPC_OWNER",
+        SysModule = "CAP_SYS_MODULE",
+        SysRawio = "CAP_SYS_RAWIO",
+        SysChroot = "CAP_SYS_CHROOT",
+        SysPtrace = "CAP_SYS_PTRACE",
+        SysPacct = "CAP_SYS_PACCT",
+        SysAdmin = "CAP_SYS_ADMIN",
+        SysBoot = "CAP_SYS_BOOT",
+        SysNice = "CAP_SYS_NICE",
+        SysResource = "CAP_SYS_RESOURCE",
+        SysTime = "CAP_SYS_TIME",
+        SysTtyConfig = "CAP_SYS_TTY_CONFIG",
+        Mknod = "CAP_MKNOD",
+        Lease = "CAP_LEASE",
+        AuditWrite = "CAP_AUDIT_WRITE",
+        AuditControl = "CAP_AUDIT_CONTROL",
+        Setfcap = "CAP_SETFCAP",
+        MacOverride = "CAP_MAC_OVERRIDE",
+        MacAdmin = "CAP_MAC_ADMIN",
+        Syslog = "CAP_SYSLOG",
+        WakeAlarm = "CAP_WAKE_ALARM",
+        BlockSuspend = "CAP_BLOCK_SUSPEND",
+        AuditRead = "CAP_AUDIT_READ",
+        Perfmon = "CAP_PERFMON",
+        Bpf = "CAP_BPF",
+        CheckpointRestore = "CAP_CHECKPOINT_RESTORE",
+    }
+);
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LinuxCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub bounding: Option<Vec<String>>,
+    pub bounding: Option<Vec<Capability>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub effective: Option<Vec<String>>,
+    pub effective: Option<Vec<Capability>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub inheritable: Option<Vec<String>>,
+    pub inheritable: Option<Vec<Capability>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub permitted: Option<Vec<String>>,
+    pub permitted: Option<Vec<Capability>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ambient: Option<Vec<String>>,
+    pub ambient: Option<Vec<Capability>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -158,7 +305,7 @@ pub struct Hooks {
     pub poststop: Option<Vec<Hook>>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Linux {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -199,7 +346,7 @@ pub struct LinuxIDMapping {
     pub size: u32,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct LinuxResources {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -228,9 +375,9 @@ pub struct LinuxDevice {
     pub type_: String,
     pub major: i64,
     pub minor: i64,
-    // TODO: FileMode
-    // Golang FileMode *os.FileMode
-    // -> Rust file_mode Option<T>
+    /// Permission bits of the device node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_mode: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub uid: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -255,25 +402,71 @@ pub struct LinuxDeviceCgroup {
 #[serde(rename_all = "camelCase")]
 pub struct LinuxSeccomp {
     pub default_action: LinuxSeccompAction,
+    /// Errno returned by an SCMP_ACT_ERRNO default action.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_errno_ret: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub architectures: Option<Vec<Arch>>,
+    /// SECCOMP_FILTER_FLAG_* flags the filter is loaded with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags: Option<Vec<String>>,
+    /// Unix socket of the agent receiving the fd of an SCMP_ACT_NOTIFY filter.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listener_path: Option<String>,
+    /// Passed as is to the agent along with the fd.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listener_metadata: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub syscalls: Option<Vec<LinuxSyscall>>,
 }
 
-pub type Arch = String;
-
-// TODO: define Architecures as const
-
-pub type LinuxSeccompAction = String;
+string_enum!(
+    Arch, "seccomp architecture", {
+        X86 = "SCMP_ARCH_X86",
+        X86_64 = "SCMP_ARCH_X86_64",
+        X32 = "SCMP_ARCH_X32",
+        Arm = "SCMP_ARCH_ARM",
+        Aarch64 = "SCMP_ARCH_AARCH64",
+        Mips = "SCMP_ARCH_MIPS",
+        Mips64 = "SCMP_ARCH_MIPS64",
+        Mips64n32 = "SCMP_ARCH_MIPS64N32",
+        Mipsel = "SCMP_ARCH_MIPSEL",
+        Mipsel64 = "SCMP_ARCH_MIPSEL64",
+        Mipsel64n32 = "SCMP_ARCH_MIPSEL64N32",
+        Ppc = "SCMP_ARCH_PPC",
+        Ppc64 = "SCMP_ARCH_PPC64",
+        Ppc64le = "SCMP_ARCH_PPC64LE",
+        S390 = "SCMP_ARCH_S390",
+        S390x = "SCMP_ARCH_S390X",
+        Parisc = "SCMP_ARCH_PARISC",
+        Parisc64 = "SCMP_ARCH_PARISC64",
+        Riscv64 = "SCMP_ARCH_RISCV64",
+        Loongarch64 = "SCMP_ARCH_LOONGARCH64",
+    }
+);
 
-// TODO: define seccomp actions as const
+string_enum!(
+    LinuxSeccompAction, "seccomp action", {
+        Kill = "SCMP_ACT_KILL",
+        KillProcess = "SCMP_ACT_KILL_PROCESS",
+        KillThread = "SCMP_ACT_KILL_THREAD",
+        Trap = "SCMP_ACT_TRAP",
+        Errno = "SCMP_ACT_ERRNO",
+        Trace = "SCMP_ACT_TRACE",
+        Allow = "SCMP_ACT_ALLOW",
+        Log = "SCMP_ACT_LOG",
+        Notify = "SCMP_ACT_NOTIFY",
+    }
+);
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LinuxSyscall {
     pub names: Vec<String>,
     pub action: LinuxSeccompAction,
+    /// Errno returned by an SCMP_ACT_ERRNO action.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errno_ret: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub args: Option<Vec<LinuxSeccompArg>>,
 }
@@ -288,11 +481,19 @@ pub struct LinuxSeccompArg {
     pub op: LinuxSeccompOperator,
 }
 
-pub type LinuxSeccompOperator = String;
-
-// TODO: define seccomp operators as const
+string_enum!(
+    LinuxSeccompOperator, "seccomp operator", {
+        NotEqual = "SCMP_CMP_NE",
+        LessThan = "SCMP_CMP_LT",
+        LessOrEqual = "SCMP_CMP_LE",
+        Equal = "SCMP_CMP_EQ",
+        GreaterOrEqual = "SCMP_CMP_GE",
+        GreaterThan = "SCMP_CMP_GT",
+        MaskedEqual = "SCMP_CMP_MASKED_EQ",
+    }
+);
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct LinuxMemory {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -300,6 +501,8 @@ pub struct LinuxMemory {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reservation: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub kernel: Option<i64>,
     #[serde(rename = "kernelTCP", skip_serializing_if = "Option::is_none")]
     pub kernel_tcp: Option<i64>,
@@ -309,7 +512,7 @@ pub struct LinuxMemory {
     pub disable_oom_killer: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct LinuxCPU {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -331,45 +534,45 @@ pub struct LinuxCPU {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LinuxPids {
-    limit: i64,
+    pub limit: i64,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct LinuxBlockIO {
     #[serde(skip_serializing_if = "Option::is_none")]
-    weight: Option<u16>,
+    pub weight: Option<u16>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    leaf_weight: Option<u16>,
+    pub leaf_weight: Option<u16>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    weight_device: Option<Vec<LinuxWeightDevice>>,
+    pub weight_device: Option<Vec<LinuxWeightDevice>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    throttle_read_bps_device: Option<Vec<LinuxThrottleDevice>>,
+    pub throttle_read_bps_device: Option<Vec<LinuxThrottleDevice>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    throttle_write_bps_device: Option<Vec<LinuxThrottleDevice>>,
+    pub throttle_write_bps_device: Option<Vec<LinuxThrottleDevice>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    throttle_read_iops_device: Option<Vec<LinuxThrottleDevice>>,
+    pub throttle_read_iops_device: Option<Vec<LinuxThrottleDevice>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    throttle_write_iops_device: Option<Vec<LinuxThrottleDevice>>,
+    pub throttle_write_iops_device: Option<Vec<LinuxThrottleDevice>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LinuxWeightDevice {
-    major: i64,
-    minor: i64,
+    pub major: i64,
+    pub minor: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    weight: Option<u16>,
+    pub weight: Option<u16>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    leaf_weight: Option<u16>,
+    pub leaf_weight: Option<u16>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LinuxThrottleDevice {
-    major: i64,
-    minor: i64,
-    rate: u64,
+    pub major: i64,
+    pub minor: i64,
+    pub rate: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -419,11 +622,32 @@ pub struct User {
     pub username: Option<String>,
 }
 
+string_enum!(
+    RlimitType, "rlimit type", {
+        Cpu = "RLIMIT_CPU",
+        Fsize = "RLIMIT_FSIZE",
+        Data = "RLIMIT_DATA",
+        Stack = "RLIMIT_STACK",
+        Core = "RLIMIT_CORE",
+        Rss = "RLIMIT_RSS",
+        Nproc = "RLIMIT_NPROC",
+        Nofile = "RLIMIT_NOFILE",
+        Memlock = "RLIMIT_MEMLOCK",
+        As = "RLIMIT_AS",
+        Locks = "RLIMIT_LOCKS",
+        Sigpending = "RLIMIT_SIGPENDING",
+        Msgqueue = "RLIMIT_MSGQUEUE",
+        Nice = "RLIMIT_NICE",
+        Rtprio = "RLIMIT_RTPRIO",
+        Rttime = "RLIMIT_RTTIME",
+    }
+);
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct POSIXRlimit {
     #[serde(rename = "type")]
-    pub type_: String,
+    pub type_: RlimitType,
     pub hard: u64,
     pub soft: u64,
 }
@@ -431,8 +655,8 @@ pub struct POSIXRlimit {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LinuxHugepageLimit {
-    page_size: String,
-    limit: u64,
+    pub page_size: String,
+    pub limit: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -474,13 +698,7 @@ pub struct VM {}
 #[macro_export]
 macro_rules! vec_str_convert {
     ( $( $x:expr ),* ) => {
-        {
-            let mut temp_vec = Vec::new();
-            $(
-                temp_vec.push(String::from($x));
-            )*
-            temp_vec
-        }
+        vec![$(String::from($x)),*]
     };
 }
 
@@ -516,30 +734,30 @@ impl Spec {
                 command_line: None,
                 no_new_privileges: Some(true),
                 capabilities: Some(LinuxCapabilities {
-                    bounding: Some(vec_str_convert![
-                        "CAP_AUDIT_WRITE",
-                        "CAP_KILL",
-                        "CAP_NET_BIND_SERVICE"
+                    bounding: Some(vec![
+                        Capability::AuditWrite,
+                        Capability::Kill,
+                        Capability::NetBindService,
                     ]),
-                    permitted: Some(vec_str_convert![
-                        "CAP_AUDIT_WRITE",
-                        "CAP_KILL",
-                        "CAP_NET_BIND_SERVICE"
+                    permitted: Some(vec![
+                        Capability::AuditWrite,
+                        Capability::Kill,
+                        Capability::NetBindService,
                     ]),
-                    inheritable: Some(vec_str_convert![
-                        "CAP_AUDIT_WRITE",
-                        "CAP_KILL",
-                        "CAP_NET_BIND_SERVICE"
+                    inheritable: Some(vec![
+                        Capability::AuditWrite,
+                        Capability::Kill,
+                        Capability::NetBindService,
                     ]),
-                    ambient: Some(vec_str_convert![
-                        "CAP_AUDIT_WRITE",
-                        "CAP_KILL",
-                        "CAP_NET_BIND_SERVICE"
+                    ambient: Some(vec![
+                        Capability::AuditWrite,
+                        Capability::Kill,
+                        Capability::NetBindService,
                     ]),
-                    effective: Some(vec_str_convert![
-                        "CAP_AUDIT_WRITE",
-                        "CAP_KILL",
-                        "CAP_NET_BIND_SERVICE"
+                    effective: Some(vec![
+                        Capability::AuditWrite,
+                        Capability::Kill,
+                        Capability::NetBindService,
                     ]),
                 }),
                 rlimits: Some(vec![POSIXRlimit {
@@ -675,6 +893,61 @@ impl Spec {
             vm: None,
         }
     }
+
+    /// The default spec adapted for a container run by a user with `uid` and
+    /// `gid`, who becomes root in a new user namespace and is denied what
+    /// needs privileges on the host.
+    pub fn rootless(uid: u32, gid: u32) -> Self {
+        let mut spec = Spec::new();
+        spec.make_rootless(uid, gid);
+        spec
+    }
+
+    fn make_rootless(&mut self, uid: u32, gid: u32) {
+        // Mounting these needs privileges on the host.
+        self.mounts
+            .retain(|mount| !matches!(mount.type_.as_str(), "mqueue" | "sysfs" | "cgroup"));
+        for mount in self.mounts.iter_mut() {
+            // Only our own ids are mapped.
+            if let Some(options) = mount.options.as_mut() {
+                options.retain(|o| !o.starts_with("uid=") && !o.starts_with("gid="));
+            }
+        }
+        // Only the owner of a network namespace may mount sysfs.
+        self.mounts.push(Mount {
+            destination: "/sys".into(),
+            type_: "none".into(),
+            source: "/sys".into(),
+            options: Some(vec_str_convert!["rbind", "nosuid", "noexec", "nodev", "ro"]),
+        });
+
+        let linux = self.linux.get_or_insert_with(Linux::default);
+        // There is no cgroup to enforce them unless one is delegated.
+        linux.resources = None;
+        let namespaces = linux.namespaces.get_or_insert_with(Vec::new);
+        // A new network namespace would have no way out, and nobody to set
+        // it up. One set up beforehand and joined by path is kept.
+        namespaces.retain(|ns| ns.type_ != LinuxNamespaceType::Network || ns.path.is_some());
+        if namespaces
+            .iter()
+            .all(|ns| ns.type_ != LinuxNamespaceType::User)
+        {
+            namespaces.push(LinuxNamespace {
+                type_: LinuxNamespaceType::User,
+                path: None,
+            });
+        }
+        linux.uid_mappings = Some(vec![LinuxIDMapping {
+            container_id: 0,
+            host_id: uid,
+            size: 1,
+        }]);
+        linux.gid_mappings = Some(vec![LinuxIDMapping {
+            container_id: 0,
+            host_id: gid,
+            size: 1,
+        }]);
+    }
 }
 
 #[cfg(test)]
@@ -714,11 +987,112 @@ mod tests {
         assert_eq!(r#"{"containerID":0,"hostID":1000,"size":1}"#, json_string)
     }
 
+    #[test]
+    fn test_linux_device_convert_json() {
+        let device_string = r#"{"path":"/dev/fuse","type":"c","major":10,"minor":229,"fileMode":438,"uid":0,"gid":0}"#;
+        let device: LinuxDevice = serde_json::from_str(device_string).unwrap();
+        assert_eq!(device.file_mode, Some(0o666));
+        assert_eq!(serde_json::to_string(&device).unwrap(), device_string);
+    }
+
+    #[test]
+    fn test_string_enum_convert_json() {
+        let caps: Vec<Capability> =
+            serde_json::from_str(r#"["CAP_KILL","CAP_FOO","CAP_CHECKPOINT_RESTORE"]"#).unwrap();
+        assert_eq!(
+            caps,
+            vec![
+                Capability::Kill,
+                Capability::Unknown("CAP_FOO".into()),
+                Capability::CheckpointRestore,
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&caps).unwrap(),
+            r#"["CAP_KILL","CAP_FOO","CAP_CHECKPOINT_RESTORE"]"#
+        );
+        assert!(caps[0].check().is_ok());
+        assert_eq!(
+            LinuxSeccompOperator::from("SCMP_CMP_FOO")
+                .check()
+                .unwrap_err()
+                .to_string(),
+            "unknown seccomp operator: SCMP_CMP_FOO (expected one of SCMP_CMP_NE, SCMP_CMP_LT, \
+             SCMP_CMP_LE, SCMP_CMP_EQ, SCMP_CMP_GE, SCMP_CMP_GT, SCMP_CMP_MASKED_EQ)"
+        );
+    }
+
+    #[test]
+    fn rootless_spec_should_keep_a_joined_network_namespace() {
+        let mut spec = Spec::new();
+        let namespaces = spec.linux.as_mut().unwrap().namespaces.as_mut().unwrap();
+        for ns in namespaces.iter_mut() {
+            if ns.type_ == LinuxNamespaceType::Network {
+                ns.path = Some("/run/user/1000/netns/slirp".into());
+            }
+        }
+        spec.make_rootless(1000, 1000);
+
+        let namespaces = spec.linux.unwrap().namespaces.unwrap();
+        let network: Vec<_> = namespaces
+            .iter()
+            .filter(|ns| ns.type_ == LinuxNamespaceType::Network)
+            .collect();
+        assert_eq!(network.len(), 1);
+        assert_eq!(
+            network[0].path.as_deref(),
+            Some("/run/user/1000/netns/slirp")
+        );
+        let users = namespaces
+            .iter()
+            .filter(|ns| ns.type_ == LinuxNamespaceType::User)
+            .count();
+        assert_eq!(users, 1);
+    }
+
+    #[test]
+    fn rootless_spec_should_map_the_caller() {
+        let spec = Spec::rootless(1000, 1001);
+        let linux = spec.linux.unwrap();
+        assert!(linux.resources.is_none());
+        let namespaces = linux.namespaces.unwrap();
+        assert!(namespaces
+            .iter()
+            .all(|ns| ns.type_ != LinuxNamespaceType::Network));
+        assert!(namespaces
+            .iter()
+            .any(|ns| ns.type_ == LinuxNamespaceType::User));
+        let uid_mappings = serde_json::to_string(&linux.uid_mappings).unwrap();
+        assert_eq!(
+            uid_mappings,
+            r#"[{"containerID":0,"hostID":1000,"size":1}]"#
+        );
+        let gid_mappings = serde_json::to_string(&linux.gid_mappings).unwrap();
+        assert_eq!(
+            gid_mappings,
+            r#"[{"containerID":0,"hostID":1001,"size":1}]"#
+        );
+
+        assert!(spec.mounts.iter().all(|mount| mount.type_ != "mqueue"));
+        assert!(spec
+            .mounts
+            .iter()
+            .flat_map(|mount| mount.options.iter().flatten())
+            .all(|option| !option.starts_with("gid=")));
+        let sys = spec
+            .mounts
+            .iter()
+            .find(|m| m.destination == "/sys")
+            .unwrap();
+        assert_eq!(sys.source, "/sys");
+        assert_eq!(sys.options.as_ref().unwrap()[0], "rbind");
+    }
+
     #[test]
     fn should_parse_runc_spec_data() {
         let spec_string = r#"{"ociVersion":"1.0.1-dev","process":{"terminal":true,"user":{"uid":0,"gid":0},"args":["sh"],"env":["PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin","TERM=xterm"],"cwd":"/","capabilities":{"bounding":["CAP_AUDIT_WRITE","CAP_KILL","CAP_NET_BIND_SERVICE"],"effective":["CAP_AUDIT_WRITE","CAP_KILL","CAP_NET_BIND_SERVICE"],"inheritable":["CAP_AUDIT_WRITE","CAP_KILL","CAP_NET_BIND_SERVICE"],"permitted":["CAP_AUDIT_WRITE","CAP_KILL","CAP_NET_BIND_SERVICE"],"ambient":["CAP_AUDIT_WRITE","CAP_KILL","CAP_NET_BIND_SERVICE"]},"rlimits":[{"type":"RLIMIT_NOFILE","hard":1024,"soft":1024}],"noNewPrivileges":true},"root":{"path":"rootfs","readonly":true},"hostname":"runc","mounts":[{"destination":"/proc","type":"proc","source":"proc"},{"destination":"/dev","type":"tmpfs","source":"tmpfs","options":["nosuid","strictatime","mode=755","size=65536k"]},{"destination":"/dev/pts","type":"devpts","source":"devpts","options":["nosuid","noexec","newinstance","ptmxmode=0666","mode=0620","gid=5"]},{"destination":"/dev/shm","type":"tmpfs","source":"shm","options":["nosuid","noexec","nodev","mode=1777","size=65536k"]},{"destination":"/dev/mqueue","type":"mqueue","source":"mqueue","options":["nosuid","noexec","nodev"]},{"destination":"/sys","type":"sysfs","source":"sysfs","options":["nosuid","noexec","nodev","ro"]},{"destination":"/sys/fs/cgroup","type":"cgroup","source":"cgroup","options":["nosuid","noexec","nodev","relatime","ro"]}],"linux":{"resources":{"devices":[{"allow":false,"access":"rwm"}]},"namespaces":[{"type":"pid"},{"type":"network"},{"type":"ipc"},{"type":"uts"},{"type":"mount"}],"maskedPaths":["/proc/acpi","/proc/asound","/proc/kcore","/proc/keys","/proc/latency_stats","/proc/timer_list","/proc/timer_stats","/proc/sched_debug","/sys/firmware","/proc/scsi"],"readonlyPaths":["/proc/bus","/proc/fs","/proc/irq","/proc/sys","/proc/sysrq-trigger"]}}"#;
 
-        let spec: Spec = serde_json::from_str(&spec_string).unwrap();
+        let spec: Spec = serde_json::from_str(spec_string).unwrap();
 
         assert!(spec.linux.is_some())
     }
@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde_derive::Serialize;
+
+use crate::container::cgroups::{Cgroup, CgroupVersion};
+
+const HUGEPAGES_DIR: &str = "/sys/kernel/mm/hugepages";
+
+// The types below follow the JSON layout of runc's `events` so that the
+// same parsers can read both.
+
+#[derive(Serialize, Default, Debug)]
+pub struct Stats {
+    pub cpu: Cpu,
+    pub memory: Memory,
+    pub pids: Pids,
+    pub blkio: Blkio,
+    pub hugetlb: HashMap<String, Hugetlb>,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Cpu {
+    pub usage: CpuUsage,
+    pub throttling: Throttling,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct CpuUsage {
+    #[serde(skip_serializing_if = "is_zero")]
+    pub total: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub percpu: Vec<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub percpu_kernel: Vec<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub percpu_user: Vec<u64>,
+    pub kernel: u64,
+    pub user: u64,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Throttling {
+    #[serde(skip_serializing_if = "is_zero")]
+    pub periods: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub throttled_periods: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub throttled_time: u64,
+}
+
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Memory {
+    #[serde(skip_serializing_if = "is_zero")]
+    pub cache: u64,
+    pub usage: MemoryEntry,
+    pub swap: MemoryEntry,
+    pub kernel: MemoryEntry,
+    #[serde(rename = "kernelTCP")]
+    pub kernel_tcp: MemoryEntry,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub raw: HashMap<String, u64>,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct MemoryEntry {
+    pub limit: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub usage: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub max: u64,
+    pub failcnt: u64,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Pids {
+    #[serde(skip_serializing_if = "is_zero")]
+    pub current: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub limit: u64,
+}
+
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Blkio {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub io_service_bytes_recursive: Vec<BlkioEntry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub io_serviced_recursive: Vec<BlkioEntry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub io_queue_recursive: Vec<BlkioEntry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub io_service_time_recursive: Vec<BlkioEntry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub io_wait_time_recursive: Vec<BlkioEntry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub io_merged_recursive: Vec<BlkioEntry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub io_time_recursive: Vec<BlkioEntry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sectors_recursive: Vec<BlkioEntry>,
+}
+
+#[derive(Serialize, Default, Debug, PartialEq)]
+pub struct BlkioEntry {
+    #[serde(skip_serializing_if = "is_zero")]
+    pub major: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub minor: u64,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub op: String,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub value: u64,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Hugetlb {
+    #[serde(skip_serializing_if = "is_zero")]
+    pub usage: u64,
+    pub max: u64,
+    pub failcnt: u64,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+impl Cgroup {
+    /// Resource usage of the container cgroup. Subsystems which are not
+    /// mounted or files the kernel does not provide are left zeroed.
+    pub fn stats(&self) -> Result<Stats> {
+        let mut stats = Stats::default();
+        match self.version() {
+            CgroupVersion::V1 => {
+                if let Some(path) = self.subsystem_path("cpuacct")? {
+                    cpuacct_stats_v1(&path, &mut stats.cpu)?;
+                }
+                if let Some(path) = self.subsystem_path("cpu")? {
+                    throttling_stats(&path, 1, &mut stats.cpu.throttling)?;
+                }
+                if let Some(path) = self.subsystem_path("memory")? {
+                    memory_stats_v1(&path, &mut stats.memory)?;
+                }
+                if let Some(path) = self.subsystem_path("pids")? {
+                    pids_stats(&path, &mut stats.pids)?;
+                }
+                if let Some(path) = self.subsystem_path("blkio")? {
+                    blkio_stats_v1(&path, &mut stats.blkio)?;
+                }
+                if let Some(path) = self.subsystem_path("hugetlb")? {
+                    hugetlb_stats(&path, CgroupVersion::V1, &mut stats.hugetlb)?;
+                }
+            }
+            CgroupVersion::V2 => {
+                if let Some(path) = self.subsystem_path("cpu")? {
+                    cpu_stats_v2(&path, &mut stats.cpu)?;
+                    memory_stats_v2(&path, &mut stats.memory)?;
+                    pids_stats(&path, &mut stats.pids)?;
+                    io_stats_v2(&path, &mut stats.blkio)?;
+                    hugetlb_stats(&path, CgroupVersion::V2, &mut stats.hugetlb)?;
+                }
+            }
+        }
+        Ok(stats)
+    }
+}
+
+/// Contents of `dir/file`, or None if the kernel does not provide it.
+fn read_optional(dir: &Path, file: &str) -> Result<Option<String>> {
+    match fs::read_to_string(dir.join(file)) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A single number; `max` means unlimited and reads as `unlimited`.
+fn read_u64(dir: &Path, file: &str, unlimited: u64) -> Result<u64> {
+    match read_optional(dir, file)? {
+        Some(value) if value.trim() == "max" => Ok(unlimited),
+        Some(value) => Ok(value.trim().parse()?),
+        None => Ok(0),
+    }
+}
+
+/// A flat keyed file of `<key> <value>` lines.
+fn read_flat_keyed(dir: &Path, file: &str) -> Result<HashMap<String, u64>> {
+    Ok(parse_flat_keyed(
+        &read_optional(dir, file)?.unwrap_or_default(),
+    ))
+}
+
+fn parse_flat_keyed(content: &str) -> HashMap<String, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let key = fields.next()?;
+            let value = fields.next()?.parse().ok()?;
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+fn parse_u64_list(content: &str) -> Result<Vec<u64>> {
+    Ok(content
+        .split_whitespace()
+        .map(|value| value.parse())
+        .collect::<Result<_, _>>()?)
+}
+
+fn cpuacct_stats_v1(path: &Path, cpu: &mut Cpu) -> Result<()> {
+    cpu.usage.total = read_u64(path, "cpuacct.usage", 0)?;
+    if let Some(percpu) = read_optional(path, "cpuacct.usage_percpu")? {
+        cpu.usage.percpu = parse_u64_list(&percpu)?;
+    }
+    if let Some(percpu) = read_optional(path, "cpuacct.usage_percpu_sys")? {
+        cpu.usage.percpu_kernel = parse_u64_list(&percpu)?;
+    }
+    if let Some(percpu) = read_optional(path, "cpuacct.usage_percpu_user")? {
+        cpu.usage.percpu_user = parse_u64_list(&percpu)?;
+    }
+
+    // cpuacct.stat counts clock ticks rather than nanoseconds.
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    let nanos_per_tick = 1_000_000_000 / if ticks > 0 { ticks as u64 } else { 100 };
+    let stat = read_flat_keyed(path, "cpuacct.stat")?;
+    cpu.usage.kernel = stat.get("system").copied().unwrap_or(0) * nanos_per_tick;
+    cpu.usage.user = stat.get("user").copied().unwrap_or(0) * nanos_per_tick;
+    Ok(())
+}
+
+/// `cpu.stat` throttling counters, whose times are in `time_unit` ns.
+fn throttling_stats(path: &Path, time_unit: u64, throttling: &mut Throttling) -> Result<()> {
+    let stat = read_flat_keyed(path, "cpu.stat")?;
+    throttling.periods = stat.get("nr_periods").copied().unwrap_or(0);
+    throttling.throttled_periods = stat.get("nr_throttled").copied().unwrap_or(0);
+    throttling.throttled_time = stat
+        .get("throttled_time")
+        .or_else(|| stat.get("throttled_usec"))
+        .copied()
+        .unwrap_or(0)
+        * time_unit;
+    Ok(())
+}
+
+fn cpu_stats_v2(path: &Path, cpu: &mut Cpu) -> Result<()> {
+    let stat = read_flat_keyed(path, "cpu.stat")?;
+    cpu.usage.total = stat.get("usage_usec").copied().unwrap_or(0) * 1000;
+    cpu.usage.kernel = stat.get("system_usec").copied().unwrap_or(0) * 1000;
+    cpu.usage.user = stat.get("user_usec").copied().unwrap_or(0) * 1000;
+    throttling_stats(path, 1000, &mut cpu.throttling)
+}
+
+fn memory_entry_v1(path: &Path, prefix: &str) -> Result<MemoryEntry> {
+    let file = |name: &str| format!("{}.{}", prefix, name);
+    Ok(MemoryEntry {
+        limit: read_u64(path, &file("limit_in_bytes"), u64::MAX)?,
+        usage: read_u64(path, &file("usage_in_bytes"), 0)?,
+        max: read_u64(path, &file("max_usage_in_bytes"), 0)?,
+        failcnt: read_u64(path, &file("failcnt"), 0)?,
+    })
+}
+
+fn memory_stats_v1(path: &Path, memory: &mut Memory) -> Result<()> {
+    memory.raw = read_flat_keyed(path, "memory.stat")?;
+    memory.cache = memory.raw.get("cache").copied().unwrap_or(0);
+    memory.usage = memory_entry_v1(path, "memory")?;
+    memory.swap = memory_entry_v1(path, "memory.memsw")?;
+    memory.kernel = memory_entry_v1(path, "memory.kmem")?;
+    memory.kernel_tcp = memory_entry_v1(path, "memory.kmem.tcp")?;
+    Ok(())
+}
+
+fn memory_stats_v2(path: &Path, memory: &mut Memory) -> Result<()> {
+    memory.raw = read_flat_keyed(path, "memory.stat")?;
+    memory.cache = memory.raw.get("file").copied().unwrap_or(0);
+    let events = read_flat_keyed(path, "memory.events")?;
+    memory.usage = MemoryEntry {
+        limit: read_u64(path, "memory.max", u64::MAX)?,
+        usage: read_u64(path, "memory.current", 0)?,
+        max: read_u64(path, "memory.peak", 0)?,
+        failcnt: events.get("max").copied().unwrap_or(0),
+    };
+    memory.swap = MemoryEntry {
+        limit: read_u64(path, "memory.swap.max", u64::MAX)?,
+        usage: read_u64(path, "memory.swap.current", 0)?,
+        ..MemoryEntry::default()
+    };
+    Ok(())
+}
+
+fn pids_stats(path: &Path, pids: &mut Pids) -> Result<()> {
+    pids.current = read_u64(path, "pids.current", 0)?;
+    // runc reports an unlimited pids.max as 0.
+    pids.limit = read_u64(path, "pids.max", 0)?;
+    Ok(())
+}
+
+/// v1 blkio files of `<major>:<minor> [<op>] <value>` lines.
+fn parse_blkio_v1(content: &str) -> Result<Vec<BlkioEntry>> {
+    let mut entries = Vec::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (device, op, value) = match fields.as_slice() {
+            [device, op, value] => (device, op.to_string(), value),
+            [device, value] => (device, String::new(), value),
+            _ => continue,
+        };
+        // Skip the trailing "Total <value>" line.
+        let (major, minor) = match parse_device(device) {
+            Some(device) => device,
+            None => continue,
+        };
+        entries.push(BlkioEntry {
+            major,
+            minor,
+            op,
+            value: value.parse()?,
+        });
+    }
+    Ok(entries)
+}
+
+fn parse_device(device: &str) -> Option<(u64, u64)> {
+    let mut numbers = device.splitn(2, ':');
+    let major = numbers.next()?.parse().ok()?;
+    let minor = numbers.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+fn blkio_stats_v1(path: &Path, blkio: &mut Blkio) -> Result<()> {
+    // The CFQ files are missing with other io schedulers, in which case
+    // only the throttle counters are available.
+    let read = |file: &str| -> Result<Vec<BlkioEntry>> {
+        match read_optional(path, &format!("blkio.{}", file))? {
+            Some(content) => parse_blkio_v1(&content),
+            None => Ok(vec![]),
+        }
+    };
+    blkio.io_service_bytes_recursive = read("io_service_bytes_recursive")?;
+    blkio.io_serviced_recursive = read("io_serviced_recursive")?;
+    blkio.io_queue_recursive = read("io_queued_recursive")?;
+    blkio.io_service_time_recursive = read("io_service_time_recursive")?;
+    blkio.io_wait_time_recursive = read("io_wait_time_recursive")?;
+    blkio.io_merged_recursive = read("io_merged_recursive")?;
+    blkio.io_time_recursive = read("time_recursive")?;
+    blkio.sectors_recursive = read("sectors_recursive")?;
+    if blkio.io_service_bytes_recursive.is_empty() {
+        blkio.io_service_bytes_recursive = read("throttle.io_service_bytes")?;
+        blkio.io_serviced_recursive = read("throttle.io_serviced")?;
+    }
+    Ok(())
+}
+
+/// io.stat lines of `<major>:<minor> rbytes=<n> wbytes=<n> rios=<n> ...`.
+fn parse_io_stat(content: &str, blkio: &mut Blkio) -> Result<()> {
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let (major, minor) = match fields.next().and_then(parse_device) {
+            Some(device) => device,
+            None => continue,
+        };
+        for field in fields {
+            let mut pair = field.splitn(2, '=');
+            let (key, value) = match (pair.next(), pair.next()) {
+                (Some(key), Some(value)) => (key, value.parse()?),
+                _ => continue,
+            };
+            let (entries, op) = match key {
+                "rbytes" => (&mut blkio.io_service_bytes_recursive, "Read"),
+                "wbytes" => (&mut blkio.io_service_bytes_recursive, "Write"),
+                "rios" => (&mut blkio.io_serviced_recursive, "Read"),
+                "wios" => (&mut blkio.io_serviced_recursive, "Write"),
+                _ => continue,
+            };
+            entries.push(BlkioEntry {
+                major,
+                minor,
+                op: op.into(),
+                value,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn io_stats_v2(path: &Path, blkio: &mut Blkio) -> Result<()> {
+    parse_io_stat(&read_optional(path, "io.stat")?.unwrap_or_default(), blkio)
+}
+
+/// Huge page sizes as named in hugetlb files, e.g. `2MB` or `1GB`.
+fn hugepage_sizes() -> Result<Vec<String>> {
+    let entries = match fs::read_dir(HUGEPAGES_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut sizes = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        sizes.push(hugepage_size_name(&name)?);
+    }
+    Ok(sizes)
+}
+
+/// `hugepages-2048kB` to `2MB`.
+fn hugepage_size_name(dir_name: &str) -> Result<String> {
+    let kb: u64 = dir_name
+        .trim_start_matches("hugepages-")
+        .trim_end_matches("kB")
+        .parse()
+        .map_err(|_| anyhow!("unexpected hugepages directory: {}", dir_name))?;
+    let units = ["KB", "MB", "GB", "TB"];
+    let mut size = kb;
+    let mut unit = 0;
+    while size >= 1024 && size.is_multiple_of(1024) && unit < units.len() - 1 {
+        size /= 1024;
+        unit += 1;
+    }
+    Ok(format!("{}{}", size, units[unit]))
+}
+
+fn hugetlb_stats(
+    path: &Path,
+    version: CgroupVersion,
+    hugetlb: &mut HashMap<String, Hugetlb>,
+) -> Result<()> {
+    for size in hugepage_sizes()? {
+        let file = |name: &str| format!("hugetlb.{}.{}", size, name);
+        let stats = match version {
+            CgroupVersion::V1 => Hugetlb {
+                usage: read_u64(path, &file("usage_in_bytes"), 0)?,
+                max: read_u64(path, &file("max_usage_in_bytes"), 0)?,
+                failcnt: read_u64(path, &file("failcnt"), 0)?,
+            },
+            CgroupVersion::V2 => Hugetlb {
+                usage: read_u64(path, &file("current"), 0)?,
+                max: read_u64(path, &file("max"), u64::MAX)?,
+                failcnt: read_flat_keyed(path, &file("events"))?
+                    .get("max")
+                    .copied()
+                    .unwrap_or(0),
+            },
+        };
+        hugetlb.insert(size, stats);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flat_keyed_file_should_be_parsed() {
+        let stat = parse_flat_keyed("cache 4096\nrss 8192\nbroken\n");
+        assert_eq!(stat.len(), 2);
+        assert_eq!(stat["cache"], 4096);
+        assert_eq!(stat["rss"], 8192);
+    }
+
+    #[test]
+    fn blkio_v1_file_should_be_parsed() {
+        let entries = parse_blkio_v1("8:0 Read 100\n8:0 Write 200\nTotal 300\n").unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                BlkioEntry {
+                    major: 8,
+                    minor: 0,
+                    op: "Read".into(),
+                    value: 100,
+                },
+                BlkioEntry {
+                    major: 8,
+                    minor: 0,
+                    op: "Write".into(),
+                    value: 200,
+                },
+            ]
+        );
+
+        let entries = parse_blkio_v1("259:1 42\n").unwrap();
+        assert_eq!(entries[0].major, 259);
+        assert_eq!(entries[0].op, "");
+        assert_eq!(entries[0].value, 42);
+    }
+
+    #[test]
+    fn io_stat_should_be_parsed() {
+        let mut blkio = Blkio::default();
+        parse_io_stat(
+            "8:16 rbytes=1024 wbytes=2048 rios=1 wios=2 dbytes=0 dios=0\n",
+            &mut blkio,
+        )
+        .unwrap();
+
+        assert_eq!(blkio.io_service_bytes_recursive.len(), 2);
+        assert_eq!(blkio.io_service_bytes_recursive[1].op, "Write");
+        assert_eq!(blkio.io_service_bytes_recursive[1].value, 2048);
+        assert_eq!(blkio.io_serviced_recursive[0].minor, 16);
+        assert_eq!(blkio.io_serviced_recursive[0].value, 1);
+    }
+
+    #[test]
+    fn hugepage_size_should_be_named() {
+        assert_eq!(hugepage_size_name("hugepages-2048kB").unwrap(), "2MB");
+        assert_eq!(hugepage_size_name("hugepages-1048576kB").unwrap(), "1GB");
+        assert_eq!(hugepage_size_name("hugepages-64kB").unwrap(), "64KB");
+        assert!(hugepage_size_name("hugepages").is_err());
+    }
+
+    #[test]
+    fn empty_fields_should_be_omitted() {
+        let json = serde_json::to_value(Stats::default()).unwrap();
+        assert_eq!(json["pids"], serde_json::json!({}));
+        assert_eq!(
+            json["cpu"]["usage"],
+            serde_json::json!({"kernel": 0, "user": 0})
+        );
+        assert_eq!(
+            json["memory"]["kernelTCP"],
+            serde_json::json!({"limit": 0, "failcnt": 0})
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::fs;
+use std::io::ErrorKind;
+
+use anyhow::{bail, Context, Result};
+
+pub static PASSWD_FILE: &str = "/etc/passwd";
+pub static GROUP_FILE: &str = "/etc/group";
+
+/// Identity a user name stands for in the container.
+#[derive(Debug, PartialEq)]
+pub struct ResolvedUser {
+    pub uid: u32,
+    pub gid: u32,
+    /// Groups listing the user as a member.
+    pub groups: Vec<u32>,
+}
+
+/// Resolve `name` against the passwd and group files of the filesystem the
+/// calling process is in, i.e. the container's once the rootfs is set up.
+pub fn resolve(name: &str) -> Result<ResolvedUser> {
+    let passwd = fs::read_to_string(PASSWD_FILE)
+        .with_context(|| format!("failed to resolve user {}", name))?;
+    let group = match fs::read_to_string(GROUP_FILE) {
+        Ok(group) => group,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context(format!("failed to read {}", GROUP_FILE)),
+    };
+    resolve_in(name, &passwd, &group)
+}
+
+fn resolve_in(name: &str, passwd: &str, group: &str) -> Result<ResolvedUser> {
+    let (uid, gid) = match find_user(passwd, name)? {
+        Some(ids) => ids,
+        None => bail!("user {} is not found in {}", name, PASSWD_FILE),
+    };
+    Ok(ResolvedUser {
+        uid,
+        gid,
+        groups: member_groups(group, name)?,
+    })
+}
+
+/// Uid and gid of the entry for `name` in a passwd file.
+fn find_user(passwd: &str, name: &str) -> Result<Option<(u32, u32)>> {
+    for line in entries(passwd) {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 4 || fields[0] != name {
+            continue;
+        }
+        let uid = parse_id(fields[2], PASSWD_FILE)?;
+        let gid = parse_id(fields[3], PASSWD_FILE)?;
+        return Ok(Some((uid, gid)));
+    }
+    Ok(None)
+}
+
+/// Gids of the entries of a group file listing `name` as a member.
+fn member_groups(group: &str, name: &str) -> Result<Vec<u32>> {
+    let mut gids = vec![];
+    for line in entries(group) {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 4 || !fields[3].split(',').any(|member| member.trim() == name) {
+            continue;
+        }
+        gids.push(parse_id(fields[2], GROUP_FILE)?);
+    }
+    Ok(gids)
+}
+
+fn entries(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+fn parse_id(id: &str, file: &str) -> Result<u32> {
+    id.parse()
+        .with_context(|| format!("invalid id {} in {}", id, file))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PASSWD: &str = "root:x:0:0:root:/root:/bin/sh
+# comment
+daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin
+app:x:1000:1001:App:/home/app:/bin/sh
+";
+    const GROUP: &str = "root:x:0:
+wheel:x:10:root,app
+audio:x:29:daemon
+video:x:44: app
+";
+
+    #[test]
+    fn user_should_be_resolved_with_groups() {
+        let user = resolve_in("app", PASSWD, GROUP).unwrap();
+        assert_eq!(
+            user,
+            ResolvedUser {
+                uid: 1000,
+                gid: 1001,
+                groups: vec![10, 44],
+            }
+        );
+        let user = resolve_in("daemon", PASSWD, "").unwrap();
+        assert_eq!((user.uid, user.gid), (1, 1));
+        assert!(user.groups.is_empty());
+    }
+
+    #[test]
+    fn unknown_user_should_be_rejected() {
+        let err = resolve_in("nobody", PASSWD, GROUP).unwrap_err();
+        assert_eq!(err.to_string(), "user nobody is not found in /etc/passwd");
+    }
+
+    #[test]
+    fn broken_entry_should_be_rejected() {
+        let err = resolve_in("app", "app:x:abc:0::/:/bin/sh", "").unwrap_err();
+        assert_eq!(err.to_string(), "invalid id abc in /etc/passwd");
+    }
+}
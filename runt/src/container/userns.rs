@@ -0,0 +1,229 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use nix::unistd::{getegid, geteuid, Pid};
+
+use crate::container::specs::{LinuxIDMapping, LinuxNamespace, LinuxNamespaceType, Spec};
+
+/// Helpers from shadow-utils writing the mappings an unprivileged user is
+/// allowed in /etc/subuid and /etc/subgid.
+const NEWUIDMAP: &str = "newuidmap";
+const NEWGIDMAP: &str = "newgidmap";
+
+/// Whether `namespaces` asks for a new user namespace, whose ids the parent
+/// has to map, rather than joining one.
+pub fn is_new(namespaces: &[LinuxNamespace]) -> bool {
+    namespaces
+        .iter()
+        .any(|ns| ns.type_ == LinuxNamespaceType::User && ns.path.is_none())
+}
+
+fn mappings(spec: &Spec) -> (&[LinuxIDMapping], &[LinuxIDMapping]) {
+    let linux = spec.linux.as_ref();
+    (
+        linux
+            .and_then(|linux| linux.uid_mappings.as_deref())
+            .unwrap_or_default(),
+        linux
+            .and_then(|linux| linux.gid_mappings.as_deref())
+            .unwrap_or_default(),
+    )
+}
+
+/// Reject mappings without a new user namespace and the other way around,
+/// and a rootfs the container root couldn't own.
+pub fn validate(spec: &Spec, namespaces: &[LinuxNamespace], rootfs: &Path) -> Result<()> {
+    let (uid_mappings, gid_mappings) = mappings(spec);
+    if !is_new(namespaces) {
+        if !uid_mappings.is_empty() || !gid_mappings.is_empty() {
+            bail!("uid and gid mappings need a new user namespace");
+        }
+        return Ok(());
+    }
+    if uid_mappings.is_empty() || gid_mappings.is_empty() {
+        bail!("a new user namespace needs uid and gid mappings");
+    }
+
+    let metadata = fs::metadata(rootfs)
+        .with_context(|| format!("failed to stat rootfs {}", rootfs.display()))?;
+    if to_container(uid_mappings, metadata.uid()).is_none() {
+        bail!(
+            "rootfs {} is owned by uid {}, which is not mapped in the user namespace",
+            rootfs.display(),
+            metadata.uid()
+        );
+    }
+    if to_container(gid_mappings, metadata.gid()).is_none() {
+        bail!(
+            "rootfs {} is owned by gid {}, which is not mapped in the user namespace",
+            rootfs.display(),
+            metadata.gid()
+        );
+    }
+    Ok(())
+}
+
+/// Id in the container of the host id `id`.
+fn to_container(mappings: &[LinuxIDMapping], id: u32) -> Option<u32> {
+    mappings
+        .iter()
+        .find(|m| id >= m.host_id && id - m.host_id < m.size)
+        .map(|m| m.container_id + (id - m.host_id))
+}
+
+/// Write the mappings of `spec` for the user namespace `pid` has created.
+/// The uid map goes first, then setgroups and the gid map.
+pub fn write_mappings(pid: Pid, spec: &Spec) -> Result<()> {
+    let (uid_mappings, gid_mappings) = mappings(spec);
+    if needs_helper(uid_mappings, geteuid().as_raw()) {
+        run_helper(NEWUIDMAP, pid, uid_mappings)?;
+    } else {
+        write_map(pid, "uid_map", uid_mappings)?;
+    }
+    if needs_helper(gid_mappings, getegid().as_raw()) {
+        run_helper(NEWGIDMAP, pid, gid_mappings)?;
+    } else {
+        // Without CAP_SETGID, the kernel only takes a gid map once
+        // setgroups(2) is denied in the namespace.
+        if !geteuid().is_root() {
+            fs::write(format!("/proc/{}/setgroups", pid), "deny")
+                .context("failed to deny setgroups in the user namespace")?;
+        }
+        write_map(pid, "gid_map", gid_mappings)?;
+    }
+    Ok(())
+}
+
+/// Whether the kernel would refuse `mappings` from us: an unprivileged user
+/// may only map its own `id`.
+fn needs_helper(mappings: &[LinuxIDMapping], id: u32) -> bool {
+    match mappings {
+        _ if geteuid().is_root() => false,
+        [mapping] => mapping.host_id != id || mapping.size != 1,
+        _ => true,
+    }
+}
+
+/// Content of a uid_map or gid_map file, one `<container> <host> <size>`
+/// line per mapping.
+fn format_map(mappings: &[LinuxIDMapping]) -> String {
+    mappings
+        .iter()
+        .map(|m| format!("{} {} {}\n", m.container_id, m.host_id, m.size))
+        .collect()
+}
+
+fn write_map(pid: Pid, file: &str, mappings: &[LinuxIDMapping]) -> Result<()> {
+    // The kernel takes the whole map in a single write.
+    fs::write(format!("/proc/{}/{}", pid, file), format_map(mappings))
+        .with_context(|| format!("failed to write {}", file))
+}
+
+fn run_helper(helper: &str, pid: Pid, mappings: &[LinuxIDMapping]) -> Result<()> {
+    let mut command = Command::new(helper);
+    command.arg(pid.to_string());
+    for m in mappings {
+        command.args(&[
+            m.container_id.to_string(),
+            m.host_id.to_string(),
+            m.size.to_string(),
+        ]);
+    }
+    let output = command
+        .output()
+        .with_context(|| format!("failed to run {}", helper))?;
+    if !output.status.success() {
+        bail!(
+            "{} failed: {}",
+            helper,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    Ok(())
+}
+
+/// Whether setgroups(2) is denied in our user namespace, as it is in one
+/// whose gid map was written by an unprivileged user.
+pub fn setgroups_denied() -> bool {
+    fs::read_to_string("/proc/self/setgroups")
+        .map(|setgroups| setgroups.trim_end() == "deny")
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::container::specs::Linux;
+
+    fn mapping(container_id: u32, host_id: u32, size: u32) -> LinuxIDMapping {
+        LinuxIDMapping {
+            container_id,
+            host_id,
+            size,
+        }
+    }
+
+    fn user_namespace() -> Vec<LinuxNamespace> {
+        vec![LinuxNamespace {
+            type_: LinuxNamespaceType::User,
+            path: None,
+        }]
+    }
+
+    #[test]
+    fn mappings_should_be_formatted_one_per_line() {
+        let mappings = [mapping(0, 1000, 1), mapping(1, 100_000, 65536)];
+        assert_eq!(format_map(&mappings), "0 1000 1\n1 100000 65536\n");
+        assert_eq!(to_container(&mappings, 1000), Some(0));
+        assert_eq!(to_container(&mappings, 100_009), Some(10));
+        assert_eq!(to_container(&mappings, 165_536), None);
+        assert_eq!(to_container(&mappings, 0), None);
+    }
+
+    #[test]
+    fn mappings_should_match_user_namespace() {
+        let rootfs = tempfile::tempdir().unwrap();
+        let mut spec = Spec::default();
+        let err = validate(&spec, &user_namespace(), rootfs.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a new user namespace needs uid and gid mappings"
+        );
+
+        let linux = spec.linux.get_or_insert_with(Linux::default);
+        linux.uid_mappings = Some(vec![mapping(0, 0, 1)]);
+        linux.gid_mappings = Some(vec![mapping(0, 0, 1)]);
+        let err = validate(&spec, &[], rootfs.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "uid and gid mappings need a new user namespace"
+        );
+    }
+
+    #[test]
+    fn unmapped_rootfs_owner_should_be_rejected() {
+        let rootfs = tempfile::tempdir().unwrap();
+        let metadata = fs::metadata(rootfs.path()).unwrap();
+        let mut spec = Spec::default();
+        let linux = spec.linux.get_or_insert_with(Linux::default);
+        linux.uid_mappings = Some(vec![mapping(0, metadata.uid() + 1, 10)]);
+        linux.gid_mappings = Some(vec![mapping(0, metadata.gid(), 1)]);
+        let err = validate(&spec, &user_namespace(), rootfs.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "rootfs {} is owned by uid {}, which is not mapped in the user namespace",
+                rootfs.path().display(),
+                metadata.uid()
+            )
+        );
+
+        let linux = spec.linux.as_mut().unwrap();
+        linux.uid_mappings = Some(vec![mapping(0, metadata.uid(), 1)]);
+        assert!(validate(&spec, &user_namespace(), rootfs.path()).is_ok());
+    }
+}
@@ -5,10 +5,22 @@ mod specutil;
 mod subcommand;
 
 use anyhow::Result;
+use subcommand::checkpoint::CheckpointCommand;
 use subcommand::create::CreateCommand;
 use subcommand::delete::DeleteCommand;
+use subcommand::events::EventsCommand;
+use subcommand::exec::ExecCommand;
+use subcommand::kill::KillCommand;
+use subcommand::list::ListCommand;
+use subcommand::pause::PauseCommand;
+use subcommand::ps::PsCommand;
+use subcommand::restore::RestoreCommand;
+use subcommand::resume::ResumeCommand;
+use subcommand::run::RunCommand;
 use subcommand::spec::SpecCommand;
+use subcommand::start::StartCommand;
 use subcommand::state::StateCommand;
+use subcommand::update::UpdateCommand;
 use subcommand::{SubCommand, SubCommandImpl};
 
 fn main() -> Result<()> {
@@ -17,9 +29,21 @@ fn main() -> Result<()> {
 
     let subcommand_: SubCommand = match app_matches.subcommand() {
         ("create", Some(matches)) => SubCommand::Create(CreateCommand::new(matches)?),
+        ("start", Some(matches)) => SubCommand::Start(StartCommand::new(matches)?),
+        ("run", Some(matches)) => SubCommand::Run(RunCommand::new(matches)?),
         ("spec", Some(matches)) => SubCommand::Spec(SpecCommand::new(matches)?),
         ("state", Some(matches)) => SubCommand::State(StateCommand::new(matches)?),
         ("delete", Some(matches)) => SubCommand::Delete(DeleteCommand::new(matches)?),
+        ("kill", Some(matches)) => SubCommand::Kill(KillCommand::new(matches)?),
+        ("exec", Some(matches)) => SubCommand::Exec(ExecCommand::new(matches)?),
+        ("list", Some(matches)) => SubCommand::List(ListCommand::new(matches)?),
+        ("ps", Some(matches)) => SubCommand::Ps(PsCommand::new(matches)?),
+        ("pause", Some(matches)) => SubCommand::Pause(PauseCommand::new(matches)?),
+        ("resume", Some(matches)) => SubCommand::Resume(ResumeCommand::new(matches)?),
+        ("events", Some(matches)) => SubCommand::Events(EventsCommand::new(matches)?),
+        ("update", Some(matches)) => SubCommand::Update(Box::new(UpdateCommand::new(matches)?)),
+        ("checkpoint", Some(matches)) => SubCommand::Checkpoint(CheckpointCommand::new(matches)?),
+        ("restore", Some(matches)) => SubCommand::Restore(RestoreCommand::new(matches)?),
         _ => {
             app.print_help()?;
             std::process::exit(1);
@@ -28,9 +52,21 @@ fn main() -> Result<()> {
 
     match subcommand_ {
         SubCommand::Create(command) => command.run()?,
+        SubCommand::Start(command) => command.run()?,
+        SubCommand::Run(command) => command.run()?,
         SubCommand::Spec(command) => command.run()?,
         SubCommand::State(command) => command.run()?,
         SubCommand::Delete(command) => command.run()?,
+        SubCommand::Kill(command) => command.run()?,
+        SubCommand::Exec(command) => command.run()?,
+        SubCommand::List(command) => command.run()?,
+        SubCommand::Ps(command) => command.run()?,
+        SubCommand::Pause(command) => command.run()?,
+        SubCommand::Resume(command) => command.run()?,
+        SubCommand::Events(command) => command.run()?,
+        SubCommand::Update(command) => command.run()?,
+        SubCommand::Checkpoint(command) => command.run()?,
+        SubCommand::Restore(command) => command.run()?,
     }
 
     Ok(())
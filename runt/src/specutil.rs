@@ -1,12 +1,12 @@
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::Path;
 
 use anyhow::Result;
 
 use crate::config::SPEC_FILE;
 use crate::container::specs::Spec;
 
-pub fn load(bundle: &PathBuf) -> Result<Spec> {
+pub fn load(bundle: &Path) -> Result<Spec> {
     let config_path = bundle.join(SPEC_FILE);
     let config_file = File::open(&config_path)?;
 
@@ -15,7 +15,7 @@ pub fn load(bundle: &PathBuf) -> Result<Spec> {
     Ok(spec)
 }
 
-pub fn write(bundle: &PathBuf, spec: &Spec) -> Result<()> {
+pub fn write(bundle: &Path, spec: &Spec) -> Result<()> {
     let config_path = bundle.join(SPEC_FILE);
     let mut config_file = File::create(&config_path)?;
 
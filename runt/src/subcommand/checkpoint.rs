@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ArgMatches;
+
+use crate::config;
+use crate::container::checkpoint::CheckpointOptions;
+use crate::container::{Container, MetadataManager};
+use crate::subcommand::SubCommandImpl;
+
+pub struct CheckpointCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+    pub options: CheckpointOptions,
+}
+
+/// Options of `checkpoint` which `restore` shares.
+pub fn checkpoint_options(matches: &ArgMatches) -> CheckpointOptions {
+    CheckpointOptions {
+        image_path: PathBuf::from(matches.value_of("image-path").unwrap_or("checkpoint")),
+        work_path: matches.value_of("work-path").map(PathBuf::from),
+        leave_running: matches.is_present("leave-running"),
+        tcp_established: matches.is_present("tcp-established"),
+    }
+}
+
+impl SubCommandImpl for CheckpointCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches
+            .value_of("container-id")
+            .expect("container-id must be specify:");
+
+        Ok(CheckpointCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+            options: checkpoint_options(matches),
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        let mut container = Container::load(&self.root, &self.container_id)?;
+        container.refresh_status()?;
+        container.checkpoint(&self.options)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cli::app_config;
+    use crate::container::testutil;
+
+    #[test]
+    fn options_should_be_parsed() {
+        let args = vec![
+            "runt",
+            "checkpoint",
+            "--image-path",
+            "/images",
+            "--leave-running",
+            "--tcp-established",
+            "container-a",
+        ];
+        let app_matches = app_config()
+            .get_matches_from_safe(testutil::args_with_root(&args))
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+        let checkpoint_command = match app_matches.subcommand() {
+            ("checkpoint", Some(matches)) => Some(CheckpointCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(checkpoint_command.container_id, "container-a");
+        let options = checkpoint_command.options;
+        assert_eq!(options.image_path, PathBuf::from("/images"));
+        assert_eq!(options.work_path, None);
+        assert!(options.leave_running);
+        assert!(options.tcp_established);
+    }
+}
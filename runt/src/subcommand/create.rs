@@ -1,17 +1,23 @@
+use std::fs;
 use std::path::PathBuf;
 
 use anyhow::Result;
 use clap::ArgMatches;
 
+use crate::config;
 use crate::container::Container;
 use crate::specutil;
 use crate::subcommand::SubCommandImpl;
 
 pub struct CreateCommand {
     pub container_id: String,
+    pub root: PathBuf,
     pub bundle: PathBuf,
     pub pid_file: Option<PathBuf>,
+    #[allow(dead_code)]
     pub console_socket: Option<PathBuf>,
+    pub no_pivot: bool,
+    pub ignore_unknown_caps: bool,
 }
 
 impl SubCommandImpl for CreateCommand {
@@ -22,17 +28,25 @@ impl SubCommandImpl for CreateCommand {
         let console_socket = matches.value_of("console-socket").map(PathBuf::from);
         Ok(CreateCommand {
             container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
             bundle,
             pid_file,
             console_socket,
+            no_pivot: matches.is_present("no-pivot"),
+            ignore_unknown_caps: matches.is_present("ignore-unknown-caps"),
         })
     }
 
     fn run(&self) -> Result<()> {
         let spec = specutil::load(&self.bundle)?;
-        let mut container = Container::new(&self.container_id, &self.bundle, spec);
+        let mut container = Container::new(&self.root, &self.container_id, &self.bundle, spec);
+        container.no_pivot = self.no_pivot;
+        container.ignore_unknown_caps = self.ignore_unknown_caps;
         container.create()?;
 
+        if let (Some(pid_file), Some(pid)) = (&self.pid_file, container.pid) {
+            fs::write(pid_file, pid.to_string())?;
+        }
         Ok(())
     }
 }
@@ -44,12 +58,11 @@ mod test {
     use uuid::Uuid;
 
     use crate::cli::app_config;
-    use crate::container::testutil;
-    use crate::container::DEFAULT_META_ROOT;
+    use crate::container::{testutil, MetadataManager};
 
     fn init_create_command(args: Vec<&str>) -> CreateCommand {
         let app_matches = app_config()
-            .get_matches_from_safe(&args)
+            .get_matches_from_safe(testutil::args_with_root(&args))
             .expect("arg matche err: ");
 
         match app_matches.subcommand() {
@@ -166,7 +179,7 @@ mod test {
     fn create_shuould_be_success() {
         let container_id = Uuid::new_v4().to_string();
         let bundle = testutil::init_bundle_dir().unwrap();
-        let meta_dir = PathBuf::from(DEFAULT_META_ROOT).join(&container_id);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
         let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
         testutil::init_spec_file(&bundle, &rootfs).unwrap();
 
@@ -181,6 +194,10 @@ mod test {
         let create_command = init_create_command(args);
 
         assert!(create_command.run().is_ok());
+        assert!(Container::load(testutil::TEST_ROOT.as_ref(), &container_id)
+            .unwrap()
+            .delete()
+            .is_ok());
 
         testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
     }
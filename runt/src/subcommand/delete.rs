@@ -1,11 +1,17 @@
-use anyhow::Result;
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
 use clap::ArgMatches;
 
+use crate::config;
+use crate::container::specs::Status;
 use crate::container::{Container, MetadataManager};
 use crate::subcommand::SubCommandImpl;
 
 pub struct DeleteCommand {
     pub container_id: String,
+    pub root: PathBuf,
+    pub force: bool,
 }
 
 impl SubCommandImpl for DeleteCommand {
@@ -16,11 +22,23 @@ impl SubCommandImpl for DeleteCommand {
 
         Ok(DeleteCommand {
             container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+            force: matches.is_present("force delete container"),
         })
     }
 
     fn run(&self) -> Result<()> {
-        let container = Container::load(&self.container_id)?;
+        let mut container = Container::load(&self.root, &self.container_id)?;
+        container.refresh_status()?;
+        if let Status::Running | Status::Paused = container.status {
+            if !self.force {
+                bail!(
+                    "cannot delete {} container {}, stop it first or use --force",
+                    container.status,
+                    self.container_id
+                );
+            }
+        }
         container.delete()?;
         Ok(())
     }
@@ -34,7 +52,7 @@ mod test {
     use uuid::Uuid;
 
     use crate::cli::app_config;
-    use crate::container::{testutil, DEFAULT_META_ROOT};
+    use crate::container::testutil;
     use crate::specutil;
 
     #[test]
@@ -45,15 +63,16 @@ mod test {
         testutil::init_spec_file(&bundle, &rootfs).unwrap();
         let spec = specutil::load(&bundle).unwrap();
 
-        let meta_dir = PathBuf::from(DEFAULT_META_ROOT).join(&container_id);
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
 
-        let mut container = Container::new(&container_id, &bundle, spec);
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
         assert!(container.create().is_ok());
 
         let args = vec!["runt", "delete", &container_id];
 
         let app_matches = app_config()
-            .get_matches_from_safe(&args)
+            .get_matches_from_safe(testutil::args_with_root(&args))
             .unwrap_or_else(|e| panic!("An error occurs: {}", e));
 
         let subcommand = match app_matches.subcommand() {
@@ -68,4 +87,39 @@ mod test {
 
         testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
     }
+
+    #[test]
+    fn running_container_should_need_force() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        spec.process.as_mut().unwrap().args = vec!["/bin/sleep".into(), "10".into()];
+
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        assert!(container.create().is_ok());
+        assert!(container.start().is_ok());
+
+        let subcommand = DeleteCommand {
+            container_id: container_id.clone(),
+            root: testutil::TEST_ROOT.into(),
+            force: false,
+        };
+        assert!(subcommand.run().is_err());
+        assert!(meta_dir.exists());
+
+        let subcommand = DeleteCommand {
+            container_id: container_id.clone(),
+            root: testutil::TEST_ROOT.into(),
+            force: true,
+        };
+        assert!(subcommand.run().is_ok());
+        assert!(!meta_dir.exists());
+
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
 }
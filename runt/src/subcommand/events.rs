@@ -0,0 +1,265 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use clap::ArgMatches;
+use serde_derive::Serialize;
+
+use crate::config;
+use crate::container::specs::Status;
+use crate::container::stats::Stats;
+use crate::container::{Container, MetadataManager};
+use crate::subcommand::SubCommandImpl;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct EventsCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+    pub interval: Duration,
+    pub stats: bool,
+}
+
+/// An event in the format printed by `runc events`.
+#[derive(Serialize, Debug)]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Stats>,
+}
+
+impl EventsCommand {
+    fn stats_event(&self, container: &Container) -> Result<Event> {
+        Ok(Event {
+            type_: "stats".into(),
+            id: self.container_id.clone(),
+            data: Some(container.cgroup()?.stats()?),
+        })
+    }
+
+    fn oom_event(&self) -> Event {
+        Event {
+            type_: "oom".into(),
+            id: self.container_id.clone(),
+            data: None,
+        }
+    }
+
+    fn is_stopped(&self) -> Result<bool> {
+        let mut container = Container::load(&self.root, &self.container_id)?;
+        container.refresh_status()?;
+        Ok(container.status == Status::Stopped)
+    }
+
+    /// Print stats every interval and OOM events as they happen until the
+    /// container stops.
+    fn watch(&self, container: &Container) -> Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        container.cgroup()?.notify_oom(sender)?;
+
+        let mut oom_watched = true;
+        let mut next = Instant::now();
+        loop {
+            let now = Instant::now();
+            if now >= next {
+                if self.is_stopped()? {
+                    return Ok(());
+                }
+                print_event(&self.stats_event(container)?)?;
+                next += self.interval;
+                continue;
+            }
+
+            if !oom_watched {
+                thread::sleep(next - now);
+                continue;
+            }
+            match receiver.recv_timeout(next - now) {
+                Ok(()) => print_event(&self.oom_event())?,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => oom_watched = false,
+            }
+        }
+    }
+}
+
+fn print_event(event: &Event) -> Result<()> {
+    println!("{}", serde_json::to_string(event)?);
+    Ok(())
+}
+
+/// Parse durations such as `5s`, `500ms`, `1m` or `1h`. Bare numbers are seconds.
+fn parse_duration(duration: &str) -> Result<Duration> {
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (value, unit) = duration.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| anyhow!("invalid duration: {}", duration))?;
+    let duration = match unit {
+        "ms" => Duration::from_millis(value),
+        "" | "s" => Duration::from_secs(value),
+        "m" => Duration::from_secs(value * 60),
+        "h" => Duration::from_secs(value * 60 * 60),
+        _ => bail!("invalid duration: {}", duration),
+    };
+    if duration == Duration::from_secs(0) {
+        bail!("duration must be greater than 0");
+    }
+    Ok(duration)
+}
+
+impl SubCommandImpl for EventsCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches
+            .value_of("container-id")
+            .expect("container-id must be specify:");
+        let interval = match matches.value_of("interval") {
+            Some(interval) => parse_duration(interval)?,
+            None => DEFAULT_INTERVAL,
+        };
+
+        Ok(EventsCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+            interval,
+            stats: matches.is_present("stats"),
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        let container = Container::load(&self.root, &self.container_id)?;
+        if self.is_stopped()? {
+            bail!("container {} is not running", self.container_id);
+        }
+        if self.stats {
+            return print_event(&self.stats_event(&container)?);
+        }
+        self.watch(&container)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use uuid::Uuid;
+
+    use crate::cli::app_config;
+    use crate::container::testutil;
+    use crate::specutil;
+
+    fn init_events_command(args: Vec<&str>) -> Result<EventsCommand> {
+        let app_matches = app_config()
+            .get_matches_from_safe(testutil::args_with_root(&args))
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+
+        match app_matches.subcommand() {
+            ("events", Some(matches)) => Some(EventsCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+    }
+
+    fn init_running_container(container_id: &str, bundle: &Path) -> Container {
+        let rootfs = testutil::init_rootfs_dir(bundle).unwrap();
+        testutil::init_spec_file(bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(bundle).unwrap();
+        spec.process.as_mut().unwrap().args = vec!["/bin/sleep".into(), "10".into()];
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), container_id, bundle, spec);
+        container.create().unwrap();
+        container.start().unwrap();
+        container
+    }
+
+    #[test]
+    fn duration_should_be_parsed() {
+        assert_eq!(parse_duration("5s").unwrap(), Duration::from_secs(5));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("3").unwrap(), Duration::from_secs(3));
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("5 s").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+
+    #[test]
+    fn flags_should_be_parsed() {
+        let events_command = init_events_command(vec!["runt", "events", "container-a"]).unwrap();
+        assert_eq!(events_command.interval, DEFAULT_INTERVAL);
+        assert!(!events_command.stats);
+
+        let events_command = init_events_command(vec![
+            "runt",
+            "events",
+            "--interval",
+            "1s",
+            "--stats",
+            "container-a",
+        ])
+        .unwrap();
+        assert_eq!(events_command.interval, Duration::from_secs(1));
+        assert!(events_command.stats);
+    }
+
+    #[test]
+    fn stats_event_should_be_collected() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+        let container = init_running_container(&container_id, &bundle);
+
+        let events_command =
+            init_events_command(vec!["runt", "events", "--stats", &container_id]).unwrap();
+        let event = events_command.stats_event(&container).unwrap();
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "stats");
+        assert_eq!(json["id"], container_id.as_str());
+        assert_eq!(json["data"]["pids"]["current"], 1);
+        assert!(json["data"]["memory"]["usage"]["usage"].as_u64().unwrap() > 0);
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn oom_should_be_notified() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+        let container = init_running_container(&container_id, &bundle);
+        let cgroup = container.cgroup().unwrap();
+        let memory = cgroup.subsystem_path("memory").unwrap().unwrap();
+        let limit = match fs::metadata(memory.join("memory.max")) {
+            Ok(_) => memory.join("memory.max"),
+            Err(_) => memory.join("memory.limit_in_bytes"),
+        };
+        fs::write(limit, (16 * 1024 * 1024).to_string()).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        cgroup.notify_oom(sender).unwrap();
+        // The string doubles until it no longer fits.
+        let mut process = container.spec.process.clone().unwrap();
+        process.args = vec![
+            "/bin/sh".into(),
+            "-c".into(),
+            "a=x; while :; do a=$a$a; done".into(),
+        ];
+        let pid = container.exec(&process, None).unwrap();
+
+        assert!(receiver.recv_timeout(Duration::from_secs(10)).is_ok());
+        nix::sys::wait::waitpid(pid, None).ok();
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+}
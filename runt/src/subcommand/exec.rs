@@ -0,0 +1,353 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::path::PathBuf;
+use std::thread;
+
+use anyhow::{bail, Result};
+use clap::ArgMatches;
+use nix::pty::{openpty, OpenptyResult, Winsize};
+use nix::sys::signal::SigSet;
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg, Termios};
+use nix::unistd::{close, isatty};
+
+use crate::config;
+use crate::container::specs::Process;
+use crate::container::{self, Container, MetadataManager};
+use crate::subcommand::SubCommandImpl;
+
+pub struct ExecCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+    pub command: Vec<String>,
+    pub process: Option<PathBuf>,
+    pub tty: bool,
+    pub detach: bool,
+    pub pid_file: Option<PathBuf>,
+}
+
+impl ExecCommand {
+    /// The process from `--process`, or the container process running `command`.
+    fn load_process(&self, container: &Container) -> Result<Process> {
+        let mut process = match &self.process {
+            Some(path) => serde_json::from_reader(File::open(path)?)?,
+            None => {
+                let mut process = container
+                    .spec
+                    .process
+                    .clone()
+                    .expect("process must be set: ");
+                process.args = self.command.clone();
+                process
+            }
+        };
+        if self.tty {
+            process.terminal = Some(true);
+        }
+        if process.args.is_empty() {
+            bail!("process args must not be empty");
+        }
+        Ok(process)
+    }
+
+    /// Start the process. Unless detached, wait for it and return its exit code.
+    fn execute(&self) -> Result<Option<i32>> {
+        let mut container = Container::load(&self.root, &self.container_id)?;
+        // A dead init's pid may already belong to someone else.
+        container.refresh_status()?;
+        let process = self.load_process(&container)?;
+        let tty = process.terminal.unwrap_or(false);
+        if tty && self.detach {
+            bail!("cannot allocate a tty for a detached process");
+        }
+
+        if !self.detach {
+            // The exec'd process is orphaned by the intermediate fork which
+            // joins the namespaces; become its parent so it can be waited for.
+            container::set_child_subreaper()?;
+        }
+
+        let console = if tty { Some(open_console()?) } else { None };
+        let pid = container.exec(&process, console.as_ref().map(|pty| pty.slave))?;
+
+        if let Some(pid_file) = &self.pid_file {
+            fs::write(pid_file, pid.to_string())?;
+        }
+        if self.detach {
+            return Ok(None);
+        }
+
+        let raw_mode = match console {
+            Some(pty) => {
+                close(pty.slave)?;
+                copy_console(pty.master);
+                RawMode::enable()?
+            }
+            None => None,
+        };
+        let exit_code = container::wait_forwarding_signals(pid);
+        if let Some(raw_mode) = raw_mode {
+            raw_mode.restore()?;
+        }
+        Ok(Some(exit_code?))
+    }
+}
+
+fn open_console() -> Result<OpenptyResult> {
+    let mut winsize: Winsize = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::ioctl(libc::STDIN_FILENO, libc::TIOCGWINSZ, &mut winsize) };
+    let winsize = if ret == 0 { Some(&winsize) } else { None };
+    Ok(openpty(winsize, None)?)
+}
+
+/// Proxy stdin and stdout to the pty master until the process exits.
+fn copy_console(master: RawFd) {
+    let spawn_copy = |mut from: Box<dyn Read + Send>, mut to: Box<dyn Write + Send>| {
+        thread::spawn(move || {
+            // Leave signals to the thread forwarding them to the process.
+            let _ = SigSet::all().thread_block();
+            let _ = io::copy(&mut from, &mut to);
+        });
+    };
+    let master_in = unsafe { File::from_raw_fd(master) };
+    let master_out = master_in.try_clone().expect("failed to dup pty master: ");
+    spawn_copy(Box::new(io::stdin()), Box::new(master_in));
+    spawn_copy(Box::new(master_out), Box::new(io::stdout()));
+}
+
+/// Puts the terminal on stdin into raw mode while attached to the process.
+struct RawMode {
+    original: Termios,
+}
+
+impl RawMode {
+    fn enable() -> Result<Option<Self>> {
+        if !isatty(libc::STDIN_FILENO)? {
+            return Ok(None);
+        }
+        let original = tcgetattr(libc::STDIN_FILENO)?;
+        let mut raw = original.clone();
+        cfmakeraw(&mut raw);
+        tcsetattr(libc::STDIN_FILENO, SetArg::TCSANOW, &raw)?;
+        Ok(Some(RawMode { original }))
+    }
+
+    fn restore(self) -> Result<()> {
+        tcsetattr(libc::STDIN_FILENO, SetArg::TCSANOW, &self.original)?;
+        Ok(())
+    }
+}
+
+impl SubCommandImpl for ExecCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches
+            .value_of("container-id")
+            .expect("container-id must be specify:");
+        let command: Vec<String> = matches
+            .values_of("command")
+            .map(|values| values.map(String::from).collect())
+            .unwrap_or_default();
+        let process = matches.value_of("process").map(PathBuf::from);
+        if command.is_empty() && process.is_none() {
+            bail!("either a command or --process must be specified");
+        }
+
+        Ok(ExecCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+            command,
+            process,
+            tty: matches.is_present("tty"),
+            detach: matches.is_present("detach"),
+            pid_file: matches.value_of("pid-file").map(PathBuf::from),
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        if let Some(exit_code) = self.execute()? {
+            std::process::exit(exit_code);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::Path;
+
+    use uuid::Uuid;
+
+    use crate::cli::app_config;
+    use crate::container::testutil;
+    use crate::specutil;
+
+    fn init_exec_command(args: Vec<&str>) -> Result<ExecCommand> {
+        let app_matches = app_config()
+            .get_matches_from_safe(testutil::args_with_root(&args))
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+
+        match app_matches.subcommand() {
+            ("exec", Some(matches)) => Some(ExecCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+    }
+
+    fn init_running_container(container_id: &str, bundle: &Path) -> Container {
+        let rootfs = testutil::init_rootfs_dir(bundle).unwrap();
+        testutil::init_spec_file(bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(bundle).unwrap();
+        spec.process.as_mut().unwrap().args = vec!["/bin/sleep".into(), "10".into()];
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), container_id, bundle, spec);
+        container.create().unwrap();
+        container.start().unwrap();
+        container
+    }
+
+    #[test]
+    fn command_should_be_parsed() {
+        let exec_command = init_exec_command(vec![
+            "runt",
+            "exec",
+            "--pid-file",
+            "/tmp/exec.pid",
+            "-d",
+            "container-a",
+            "--",
+            "ls",
+            "-l",
+        ])
+        .unwrap();
+
+        assert_eq!(exec_command.container_id, "container-a");
+        assert_eq!(exec_command.command, vec!["ls", "-l"]);
+        assert_eq!(exec_command.process, None);
+        assert!(exec_command.detach);
+        assert!(!exec_command.tty);
+        assert_eq!(exec_command.pid_file, Some(PathBuf::from("/tmp/exec.pid")));
+    }
+
+    #[test]
+    fn command_or_process_must_be_specified() {
+        assert!(init_exec_command(vec!["runt", "exec", "container-a"]).is_err());
+        assert!(
+            init_exec_command(vec!["runt", "exec", "-p", "process.json", "container-a"]).is_ok()
+        );
+    }
+
+    #[test]
+    fn exit_code_of_command_should_be_returned() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+        let container = init_running_container(&container_id, &bundle);
+
+        let exec_command = init_exec_command(vec![
+            "runt",
+            "exec",
+            &container_id,
+            "/bin/sh",
+            "-c",
+            "exit 7",
+        ])
+        .unwrap();
+        assert_eq!(exec_command.execute().unwrap(), Some(7));
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn process_file_should_be_applied() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+        let container = init_running_container(&container_id, &bundle);
+
+        let mut process = container.spec.process.clone().unwrap();
+        process.args = vec![
+            "sh".into(),
+            "-c".into(),
+            r#"test "$FOO" = bar && test "$(pwd)" = /tmp"#.into(),
+        ];
+        process.env = Some(vec!["PATH=/usr/bin:/bin".into(), "FOO=bar".into()]);
+        process.cwd = "/tmp".into();
+        let process_file = bundle.join("process.json");
+        fs::write(&process_file, serde_json::to_string(&process).unwrap()).unwrap();
+
+        let exec_command = init_exec_command(vec![
+            "runt",
+            "exec",
+            "--process",
+            process_file.to_str().unwrap(),
+            &container_id,
+        ])
+        .unwrap();
+        assert_eq!(exec_command.execute().unwrap(), Some(0));
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn stopped_container_should_be_rejected() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+        container::set_child_subreaper().unwrap();
+        let mut container = init_running_container(&container_id, &bundle);
+        container.kill(libc::SIGKILL, false).unwrap();
+        container.wait().unwrap();
+
+        let exec_command =
+            init_exec_command(vec!["runt", "exec", "-d", &container_id, "/bin/true"]).unwrap();
+        assert!(exec_command.execute().is_err());
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn reused_init_pid_should_be_rejected() {
+        use std::process::Command;
+
+        use nix::unistd::Pid;
+
+        use crate::container::specs::Status;
+
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        spec.process.as_mut().unwrap().terminal = Some(false);
+        // An unrelated process has taken the pid of the dead init.
+        let mut other = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = Pid::from_raw(other.id() as i32);
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.status = Status::Running;
+        container.pid = Some(pid.as_raw());
+        container.start_time = Some(container::start_time(pid).unwrap() + 1);
+        container.save_metadata(&container).unwrap();
+
+        let exec_command =
+            init_exec_command(vec!["runt", "exec", "-d", &container_id, "/bin/true"]).unwrap();
+        let err = exec_command.execute().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot exec in a container in stopped status"
+        );
+        let container = Container::load(testutil::TEST_ROOT.as_ref(), &container_id).unwrap();
+        assert_eq!(container.status, Status::Stopped);
+
+        other.kill().unwrap();
+        other.wait().unwrap();
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use clap::ArgMatches;
+use libc::c_int;
+use nix::sys::signal::Signal;
+
+use crate::config;
+use crate::container::{Container, MetadataManager};
+use crate::subcommand::SubCommandImpl;
+
+pub struct KillCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+    pub signal: c_int,
+    pub all: bool,
+}
+
+/// Accept `SIGTERM`, `TERM` and `15` style signals. Realtime signals, which
+/// nix has no `Signal` for, are given as numbers or as `SIGRTMIN+n` and
+/// `SIGRTMAX-n`.
+fn parse_signal(signal: &str) -> Result<c_int> {
+    let unknown = || anyhow!("unknown signal: {}", signal);
+    let number = match signal.parse::<c_int>() {
+        Ok(number) => number,
+        Err(_) => {
+            let name = signal.to_uppercase();
+            let name = if name.starts_with("SIG") {
+                name
+            } else {
+                format!("SIG{}", name)
+            };
+            let offset = |rest: &str, sign: char| match rest {
+                "" => Some(0),
+                rest => rest.strip_prefix(sign)?.parse::<c_int>().ok(),
+            };
+            if let Some(rest) = name.strip_prefix("SIGRTMIN") {
+                libc::SIGRTMIN() + offset(rest, '+').ok_or_else(unknown)?
+            } else if let Some(rest) = name.strip_prefix("SIGRTMAX") {
+                libc::SIGRTMAX() - offset(rest, '-').ok_or_else(unknown)?
+            } else {
+                Signal::from_str(&name).map_err(|_| unknown())? as c_int
+            }
+        }
+    };
+    if !(1..=libc::SIGRTMAX()).contains(&number) {
+        return Err(unknown());
+    }
+    Ok(number)
+}
+
+impl SubCommandImpl for KillCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches
+            .value_of("container-id")
+            .expect("container-id must be specify:");
+        let signal = parse_signal(matches.value_of("signal").expect("signal must be specify:"))?;
+
+        Ok(KillCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+            signal,
+            all: matches.is_present("all"),
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        let mut container = Container::load(&self.root, &self.container_id)?;
+        // A dead init's pid may already belong to someone else.
+        container.refresh_status()?;
+        container.kill(self.signal, self.all)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use uuid::Uuid;
+
+    use crate::cli::app_config;
+    use crate::container::specs::Status;
+    use crate::container::testutil;
+    use crate::specutil;
+
+    fn init_kill_command(args: Vec<&str>) -> Result<KillCommand> {
+        let app_matches = app_config()
+            .get_matches_from_safe(testutil::args_with_root(&args))
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+
+        match app_matches.subcommand() {
+            ("kill", Some(matches)) => Some(KillCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+    }
+
+    #[test]
+    fn signal_should_be_parsed_in_every_form() {
+        assert_eq!(parse_signal("SIGTERM").unwrap(), libc::SIGTERM);
+        assert_eq!(parse_signal("TERM").unwrap(), libc::SIGTERM);
+        assert_eq!(parse_signal("term").unwrap(), libc::SIGTERM);
+        assert_eq!(parse_signal("15").unwrap(), libc::SIGTERM);
+        assert_eq!(parse_signal("9").unwrap(), libc::SIGKILL);
+    }
+
+    #[test]
+    fn realtime_signals_should_be_parsed() {
+        assert_eq!(parse_signal("34").unwrap(), 34);
+        assert_eq!(parse_signal("SIGRTMIN").unwrap(), libc::SIGRTMIN());
+        assert_eq!(parse_signal("RTMIN+3").unwrap(), libc::SIGRTMIN() + 3);
+        assert_eq!(parse_signal("SIGRTMAX-1").unwrap(), libc::SIGRTMAX() - 1);
+        assert!(parse_signal("SIGRTMIN-1").is_err());
+        assert!(parse_signal("SIGRTMAX+1").is_err());
+        assert!(parse_signal("SIGRTMIN+x").is_err());
+    }
+
+    #[test]
+    fn unknown_signal_should_be_rejected() {
+        assert!(parse_signal("SIGFOO").is_err());
+        assert!(parse_signal("1000").is_err());
+        assert!(parse_signal("0").is_err());
+        assert!(init_kill_command(vec!["runt", "kill", "container-a", "FOO"]).is_err());
+    }
+
+    #[test]
+    fn all_flag_should_be_parsed() {
+        let kill_command =
+            init_kill_command(vec!["runt", "kill", "--all", "container-a", "KILL"]).unwrap();
+
+        assert_eq!(kill_command.container_id, "container-a");
+        assert_eq!(kill_command.signal, libc::SIGKILL);
+        assert!(kill_command.all);
+    }
+
+    #[test]
+    fn created_container_should_be_killed() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let spec = specutil::load(&bundle).unwrap();
+
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        assert!(container.create().is_ok());
+
+        let kill_command =
+            init_kill_command(vec!["runt", "kill", "--all", &container_id, "KILL"]).unwrap();
+        assert!(kill_command.run().is_ok());
+        // The init dies asynchronously, and may linger as a zombie.
+        let start = Instant::now();
+        loop {
+            container.refresh_status().unwrap();
+            if container.status == Status::Stopped {
+                break;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "init is alive");
+            thread::sleep(Duration::from_millis(10));
+        }
+        // A stale state still saying created must not get the pid signalled.
+        container.status = Status::Created;
+        container.save_metadata(&container).unwrap();
+        assert!(kill_command.run().is_err());
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn stopped_container_should_not_be_killed() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let spec = specutil::load(&bundle).unwrap();
+
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        crate::container::set_child_subreaper().unwrap();
+        assert!(container.create().is_ok());
+        assert!(container.start().is_ok());
+        assert!(container.wait().is_ok());
+        assert_eq!(container.status, Status::Stopped);
+
+        let kill_command = init_kill_command(vec!["runt", "kill", &container_id, "TERM"]).unwrap();
+        assert!(kill_command.run().is_err());
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use chrono::SecondsFormat;
+use clap::ArgMatches;
+use nix::unistd::{Uid, User};
+
+use crate::config;
+use crate::container::specs::State;
+use crate::container::{Container, MetadataManager};
+use crate::subcommand::SubCommandImpl;
+
+#[derive(Debug, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+pub struct ListCommand {
+    pub root: PathBuf,
+    pub format: Format,
+    pub quiet: bool,
+}
+
+impl ListCommand {
+    /// States of every container, skipping the ones which cannot be loaded.
+    fn states(&self) -> Result<Vec<State>> {
+        let mut states = Vec::new();
+        for id in Container::ids(&self.root)? {
+            match load_state(&self.root, &id) {
+                Ok(state) => states.push(state),
+                Err(e) => eprintln!("failed to load container {}: {:#}", id, e),
+            }
+        }
+        Ok(states)
+    }
+}
+
+fn load_state(root: &Path, container_id: &str) -> Result<State> {
+    let mut container = Container::load(root, container_id)?;
+    container.refresh_status()?;
+    let mut state = container.state()?;
+    // The container belongs to whoever created its state directory.
+    let uid = fs::metadata(container.state_dir())?.uid();
+    state.owner = match User::from_uid(Uid::from_raw(uid))? {
+        Some(user) => user.name,
+        None => format!("#{}", uid),
+    };
+    Ok(state)
+}
+
+fn format_table(states: &[State]) -> String {
+    let header = ["ID", "PID", "STATUS", "BUNDLE", "CREATED", "OWNER"];
+    let rows: Vec<Vec<String>> = states
+        .iter()
+        .map(|state| {
+            vec![
+                state.id.clone(),
+                state.pid.unwrap_or(0).to_string(),
+                state.status.to_string(),
+                state.bundle.display().to_string(),
+                state
+                    .created
+                    .map(|created| created.to_rfc3339_opts(SecondsFormat::Nanos, true))
+                    .unwrap_or_default(),
+                state.owner.clone(),
+            ]
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = header.iter().map(|column| column.len()).collect();
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.len());
+        }
+    }
+
+    let header = header.iter().map(|column| column.to_string()).collect();
+    let mut table = String::new();
+    for row in std::iter::once(header).chain(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(column, width)| format!("{:width$}", column, width = width))
+            .collect();
+        table.push_str(line.join("   ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+impl SubCommandImpl for ListCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let format = match matches.value_of("format").unwrap_or("table") {
+            "table" => Format::Table,
+            "json" => Format::Json,
+            format => bail!("unknown format: {}", format),
+        };
+
+        Ok(ListCommand {
+            root: config::state_root(matches.value_of("root"))?,
+            format,
+            quiet: matches.is_present("quiet"),
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        let states = self.states()?;
+        if self.quiet {
+            for state in &states {
+                println!("{}", state.id);
+            }
+            return Ok(());
+        }
+        match self.format {
+            Format::Table => print!("{}", format_table(&states)),
+            Format::Json => println!("{}", serde_json::to_string(&states)?),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    use crate::cli::app_config;
+    use crate::container::specs::Status;
+    use crate::container::testutil;
+    use crate::specutil;
+
+    fn init_list_command(args: Vec<&str>) -> Result<ListCommand> {
+        let app_matches = app_config()
+            .get_matches_from_safe(testutil::args_with_root(&args))
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+
+        match app_matches.subcommand() {
+            ("list", Some(matches)) => Some(ListCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+    }
+
+    #[test]
+    fn flags_should_be_parsed() {
+        let list_command = init_list_command(vec!["runt", "list"]).unwrap();
+        assert_eq!(list_command.format, Format::Table);
+        assert!(!list_command.quiet);
+
+        let list_command = init_list_command(vec!["runt", "list", "-f", "json", "-q"]).unwrap();
+        assert_eq!(list_command.format, Format::Json);
+        assert!(list_command.quiet);
+    }
+
+    #[test]
+    fn table_should_be_aligned() {
+        let state = State {
+            oci_version: "1.0.2".into(),
+            id: "container-a".into(),
+            status: Status::Running,
+            pid: Some(42),
+            bundle: PathBuf::from("/bundle"),
+            rootfs: PathBuf::from("/bundle/rootfs"),
+            owner: "root".into(),
+            annotation: None,
+            created: Some(Utc.ymd(2020, 1, 2).and_hms(3, 4, 5)),
+        };
+
+        assert_eq!(
+            format_table(&[state]),
+            "ID            PID   STATUS    BUNDLE    CREATED                          OWNER\n\
+             container-a   42    running   /bundle   2020-01-02T03:04:05.000000000Z   root\n"
+        );
+    }
+
+    #[test]
+    fn broken_and_dead_containers_should_be_listed() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let spec = specutil::load(&bundle).unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        // Pretend the init was killed without runt noticing.
+        container.status = Status::Running;
+        container.pid = Some(i32::MAX);
+        container.save_metadata(&container).unwrap();
+
+        let broken_dir = PathBuf::from(testutil::TEST_ROOT).join(Uuid::new_v4().to_string());
+        fs::create_dir_all(&broken_dir).unwrap();
+
+        let list_command = init_list_command(vec!["runt", "list"]).unwrap();
+        let states = list_command.states().unwrap();
+        let state = states.iter().find(|s| s.id == container_id).unwrap();
+        assert_eq!(state.status, Status::Stopped);
+        assert_eq!(
+            Container::load(testutil::TEST_ROOT.as_ref(), &container_id)
+                .unwrap()
+                .status,
+            Status::Stopped
+        );
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir, &broken_dir]).unwrap();
+    }
+}
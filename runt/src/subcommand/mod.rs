@@ -1,21 +1,57 @@
+pub mod checkpoint;
 pub mod create;
 pub mod delete;
+pub mod events;
+pub mod exec;
+pub mod kill;
+pub mod list;
+pub mod pause;
+pub mod ps;
+pub mod restore;
+pub mod resume;
+pub mod run;
 pub mod spec;
+pub mod start;
 pub mod state;
+pub mod update;
 
 use anyhow::Result;
 use clap::ArgMatches;
 
+use crate::subcommand::checkpoint::CheckpointCommand;
 use crate::subcommand::create::CreateCommand;
 use crate::subcommand::delete::DeleteCommand;
+use crate::subcommand::events::EventsCommand;
+use crate::subcommand::exec::ExecCommand;
+use crate::subcommand::kill::KillCommand;
+use crate::subcommand::list::ListCommand;
+use crate::subcommand::pause::PauseCommand;
+use crate::subcommand::ps::PsCommand;
+use crate::subcommand::restore::RestoreCommand;
+use crate::subcommand::resume::ResumeCommand;
+use crate::subcommand::run::RunCommand;
 use crate::subcommand::spec::SpecCommand;
+use crate::subcommand::start::StartCommand;
 use crate::subcommand::state::StateCommand;
+use crate::subcommand::update::UpdateCommand;
 
 pub enum SubCommand {
     Create(CreateCommand),
+    Start(StartCommand),
+    Run(RunCommand),
     Spec(SpecCommand),
     State(StateCommand),
     Delete(DeleteCommand),
+    Kill(KillCommand),
+    Exec(ExecCommand),
+    List(ListCommand),
+    Ps(PsCommand),
+    Pause(PauseCommand),
+    Resume(ResumeCommand),
+    Events(EventsCommand),
+    Update(Box<UpdateCommand>),
+    Checkpoint(CheckpointCommand),
+    Restore(RestoreCommand),
 }
 
 pub trait SubCommandImpl: Sized {
@@ -0,0 +1,174 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ArgMatches;
+
+use crate::config;
+use crate::container::{Container, MetadataManager};
+use crate::subcommand::SubCommandImpl;
+
+pub struct PauseCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+}
+
+impl SubCommandImpl for PauseCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches
+            .value_of("container-id")
+            .expect("container-id must be specify:");
+
+        Ok(PauseCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        let mut container = Container::load(&self.root, &self.container_id)?;
+        container.refresh_status()?;
+        container.pause()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use uuid::Uuid;
+
+    use crate::container::cgroups::CgroupVersion;
+    use crate::container::specs::Status;
+    use crate::container::testutil;
+    use crate::specutil;
+    use crate::subcommand::delete::DeleteCommand;
+    use crate::subcommand::resume::ResumeCommand;
+
+    /// Whether the freezer reports the container cgroup frozen.
+    fn frozen(container: &Container) -> bool {
+        let cgroup = container.cgroup().unwrap();
+        let path = cgroup
+            .subsystem_path("freezer")
+            .unwrap()
+            .expect("no cgroup freezer");
+        match cgroup.version() {
+            CgroupVersion::V1 => {
+                let state = fs::read_to_string(path.join("freezer.state")).unwrap();
+                state.trim() == "FROZEN"
+            }
+            CgroupVersion::V2 => {
+                let events = fs::read_to_string(path.join("cgroup.events")).unwrap();
+                events.lines().any(|line| line == "frozen 1")
+            }
+        }
+    }
+
+    #[test]
+    fn container_should_be_paused_and_resumed() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        spec.process.as_mut().unwrap().args = vec!["/bin/sleep".into(), "10".into()];
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        container.start().unwrap();
+
+        let pause_command = PauseCommand {
+            container_id: container_id.clone(),
+            root: testutil::TEST_ROOT.into(),
+        };
+        assert!(pause_command.run().is_ok());
+        assert_eq!(
+            Container::load(testutil::TEST_ROOT.as_ref(), &container_id)
+                .unwrap()
+                .status,
+            Status::Paused
+        );
+        assert!(frozen(&container));
+        assert!(pause_command.run().is_err());
+
+        let resume_command = ResumeCommand {
+            container_id: container_id.clone(),
+            root: testutil::TEST_ROOT.into(),
+        };
+        assert!(resume_command.run().is_ok());
+        assert_eq!(
+            Container::load(testutil::TEST_ROOT.as_ref(), &container_id)
+                .unwrap()
+                .status,
+            Status::Running
+        );
+        assert!(!frozen(&container));
+        assert!(resume_command.run().is_err());
+
+        assert!(pause_command.run().is_ok());
+        let delete_command = DeleteCommand {
+            container_id: container_id.clone(),
+            root: testutil::TEST_ROOT.into(),
+            force: false,
+        };
+        assert!(delete_command.run().is_err());
+        let delete_command = DeleteCommand {
+            container_id: container_id.clone(),
+            root: testutil::TEST_ROOT.into(),
+            force: true,
+        };
+        assert!(delete_command.run().is_ok());
+
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn container_with_dead_init_should_not_be_paused() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        spec.process.as_mut().unwrap().args = vec!["/bin/true".into()];
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        container.start().unwrap();
+        let start = Instant::now();
+        loop {
+            container.refresh_status().unwrap();
+            if container.status == Status::Stopped {
+                break;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "init is alive");
+            thread::sleep(Duration::from_millis(10));
+        }
+        // The state still says running, but the init is gone.
+        container.status = Status::Running;
+        container.save_metadata(&container).unwrap();
+
+        let pause_command = PauseCommand {
+            container_id: container_id.clone(),
+            root: testutil::TEST_ROOT.into(),
+        };
+        assert!(pause_command.run().is_err());
+        assert_eq!(
+            Container::load(testutil::TEST_ROOT.as_ref(), &container_id)
+                .unwrap()
+                .status,
+            Status::Stopped
+        );
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{anyhow, bail, Result};
+use clap::ArgMatches;
+use nix::unistd::Pid;
+use serde_derive::Serialize;
+
+use crate::config;
+use crate::container::{Container, MetadataManager};
+use crate::subcommand::list::Format;
+use crate::subcommand::SubCommandImpl;
+
+const DEFAULT_PS_OPTIONS: &[&str] = &["-ef"];
+
+pub struct PsCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+    pub format: Format,
+    pub ps_options: Vec<String>,
+}
+
+/// A process of the container as seen from the host and from inside.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerProcess {
+    pub pid: i32,
+    pub container_pid: i32,
+}
+
+impl PsCommand {
+    fn processes(&self) -> Result<Vec<ContainerProcess>> {
+        let container = Container::load(&self.root, &self.container_id)?;
+        let mut processes = Vec::new();
+        for pid in container.cgroup()?.pids()? {
+            // Skip processes which exited after the cgroup was read.
+            if let Ok(container_pid) = container_pid(pid) {
+                processes.push(ContainerProcess {
+                    pid: pid.as_raw(),
+                    container_pid,
+                });
+            }
+        }
+        Ok(processes)
+    }
+
+    /// Run ps(1) and keep the rows of the container processes, prefixed
+    /// with their pid inside the container.
+    fn table(&self, processes: &[ContainerProcess]) -> Result<String> {
+        let mut ps = Command::new("ps");
+        if self.ps_options.is_empty() {
+            ps.args(DEFAULT_PS_OPTIONS);
+        } else {
+            ps.args(&self.ps_options);
+        }
+        let output = ps.output()?;
+        if !output.status.success() {
+            bail!(
+                "ps failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let output = String::from_utf8(output.stdout)?;
+
+        let mut lines = output.lines();
+        let header = lines
+            .next()
+            .ok_or_else(|| anyhow!("ps printed no header"))?;
+        let pid_column = header
+            .split_whitespace()
+            .position(|column| column == "PID")
+            .ok_or_else(|| anyhow!("ps output has no PID column"))?;
+
+        let container_pids: HashMap<i32, i32> = processes
+            .iter()
+            .map(|process| (process.pid, process.container_pid))
+            .collect();
+        let mut table = format!("{:>6} {}\n", "CPID", header);
+        for line in lines {
+            let pid = line
+                .split_whitespace()
+                .nth(pid_column)
+                .and_then(|pid| pid.parse().ok());
+            if let Some(container_pid) = pid.and_then(|pid| container_pids.get(&pid)) {
+                table.push_str(&format!("{:>6} {}\n", container_pid, line));
+            }
+        }
+        Ok(table)
+    }
+}
+
+/// The pid of `pid` in the innermost pid namespace it belongs to.
+fn container_pid(pid: Pid) -> Result<i32> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid))?;
+    let nspid = status
+        .lines()
+        .find(|line| line.starts_with("NSpid:"))
+        .and_then(|line| line.split_whitespace().last())
+        .ok_or_else(|| anyhow!("no NSpid in status of {}", pid))?;
+    Ok(nspid.parse()?)
+}
+
+impl SubCommandImpl for PsCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches
+            .value_of("container-id")
+            .expect("container-id must be specify:");
+        let format = match matches.value_of("format").unwrap_or("table") {
+            "table" => Format::Table,
+            "json" => Format::Json,
+            format => bail!("unknown format: {}", format),
+        };
+        let ps_options = matches
+            .values_of("ps-options")
+            .map(|values| values.map(String::from).collect())
+            .unwrap_or_default();
+
+        Ok(PsCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+            format,
+            ps_options,
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        let processes = self.processes()?;
+        match self.format {
+            Format::Table => print!("{}", self.table(&processes)?),
+            Format::Json => {
+                if !self.ps_options.is_empty() {
+                    bail!("ps options cannot be used with --format json");
+                }
+                println!("{}", serde_json::to_string(&processes)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use crate::cli::app_config;
+    use crate::container::testutil;
+    use crate::specutil;
+
+    fn init_ps_command(args: Vec<&str>) -> Result<PsCommand> {
+        let app_matches = app_config()
+            .get_matches_from_safe(testutil::args_with_root(&args))
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+
+        match app_matches.subcommand() {
+            ("ps", Some(matches)) => Some(PsCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+    }
+
+    #[test]
+    fn options_should_be_parsed() {
+        let ps_command = init_ps_command(vec!["runt", "ps", "container-a"]).unwrap();
+        assert_eq!(ps_command.format, Format::Table);
+        assert!(ps_command.ps_options.is_empty());
+
+        let ps_command =
+            init_ps_command(vec!["runt", "ps", "container-a", "-o", "pid,comm"]).unwrap();
+        assert_eq!(ps_command.container_id, "container-a");
+        assert_eq!(ps_command.ps_options, vec!["-o", "pid,comm"]);
+    }
+
+    #[test]
+    fn container_processes_should_be_listed() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        spec.process.as_mut().unwrap().args = vec!["/bin/sleep".into(), "10".into()];
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        container.start().unwrap();
+        let pid = container.pid.unwrap();
+
+        let ps_command =
+            init_ps_command(vec!["runt", "ps", &container_id, "-o", "pid,comm"]).unwrap();
+        let processes = ps_command.processes().unwrap();
+        assert_eq!(processes.len(), 1);
+        assert_eq!(processes[0].pid, pid);
+
+        let table = ps_command.table(&processes).unwrap();
+        let rows: Vec<&str> = table.lines().collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].contains("CPID"));
+        assert!(rows[1].contains(&pid.to_string()));
+        assert!(rows[1].ends_with("sleep"));
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ArgMatches;
+
+use crate::config;
+use crate::container::checkpoint::CheckpointOptions;
+use crate::container::{self, Container};
+use crate::specutil;
+use crate::subcommand::checkpoint::checkpoint_options;
+use crate::subcommand::SubCommandImpl;
+
+pub struct RestoreCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+    pub bundle: PathBuf,
+    pub pid_file: Option<PathBuf>,
+    pub detach: bool,
+    pub options: CheckpointOptions,
+}
+
+impl RestoreCommand {
+    /// Restore the container. Unless detached, wait for it and return its
+    /// exit code.
+    fn execute(&self) -> Result<Option<i32>> {
+        if !self.detach {
+            // criu leaves the restored init behind as an orphan; adopt it.
+            container::set_child_subreaper()?;
+        }
+
+        let spec = specutil::load(&self.bundle)?;
+        let mut container = Container::new(&self.root, &self.container_id, &self.bundle, spec);
+        let pid = container.restore(&self.options)?;
+        if let Some(pid_file) = &self.pid_file {
+            fs::write(pid_file, pid.to_string())?;
+        }
+        if self.detach {
+            return Ok(None);
+        }
+
+        let exit_code = container.wait()?;
+        container.delete()?;
+        Ok(Some(exit_code))
+    }
+}
+
+impl SubCommandImpl for RestoreCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches
+            .value_of("container-id")
+            .expect("container-id must be specify:");
+        let bundle = PathBuf::from(matches.value_of("bundle").unwrap_or(".")).canonicalize()?;
+
+        Ok(RestoreCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+            bundle,
+            pid_file: matches.value_of("pid-file").map(PathBuf::from),
+            detach: matches.is_present("detach"),
+            options: checkpoint_options(matches),
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        if let Some(exit_code) = self.execute()? {
+            std::process::exit(exit_code);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use uuid::Uuid;
+
+    use crate::cli::app_config;
+    use crate::container::{testutil, MetadataManager};
+
+    #[test]
+    fn options_should_be_parsed() {
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let args = vec![
+            "runt",
+            "restore",
+            "--image-path",
+            "/images",
+            "--work-path",
+            "/work",
+            "--bundle",
+            bundle.to_str().unwrap(),
+            "--detach",
+            "container-a",
+        ];
+        let app_matches = app_config()
+            .get_matches_from_safe(testutil::args_with_root(&args))
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+        let restore_command = match app_matches.subcommand() {
+            ("restore", Some(matches)) => Some(RestoreCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(restore_command.bundle, bundle);
+        assert!(restore_command.detach);
+        assert_eq!(restore_command.options.image_path, PathBuf::from("/images"));
+        assert_eq!(
+            restore_command.options.work_path,
+            Some(PathBuf::from("/work"))
+        );
+        testutil::cleanup(&[&bundle]).unwrap();
+    }
+
+    #[test]
+    fn existing_container_should_not_be_overwritten() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let container = Container::new(
+            testutil::TEST_ROOT.as_ref(),
+            &container_id,
+            &bundle,
+            specutil::load(&bundle).unwrap(),
+        );
+        container.save_metadata(&container).unwrap();
+
+        let restore_command = RestoreCommand {
+            container_id: container_id.clone(),
+            root: testutil::TEST_ROOT.into(),
+            bundle: bundle.clone(),
+            pid_file: None,
+            detach: true,
+            options: CheckpointOptions::default(),
+        };
+        assert!(restore_command.execute().is_err());
+        assert!(Container::load(testutil::TEST_ROOT.as_ref(), &container_id).is_ok());
+
+        container.remove_metadata().unwrap();
+        testutil::cleanup(&[&bundle]).unwrap();
+    }
+}
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ArgMatches;
+
+use crate::config;
+use crate::container::{Container, MetadataManager};
+use crate::subcommand::SubCommandImpl;
+
+pub struct ResumeCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+}
+
+impl SubCommandImpl for ResumeCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches
+            .value_of("container-id")
+            .expect("container-id must be specify:");
+
+        Ok(ResumeCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        let mut container = Container::load(&self.root, &self.container_id)?;
+        container.refresh_status()?;
+        container.resume()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ArgMatches;
+
+use crate::config;
+use crate::container::{self, Container};
+use crate::specutil;
+use crate::subcommand::SubCommandImpl;
+
+pub struct RunCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+    pub bundle: PathBuf,
+    pub pid_file: Option<PathBuf>,
+    #[allow(dead_code)]
+    pub console_socket: Option<PathBuf>,
+    pub no_pivot: bool,
+    pub ignore_unknown_caps: bool,
+    pub detach: bool,
+    pub keep: bool,
+}
+
+impl RunCommand {
+    /// Create and start the container. Unless detached, wait for it and
+    /// return its exit code.
+    fn execute(&self) -> Result<Option<i32>> {
+        if !self.detach {
+            // The init is forked by an intermediate child which exits.
+            container::set_child_subreaper()?;
+        }
+        let spec = specutil::load(&self.bundle)?;
+        let mut container = Container::new(&self.root, &self.container_id, &self.bundle, spec);
+        container.no_pivot = self.no_pivot;
+        container.ignore_unknown_caps = self.ignore_unknown_caps;
+        container.create()?;
+
+        match self.start(&mut container) {
+            Ok(None) => Ok(None),
+            Ok(Some(exit_code)) => {
+                if !self.keep {
+                    container.delete()?;
+                }
+                Ok(Some(exit_code))
+            }
+            Err(e) => {
+                // Don't leave a container nobody asked to keep behind.
+                if !self.keep {
+                    if let Err(e) = container.delete() {
+                        eprintln!("failed to delete container {}: {}", self.container_id, e);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Start the created container and, unless detached, wait for it.
+    fn start(&self, container: &mut Container) -> Result<Option<i32>> {
+        if let (Some(pid_file), Some(pid)) = (&self.pid_file, container.pid) {
+            fs::write(pid_file, pid.to_string())?;
+        }
+
+        container.start()?;
+        if self.detach {
+            return Ok(None);
+        }
+        Ok(Some(container.wait()?))
+    }
+}
+
+impl SubCommandImpl for RunCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches.value_of("container-id").unwrap();
+        let bundle = PathBuf::from(matches.value_of("bundle").unwrap_or(".")).canonicalize()?;
+        let pid_file = matches.value_of("pid-file").map(PathBuf::from);
+        let console_socket = matches.value_of("console-socket").map(PathBuf::from);
+        Ok(RunCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+            bundle,
+            pid_file,
+            console_socket,
+            no_pivot: matches.is_present("no-pivot"),
+            ignore_unknown_caps: matches.is_present("ignore-unknown-caps"),
+            detach: matches.is_present("detach"),
+            keep: matches.is_present("keep"),
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        if let Some(exit_code) = self.execute()? {
+            std::process::exit(exit_code);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use uuid::Uuid;
+
+    use crate::cli::app_config;
+    use crate::container::specs::Status;
+    use crate::container::{testutil, MetadataManager};
+
+    fn init_run_command(args: Vec<&str>) -> RunCommand {
+        let app_matches = app_config()
+            .get_matches_from_safe(testutil::args_with_root(&args))
+            .expect("arg matche err: ");
+
+        match app_matches.subcommand() {
+            ("run", Some(matches)) => Some(RunCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn flags_should_be_parsed() {
+        let container_id = Uuid::new_v4().to_string();
+        let args = vec!["runt", "run", "-d", "--keep", &container_id];
+
+        let run_command = init_run_command(args);
+
+        assert_eq!(run_command.container_id, container_id);
+        assert!(run_command.detach);
+        assert!(run_command.keep);
+    }
+
+    #[test]
+    fn stopped_container_should_be_kept() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        spec.process.as_mut().unwrap().args = vec!["sh".into(), "-c".into(), "exit 1".into()];
+        specutil::write(&bundle, &spec).unwrap();
+
+        let args = vec![
+            "runt",
+            "run",
+            "--keep",
+            "--bundle",
+            bundle.to_str().unwrap(),
+            &container_id,
+        ];
+
+        let run_command = init_run_command(args);
+
+        let exit_code = run_command.execute().unwrap();
+        assert_eq!(exit_code, Some(1));
+
+        let container = Container::load(testutil::TEST_ROOT.as_ref(), &container_id).unwrap();
+        assert_eq!(container.status, Status::Stopped);
+        assert_eq!(container.exit_code, Some(1));
+        assert!(container.delete().is_ok());
+
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn stopped_container_should_be_removed() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+
+        let args = vec![
+            "runt",
+            "run",
+            "--bundle",
+            bundle.to_str().unwrap(),
+            &container_id,
+        ];
+
+        let run_command = init_run_command(args);
+
+        assert!(run_command.execute().unwrap().is_some());
+        assert!(!meta_dir.exists());
+
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn failed_container_should_be_removed() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let pid_file = bundle.join("nonexistent/pid");
+
+        let args = vec![
+            "runt",
+            "run",
+            "--pid-file",
+            pid_file.to_str().unwrap(),
+            "--bundle",
+            bundle.to_str().unwrap(),
+            &container_id,
+        ];
+
+        let run_command = init_run_command(args);
+
+        assert!(run_command.execute().is_err());
+        assert!(!meta_dir.exists());
+
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+}
@@ -2,6 +2,7 @@ use std::path::PathBuf;
 
 use anyhow::Result;
 use clap::ArgMatches;
+use nix::unistd::{getegid, geteuid};
 
 use crate::container::specs::Spec;
 use crate::specutil;
@@ -9,15 +10,25 @@ use crate::subcommand::SubCommandImpl;
 
 pub struct SpecCommand {
     pub bundle: PathBuf,
+    /// Map the current user to root in a user namespace.
+    pub rootless: bool,
 }
 
 impl SubCommandImpl for SpecCommand {
     fn new(matches: &ArgMatches) -> Result<Self> {
         let bundle = PathBuf::from(matches.value_of("bundle").unwrap_or(".")).canonicalize()?;
-        Ok(SpecCommand { bundle })
+        Ok(SpecCommand {
+            bundle,
+            rootless: matches.is_present("rootless"),
+        })
     }
     fn run(&self) -> Result<()> {
-        specutil::write(&self.bundle, &Spec::default())?;
+        let spec = if self.rootless {
+            Spec::rootless(geteuid().as_raw(), getegid().as_raw())
+        } else {
+            Spec::default()
+        };
+        specutil::write(&self.bundle, &spec)?;
         Ok(())
     }
 }
@@ -95,4 +106,27 @@ mod test {
 
         assert!(bundle.join(SPEC_FILE).exists())
     }
+
+    #[test]
+    fn rootless_spec_should_map_current_user() {
+        let tempdir = tempdir().unwrap();
+        let bundle = tempdir.path();
+        let args = vec!["runt", "spec", "--rootless", "-b", bundle.to_str().unwrap()];
+
+        let app_matches = app_config()
+            .get_matches_from_safe(&args)
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+        let subcommand = match app_matches.subcommand() {
+            ("spec", Some(matches)) => Some(SpecCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+        .unwrap();
+        assert!(subcommand.rootless);
+        subcommand.run().unwrap();
+
+        let spec = specutil::load(bundle).unwrap();
+        let uid_mappings = spec.linux.unwrap().uid_mappings.unwrap();
+        assert_eq!(uid_mappings[0].host_id, geteuid().as_raw());
+    }
 }
@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ArgMatches;
+
+use crate::config;
+use crate::container::{Container, MetadataManager};
+use crate::subcommand::SubCommandImpl;
+
+pub struct StartCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+}
+
+impl SubCommandImpl for StartCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches
+            .value_of("container-id")
+            .expect("container-id must be specify:");
+
+        Ok(StartCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        let mut container = Container::load(&self.root, &self.container_id)?;
+        container.start()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use crate::cli::app_config;
+    use crate::container::specs::Status;
+    use crate::container::testutil;
+    use crate::specutil;
+
+    fn init_start_command(container_id: &str) -> StartCommand {
+        let args = vec!["runt", "start", container_id];
+
+        let app_matches = app_config()
+            .get_matches_from_safe(testutil::args_with_root(&args))
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+
+        match app_matches.subcommand() {
+            ("start", Some(matches)) => Some(StartCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn should_be_container_started() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let spec = specutil::load(&bundle).unwrap();
+
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        assert!(container.create().is_ok());
+
+        let subcommand = init_start_command(&container_id);
+        assert!(subcommand.run().is_ok());
+
+        let loaded_container =
+            Container::load(testutil::TEST_ROOT.as_ref(), &container_id).unwrap();
+        assert_eq!(loaded_container.status, Status::Running);
+
+        // start is only allowed once
+        assert!(subcommand.run().is_err());
+        assert!(loaded_container.delete().is_ok());
+
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+
+    #[test]
+    fn missing_container_should_not_be_started() {
+        let container_id = Uuid::new_v4().to_string();
+
+        let subcommand = init_start_command(&container_id);
+        assert!(subcommand.run().is_err());
+    }
+}
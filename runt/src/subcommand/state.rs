@@ -1,12 +1,16 @@
+use std::path::PathBuf;
+
 use anyhow::Result;
 use clap::ArgMatches;
 
+use crate::config;
 use crate::container::specs::State;
 use crate::container::{Container, MetadataManager};
 use crate::subcommand::SubCommandImpl;
 
 pub struct StateCommand {
     pub container_id: String,
+    pub root: PathBuf,
 }
 
 impl SubCommandImpl for StateCommand {
@@ -17,11 +21,13 @@ impl SubCommandImpl for StateCommand {
 
         Ok(StateCommand {
             container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
         })
     }
     // TODO: Integrate testing along each scenario
     fn run(&self) -> Result<()> {
-        let container = Container::load(&self.container_id)?;
+        let mut container = Container::load(&self.root, &self.container_id)?;
+        container.refresh_status()?;
         let state: State = container.state()?;
         println!("{}", serde_json::to_string_pretty(&state)?);
         Ok(())
@@ -35,6 +41,7 @@ mod test {
     use uuid::Uuid;
 
     use crate::cli::app_config;
+    use crate::container::testutil;
 
     #[test]
     fn state_command_shuould_be_successfull() {
@@ -42,7 +49,7 @@ mod test {
         let args = vec!["runt", "state", &container_id];
 
         let app_matches = app_config()
-            .get_matches_from_safe(&args)
+            .get_matches_from_safe(testutil::args_with_root(&args))
             .unwrap_or_else(|e| panic!("An error occurs: {}", e));
 
         let subcommand = match app_matches.subcommand() {
@@ -0,0 +1,243 @@
+use std::fs::File;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+use clap::ArgMatches;
+
+use crate::config;
+use crate::container::resources;
+use crate::container::specs::{LinuxBlockIO, LinuxCPU, LinuxMemory, LinuxPids, LinuxResources};
+use crate::container::{Container, MetadataManager};
+use crate::subcommand::SubCommandImpl;
+
+pub struct UpdateCommand {
+    pub container_id: String,
+    pub root: PathBuf,
+    pub resources: LinuxResources,
+}
+
+/// Parse sizes such as `512m` or `1G` into bytes. `-1` means unlimited.
+fn parse_bytes(size: &str) -> Result<i64> {
+    if size == "-1" {
+        return Ok(-1);
+    }
+    let lower = size.to_lowercase();
+    let number = lower.trim_end_matches('b');
+    let split = number
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(number.len());
+    let (value, unit) = number.split_at(split);
+    let value: i64 = value
+        .parse()
+        .map_err(|_| anyhow!("invalid size: {}", size))?;
+    let shift = match unit {
+        "" => 0,
+        "k" => 10,
+        "m" => 20,
+        "g" => 30,
+        "t" => 40,
+        _ => bail!("invalid size: {}", size),
+    };
+    value
+        .checked_mul(1 << shift)
+        .ok_or_else(|| anyhow!("size is too large: {}", size))
+}
+
+fn parse_value<T: std::str::FromStr>(matches: &ArgMatches, name: &str) -> Result<Option<T>> {
+    matches
+        .value_of(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| anyhow!("invalid value for --{}: {}", name, value))
+        })
+        .transpose()
+}
+
+fn parse_size(matches: &ArgMatches, name: &str) -> Result<Option<i64>> {
+    matches.value_of(name).map(parse_bytes).transpose()
+}
+
+/// Resources set by the individual flags.
+fn resources_from_flags(matches: &ArgMatches) -> Result<LinuxResources> {
+    let memory = LinuxMemory {
+        limit: parse_size(matches, "memory")?,
+        reservation: parse_size(matches, "memory-reservation")?,
+        swap: parse_size(matches, "memory-swap")?,
+        ..LinuxMemory::default()
+    };
+    let cpu = LinuxCPU {
+        shares: parse_value(matches, "cpu-shares")?,
+        quota: parse_value(matches, "cpu-quota")?,
+        period: parse_value(matches, "cpu-period")?,
+        cpus: matches.value_of("cpuset-cpus").map(String::from),
+        mems: matches.value_of("cpuset-mems").map(String::from),
+        ..LinuxCPU::default()
+    };
+    let block_io = LinuxBlockIO {
+        weight: parse_value(matches, "blkio-weight")?,
+        ..LinuxBlockIO::default()
+    };
+    let pids = parse_value(matches, "pids-limit")?.map(|limit| LinuxPids { limit });
+
+    let has_memory =
+        memory.limit.is_some() || memory.reservation.is_some() || memory.swap.is_some();
+    let has_cpu = cpu.shares.is_some()
+        || cpu.quota.is_some()
+        || cpu.period.is_some()
+        || cpu.cpus.is_some()
+        || cpu.mems.is_some();
+    Ok(LinuxResources {
+        memory: if has_memory { Some(memory) } else { None },
+        cpu: if has_cpu { Some(cpu) } else { None },
+        block_io: block_io.weight.map(|_| block_io),
+        pids,
+        ..LinuxResources::default()
+    })
+}
+
+impl SubCommandImpl for UpdateCommand {
+    fn new(matches: &ArgMatches) -> Result<Self> {
+        let container_id = matches
+            .value_of("container-id")
+            .expect("container-id must be specify:");
+
+        let mut resources = match matches.value_of("resources").map(PathBuf::from) {
+            Some(path) => serde_json::from_reader(File::open(path)?)?,
+            None => LinuxResources::default(),
+        };
+        // Flags take precedence over the resources file.
+        resources::merge(&mut resources, &resources_from_flags(matches)?);
+
+        Ok(UpdateCommand {
+            container_id: container_id.into(),
+            root: config::state_root(matches.value_of("root"))?,
+            resources,
+        })
+    }
+
+    fn run(&self) -> Result<()> {
+        let mut container = Container::load(&self.root, &self.container_id)?;
+        container.refresh_status()?;
+        container.update(&self.resources)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+
+    use uuid::Uuid;
+
+    use crate::cli::app_config;
+    use crate::container::testutil;
+    use crate::specutil;
+
+    fn init_update_command(args: Vec<&str>) -> Result<UpdateCommand> {
+        let app_matches = app_config()
+            .get_matches_from_safe(testutil::args_with_root(&args))
+            .unwrap_or_else(|e| panic!("An error occurs: {}", e));
+
+        match app_matches.subcommand() {
+            ("update", Some(matches)) => Some(UpdateCommand::new(matches)),
+            _ => None,
+        }
+        .unwrap()
+    }
+
+    #[test]
+    fn size_should_be_parsed() {
+        assert_eq!(parse_bytes("1024").unwrap(), 1024);
+        assert_eq!(parse_bytes("4k").unwrap(), 4096);
+        assert_eq!(parse_bytes("512m").unwrap(), 512 << 20);
+        assert_eq!(parse_bytes("2GB").unwrap(), 2 << 30);
+        assert_eq!(parse_bytes("-1").unwrap(), -1);
+        assert!(parse_bytes("12x").is_err());
+        assert!(parse_bytes("m").is_err());
+    }
+
+    #[test]
+    fn flags_should_override_resources_file() {
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let resources_file = bundle.join("resources.json");
+        fs::write(
+            &resources_file,
+            r#"{"memory":{"limit":1048576,"reservation":524288},"pids":{"limit":10}}"#,
+        )
+        .unwrap();
+
+        let update_command = init_update_command(vec![
+            "runt",
+            "update",
+            "-r",
+            resources_file.to_str().unwrap(),
+            "--memory",
+            "2m",
+            "--cpu-quota",
+            "50000",
+            "container-a",
+        ])
+        .unwrap();
+
+        let resources = update_command.resources;
+        let memory = resources.memory.unwrap();
+        assert_eq!(memory.limit, Some(2 << 20));
+        assert_eq!(memory.reservation, Some(524288));
+        assert_eq!(resources.cpu.unwrap().quota, Some(50000));
+        assert_eq!(resources.pids.unwrap().limit, 10);
+        assert!(resources.block_io.is_none());
+
+        testutil::cleanup(&[&bundle]).unwrap();
+    }
+
+    #[test]
+    fn invalid_flags_should_be_rejected() {
+        assert!(init_update_command(vec!["runt", "update", "--pids-limit", "a", "c"]).is_err());
+        assert!(init_update_command(vec!["runt", "update", "--memory", "1x", "c"]).is_err());
+    }
+
+    #[test]
+    fn cgroup_and_spec_should_be_updated() {
+        let container_id = Uuid::new_v4().to_string();
+        let bundle = testutil::init_bundle_dir().unwrap();
+        let rootfs = testutil::init_rootfs_dir(&bundle).unwrap();
+        testutil::init_spec_file(&bundle, &rootfs).unwrap();
+        let mut spec = specutil::load(&bundle).unwrap();
+        spec.process.as_mut().unwrap().args = vec!["/bin/sleep".into(), "10".into()];
+        let meta_dir = PathBuf::from(testutil::TEST_ROOT).join(&container_id);
+
+        let mut container =
+            Container::new(testutil::TEST_ROOT.as_ref(), &container_id, &bundle, spec);
+        container.create().unwrap();
+        container.start().unwrap();
+
+        let update_command = init_update_command(vec![
+            "runt",
+            "update",
+            "--pids-limit",
+            "42",
+            "--memory",
+            "64m",
+            &container_id,
+        ])
+        .unwrap();
+        update_command.run().unwrap();
+
+        let stats = container.cgroup().unwrap().stats().unwrap();
+        assert_eq!(stats.pids.limit, 42);
+        assert_eq!(stats.memory.usage.limit, 64 << 20);
+
+        let updated = Container::load(testutil::TEST_ROOT.as_ref(), &container_id).unwrap();
+        let resources = updated.spec.linux.unwrap().resources.unwrap();
+        assert_eq!(resources.pids.unwrap().limit, 42);
+        assert_eq!(resources.memory.unwrap().limit, Some(64 << 20));
+        // The devices from the original spec are kept.
+        assert!(resources.devices.is_some());
+
+        assert!(container.delete().is_ok());
+        testutil::cleanup(&[&bundle, &meta_dir]).unwrap();
+    }
+}